@@ -0,0 +1,282 @@
+//! Cost of room fan-out with the local room index against the per-message redis lookups it
+//! replaced.
+//!
+//! Run with `cargo bench --bench room_fanout`. Every room size is measured on an index shaped
+//! like a busy node: each user has a few sessions, and every session sits in a few other rooms
+//! besides the measured one.
+//!
+//! The redis path reads an in-memory copy of the keys it used to query, so its CPU work is
+//! measured and every command it would send is charged `REDIS_ROUND_TRIP`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use communication_service::ws::{room_index::RoomIndex, ConnId, ConversationId, UserId};
+use uuid::Uuid;
+
+type LocalRoomIndex = RoomIndex<ConversationId, ConnId, UserId>;
+
+const ROOM_ID: ConversationId = 1;
+const OTHER_ROOMS: [ConversationId; 2] = [2, 3];
+const SESSIONS_PER_USER: usize = 2;
+const ITERATIONS: u32 = 1_000;
+const REDIS_ITERATIONS: u32 = 100;
+
+// one redis command from a pod to a redis in the same cluster
+const REDIS_ROUND_TRIP: Duration = Duration::from_micros(100);
+
+// the keys read by the redis fan-out: `room:{id}:active_users` and `user:{id}:sessions`
+struct RedisKeys {
+    active_users: HashMap<ConversationId, Vec<String>>,
+    user_sessions: HashMap<String, HashMap<String, String>>,
+}
+
+impl RedisKeys {
+    fn smembers(&self, conversation_id: ConversationId) -> Vec<String> {
+        self.active_users
+            .get(&conversation_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn hgetall(&self, user_id: &str) -> HashMap<String, String> {
+        self.user_sessions.get(user_id).cloned().unwrap_or_default()
+    }
+}
+
+struct Setup {
+    index: LocalRoomIndex,
+    redis: RedisKeys,
+    // connections registered on this node
+    local_conns: HashSet<ConnId>,
+    // (user, connection) of every session in the room
+    sessions: Vec<(UserId, ConnId)>,
+}
+
+fn setup(participants: usize) -> Setup {
+    let mut index = LocalRoomIndex::new();
+    let mut redis = RedisKeys {
+        active_users: HashMap::new(),
+        user_sessions: HashMap::new(),
+    };
+    let mut local_conns = HashSet::new();
+    let mut sessions = Vec::with_capacity(participants * SESSIONS_PER_USER);
+
+    let rooms: Vec<String> = std::iter::once(ROOM_ID)
+        .chain(OTHER_ROOMS)
+        .map(|room_id| room_id.to_string())
+        .collect();
+    let session_state = serde_json::json!({ "active_rooms": rooms, "node": "bench" }).to_string();
+
+    for _ in 0..participants {
+        let user_id = Uuid::new_v4();
+
+        for _ in 0..SESSIONS_PER_USER {
+            let conn_id = Uuid::new_v4();
+
+            index.connect(user_id, conn_id);
+            index.join(ROOM_ID, conn_id, user_id);
+            for room_id in OTHER_ROOMS {
+                index.join(room_id, conn_id, user_id);
+            }
+
+            redis
+                .user_sessions
+                .entry(user_id.to_string())
+                .or_default()
+                .insert(conn_id.to_string(), session_state.clone());
+            local_conns.insert(conn_id);
+            sessions.push((user_id, conn_id));
+        }
+
+        for room_id in std::iter::once(ROOM_ID).chain(OTHER_ROOMS) {
+            redis
+                .active_users
+                .entry(room_id)
+                .or_default()
+                .push(user_id.to_string());
+        }
+    }
+
+    Setup {
+        index,
+        redis,
+        local_conns,
+        sessions,
+    }
+}
+
+// the fan-out before the room index: the room's active users, then the sessions of each of
+// them, keeping the local ones that joined the room. Returns the connections and the number
+// of redis commands sent
+fn redis_fanout(
+    redis: &RedisKeys,
+    local_conns: &HashSet<ConnId>,
+    conversation_id: ConversationId,
+) -> (Vec<ConnId>, u32) {
+    let room = conversation_id.to_string();
+    let mut round_trips = 1;
+    let mut conns = Vec::new();
+
+    for user_id in redis.smembers(conversation_id) {
+        round_trips += 1;
+        for (conn_id, session_state) in redis.hgetall(&user_id) {
+            let in_room = serde_json::from_str::<serde_json::Value>(&session_state)
+                .ok()
+                .and_then(|value| {
+                    value["active_rooms"]
+                        .as_array()
+                        .map(|rooms| rooms.iter().any(|r| r.as_str() == Some(room.as_str())))
+                })
+                .unwrap_or_default();
+            if !in_room {
+                continue;
+            }
+
+            if let Ok(conn_id) = Uuid::parse_str(&conn_id) {
+                if local_conns.contains(&conn_id) {
+                    conns.push(conn_id);
+                }
+            }
+        }
+    }
+
+    (conns, round_trips)
+}
+
+// average time of one call of `f`
+fn time<F: FnMut()>(iterations: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
+
+fn per_second(per_message: Duration) -> f64 {
+    1.0 / per_message.as_secs_f64()
+}
+
+fn main() {
+    println!(
+        "room fan-out per message, redis commands charged {:?} each",
+        REDIS_ROUND_TRIP
+    );
+    println!(
+        "{:>12} {:>12} {:>12} {:>14} {:>14} {:>14} {:>14} {:>10}",
+        "participants",
+        "redis cmds",
+        "redis cpu",
+        "redis total",
+        "index",
+        "redis msg/s",
+        "index msg/s",
+        "gain"
+    );
+
+    let room_sizes = [100, 250, 500, 1000];
+
+    for participants in room_sizes {
+        let Setup {
+            index,
+            redis,
+            local_conns,
+            ..
+        } = setup(participants);
+
+        let (redis_conns, round_trips) = redis_fanout(&redis, &local_conns, ROOM_ID);
+        let index_conns = index.room_connections(ROOM_ID, None);
+        assert_eq!(redis_conns.len(), participants * SESSIONS_PER_USER);
+        assert_eq!(
+            redis_conns.into_iter().collect::<HashSet<_>>(),
+            index_conns.into_iter().collect::<HashSet<_>>()
+        );
+
+        let redis_cpu = time(REDIS_ITERATIONS, || {
+            black_box(redis_fanout(&redis, &local_conns, black_box(ROOM_ID)));
+        });
+        let redis_total = redis_cpu + REDIS_ROUND_TRIP * round_trips;
+
+        let index_fanout = time(ITERATIONS, || {
+            black_box(index.room_connections(black_box(ROOM_ID), None));
+        });
+
+        println!(
+            "{:>12} {:>12} {:>12?} {:>14?} {:>14?} {:>14.0} {:>14.0} {:>9.0}x",
+            participants,
+            round_trips,
+            redis_cpu,
+            redis_total,
+            index_fanout,
+            per_second(redis_total),
+            per_second(index_fanout),
+            redis_total.as_secs_f64() / index_fanout.as_secs_f64()
+        );
+    }
+
+    println!();
+    println!("room index operations");
+    println!(
+        "{:>12} {:>14} {:>14} {:>14} {:>14} {:>14}",
+        "participants", "fanout", "fanout skip", "user conns", "leave+join", "reconnect"
+    );
+
+    for participants in room_sizes {
+        let Setup {
+            mut index,
+            sessions,
+            ..
+        } = setup(participants);
+        assert_eq!(
+            index.room_connections(ROOM_ID, None).len(),
+            participants * SESSIONS_PER_USER
+        );
+
+        let (sender_id, _) = sessions[0];
+        let mut next = 0;
+
+        // a room message is delivered to every local connection of the room
+        let fanout = time(ITERATIONS, || {
+            black_box(index.room_connections(black_box(ROOM_ID), None));
+        });
+
+        // typing and receipts skip the connections of the user who caused them
+        let fanout_skip = time(ITERATIONS, || {
+            black_box(index.room_connections(black_box(ROOM_ID), Some(sender_id)));
+        });
+
+        // inbox events go to every local connection of a user
+        let user_conns = time(ITERATIONS, || {
+            let (user_id, _) = sessions[next % sessions.len()];
+            next += 1;
+            black_box(index.user_connections(black_box(user_id)));
+        });
+
+        let leave_join = time(ITERATIONS, || {
+            let (user_id, conn_id) = sessions[next % sessions.len()];
+            next += 1;
+            black_box(index.leave(ROOM_ID, conn_id));
+            index.join(ROOM_ID, conn_id, user_id);
+        });
+
+        // a dropped session leaves every room it was in, then comes back
+        let reconnect = time(ITERATIONS, || {
+            let (user_id, conn_id) = sessions[next % sessions.len()];
+            next += 1;
+            black_box(index.disconnect(user_id, conn_id));
+            index.connect(user_id, conn_id);
+            index.join(ROOM_ID, conn_id, user_id);
+            for room_id in OTHER_ROOMS {
+                index.join(room_id, conn_id, user_id);
+            }
+        });
+
+        println!(
+            "{:>12} {:>14?} {:>14?} {:>14?} {:>14?} {:>14?}",
+            participants, fanout, fanout_skip, user_conns, leave_join, reconnect
+        );
+    }
+}
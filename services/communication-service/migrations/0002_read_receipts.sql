@@ -0,0 +1,23 @@
+ALTER TABLE users_conversations
+ADD COLUMN last_read_message_id BIGINT,
+ADD COLUMN last_delivered_message_id BIGINT;
+
+-- carry the old conversation-wide flag over to every participant
+UPDATE users_conversations uc
+SET
+    last_read_message_id = (
+        SELECT MAX(m.message_id)
+        FROM messages m
+        WHERE
+            m.conversation_id = uc.conversation_id
+            AND (
+                m.is_read = TRUE
+                OR m.sender_id = uc.user_id
+            )
+    );
+
+UPDATE users_conversations
+SET
+    last_delivered_message_id = last_read_message_id;
+
+ALTER TABLE messages DROP COLUMN is_read;
@@ -0,0 +1,13 @@
+ALTER TABLE messages ADD COLUMN edited_at TIMESTAMPTZ;
+
+CREATE TABLE message_edits (
+    edit_id BIGSERIAL PRIMARY KEY,
+    message_id BIGINT NOT NULL,
+    previous_content TEXT,
+    edited_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE message_edits
+ADD CONSTRAINT edit_msg FOREIGN KEY (message_id) REFERENCES messages (message_id);
+
+CREATE INDEX idx_message_edits_message_id ON message_edits (message_id);
@@ -0,0 +1,12 @@
+ALTER TABLE messages ADD COLUMN deleted_at TIMESTAMPTZ;
+
+-- messages a participant removed from their own view only
+CREATE TABLE hidden_messages (
+    message_id BIGINT NOT NULL,
+    user_id UUID NOT NULL,
+    hidden_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (message_id, user_id)
+);
+
+ALTER TABLE hidden_messages
+ADD CONSTRAINT hidden_msg FOREIGN KEY (message_id) REFERENCES messages (message_id);
@@ -0,0 +1,10 @@
+CREATE TABLE message_reactions (
+    message_id BIGINT NOT NULL,
+    user_id UUID NOT NULL,
+    emoji TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (message_id, user_id, emoji)
+);
+
+ALTER TABLE message_reactions
+ADD CONSTRAINT reaction_msg FOREIGN KEY (message_id) REFERENCES messages (message_id);
@@ -0,0 +1,6 @@
+ALTER TABLE messages ADD COLUMN reply_to_message_id BIGINT;
+
+ALTER TABLE messages
+ADD CONSTRAINT reply_msg FOREIGN KEY (reply_to_message_id) REFERENCES messages (message_id);
+
+CREATE INDEX idx_messages_reply_to_message_id ON messages (reply_to_message_id);
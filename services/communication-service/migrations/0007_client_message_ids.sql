@@ -0,0 +1,5 @@
+-- id generated by the client, used to dedupe retried sends
+ALTER TABLE messages ADD COLUMN client_message_id TEXT;
+
+ALTER TABLE messages
+ADD CONSTRAINT unique_client_message UNIQUE (sender_id, client_message_id);
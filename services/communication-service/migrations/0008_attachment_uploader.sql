@@ -0,0 +1,2 @@
+-- attachments are uploaded before the media message that links them is sent
+ALTER TABLE attachments ADD COLUMN uploader_id UUID;
@@ -0,0 +1,5 @@
+-- server generated messages (participant changes, renames, notes from other services)
+ALTER TABLE messages DROP CONSTRAINT messages_type_check;
+
+ALTER TABLE messages
+ADD CONSTRAINT messages_type_check CHECK (type IN ('Message', 'Media', 'System'));
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- live-stream rooms are public, viewers join without a users_conversations row
+ALTER TABLE conversations
+ADD COLUMN kind TEXT NOT NULL DEFAULT 'group' CHECK (kind IN ('group', 'live'));
+
+-- minimum seconds between two messages of the same user, 0 disables slow mode
+ALTER TABLE conversations
+ADD COLUMN slow_mode_secs INT NOT NULL DEFAULT 0 CHECK (slow_mode_secs >= 0);
\ No newline at end of file
@@ -0,0 +1,11 @@
+-- role of a participant in a group, every group has at most one owner
+ALTER TABLE users_conversations
+ADD COLUMN role TEXT NOT NULL DEFAULT 'member' CHECK (role IN ('owner', 'admin', 'member'));
+
+CREATE UNIQUE INDEX users_conversations_owner_idx ON users_conversations (conversation_id)
+WHERE
+    role = 'owner';
+
+ALTER TABLE conversations ADD COLUMN description TEXT;
+
+ALTER TABLE conversations ADD COLUMN avatar_url TEXT;
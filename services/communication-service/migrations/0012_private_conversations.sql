@@ -0,0 +1,61 @@
+-- private conversations between exactly two users
+ALTER TABLE conversations DROP CONSTRAINT conversations_kind_check;
+
+ALTER TABLE conversations
+ADD CONSTRAINT conversations_kind_check CHECK (
+    kind IN ('group', 'live', 'private')
+);
+
+-- "<lower user id>:<higher user id>" of a private conversation, the same for either user order
+ALTER TABLE conversations ADD COLUMN pair_key TEXT;
+
+-- conversations opened between two users before this migration are groups created with their
+-- two participants in one go, neither of whom has left since. They become private conversations
+WITH pairs AS (
+    SELECT
+        uc.conversation_id,
+        MIN(uc.user_id::TEXT COLLATE "C") || ':' || MAX(uc.user_id::TEXT COLLATE "C") AS pair_key
+    FROM
+        users_conversations uc
+        JOIN conversations c ON c.conversation_id = uc.conversation_id
+    WHERE
+        c.kind = 'group'
+        AND c.slow_mode_secs = 0
+    GROUP BY
+        uc.conversation_id
+    HAVING
+        COUNT(*) = 2
+        AND BOOL_AND(uc.deleted_at IS NULL)
+        AND MAX(uc.id) - MIN(uc.id) = 1
+),
+-- a pair may have opened several of them, only the oldest one still open is found by its pair
+-- key. The others keep their history and stay out of the unique index
+ranked AS (
+    SELECT
+        pairs.conversation_id,
+        pairs.pair_key,
+        ROW_NUMBER() OVER (
+            PARTITION BY
+                pairs.pair_key
+            ORDER BY
+                COALESCE(c.is_deleted, FALSE),
+                c.conversation_id
+        ) AS pair_rank
+    FROM pairs
+        JOIN conversations c ON c.conversation_id = pairs.conversation_id
+)
+UPDATE conversations
+SET
+    kind = 'private',
+    pair_key = CASE
+        WHEN ranked.pair_rank = 1 THEN ranked.pair_key
+    END
+FROM ranked
+WHERE
+    conversations.conversation_id = ranked.conversation_id;
+
+-- a pair of users shares at most one private conversation, deleting it allows a new one
+CREATE UNIQUE INDEX conversations_pair_key_idx ON conversations (pair_key)
+WHERE
+    kind = 'private'
+    AND is_deleted = FALSE;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- existing groups are owned by their first participant, private conversations and live rooms
+-- are told apart from groups since the previous migration
+UPDATE users_conversations
+SET
+    role = 'owner'
+WHERE
+    id IN (
+        SELECT MIN(uc.id)
+        FROM
+            users_conversations uc
+            JOIN conversations c ON c.conversation_id = uc.conversation_id
+        WHERE
+            c.kind = 'group'
+            AND c.is_deleted = FALSE
+        GROUP BY
+            uc.conversation_id
+    );
\ No newline at end of file
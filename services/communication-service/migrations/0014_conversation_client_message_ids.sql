@@ -0,0 +1,10 @@
+-- client message ids are picked by the sender for one conversation, the same id may come back
+-- in another conversation
+ALTER TABLE messages DROP CONSTRAINT unique_client_message;
+
+ALTER TABLE messages
+ADD CONSTRAINT unique_client_message UNIQUE (
+    conversation_id,
+    sender_id,
+    client_message_id
+);
\ No newline at end of file
@@ -8,13 +8,18 @@ use crate::{
     redis_repositories::user_redis_repo::UserRedisRepo,
     repositories::{
         attachment_repo::AttachmentRepo, conversation_repo::ConversationRepo,
-        message_repo::MessageRepo,
+        message_repo::MessageRepo, reaction_repo::ReactionRepo,
     },
     services::{
         attachment_service::AttachmentService, convesation_service::ConversationService,
-        message_service::MessageService, user_service::UserService,
+        membership_guard::MembershipGuard, message_service::MessageService,
+        reaction_service::ReactionService, user_service::UserService,
+    },
+    ws::{
+        chat_server::{ChatServer, LiveRoomConfig, NodeConfig},
+        chat_server_handler::ChatServerHandler,
+        conn_queue::{QueueConfig, SlowConsumerPolicy},
     },
-    ws::{chat_server::ChatServer, chat_server_handler::ChatServerHandler},
 };
 
 pub struct AppState {
@@ -28,6 +33,7 @@ pub struct AppServices {
     pub attachment_service: Arc<AttachmentService>,
     pub conversation_service: Arc<ConversationService>,
     pub messages_service: Arc<MessageService>,
+    pub reaction_service: Arc<ReactionService>,
     pub user_service: Arc<UserService>,
 }
 
@@ -70,40 +76,164 @@ impl AppState {
         let conversation_repository = Arc::new(ConversationRepo::new(pg_pool.clone()));
         let message_repository = Arc::new(MessageRepo::new(pg_pool.clone()));
         let attachment_repository = Arc::new(AttachmentRepo::new(pg_pool.clone()));
-        let user_redis_repo = Arc::new(UserRedisRepo::new(redis_pool.clone()));
-
-        // init services
-        let conversation_service =
-            Arc::new(ConversationService::new(conversation_repository.clone()));
-        let messages_service = Arc::new(MessageService::new(
-            message_repository.clone(),
-            attachment_repository.clone(),
-        ));
-        let attachment_service = Arc::new(AttachmentService::new(
-            attachment_repository.clone(),
-            message_repository.clone(),
-        ));
-        let user_service = Arc::new(UserService::new(user_redis_repo.clone()));
+        let reaction_repository = Arc::new(ReactionRepo::new(pg_pool.clone()));
 
         // init redis client
         let redis_client = Arc::new(
             redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set")).unwrap(),
         );
 
+        let user_redis_repo =
+            Arc::new(UserRedisRepo::new(redis_pool.clone(), redis_client.clone()));
+
+        // time window in which a retried send with the same client message id is deduplicated
+        let client_message_dedupe_window = std::time::Duration::from_secs(
+            env::var("CLIENT_MESSAGE_DEDUPE_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(10 * 60),
+        );
+
+        // live-stream rooms keep every n-th message and limit how fast a viewer may send
+        let live_room_config = LiveRoomConfig {
+            sample_rate: env::var("LIVE_MESSAGE_SAMPLE_RATE")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(0),
+            rate_limit: env::var("LIVE_RATE_LIMIT_MESSAGES")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(5),
+            rate_limit_window: std::time::Duration::from_secs(
+                env::var("LIVE_RATE_LIMIT_WINDOW_SECS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .unwrap_or(10),
+            ),
+            viewer_count_interval: std::time::Duration::from_secs(
+                env::var("LIVE_VIEWER_COUNT_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .filter(|v| *v > 0)
+                    .unwrap_or(5),
+            ),
+        };
+
+        // chat nodes share redis, a crashed node's sessions are purged once its heartbeat expires.
+        // CHAT_NODE_ID should survive restarts so a restarted node cleans up after its previous
+        // run right away, HOSTNAME changes with every pod restart. Without either the node gets
+        // a fresh id and the sessions of its previous run wait for the reaper
+        let node_config = NodeConfig {
+            node_id: env::var("CHAT_NODE_ID")
+                .or_else(|_| env::var("HOSTNAME"))
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| {
+                    let node_id = uuid::Uuid::new_v4().to_string();
+                    log::warn!("CHAT_NODE_ID is not set, running as chat node {node_id}");
+                    node_id
+                }),
+            heartbeat_ttl: std::time::Duration::from_secs(
+                env::var("CHAT_NODE_HEARTBEAT_TTL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .filter(|v| *v > 0)
+                    .unwrap_or(15),
+            ),
+            reaper_interval: std::time::Duration::from_secs(
+                env::var("CHAT_NODE_REAPER_INTERVAL_SECS")
+                    .ok()
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .filter(|v| *v > 0)
+                    .unwrap_or(30),
+            ),
+        };
+
+        // room and inbox channels are multiplexed over this many pub/sub connections
+        let pubsub_connections = env::var("REDIS_PUBSUB_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(2);
+
+        // outgoing messages a connection may have queued, and what happens once a client falls behind
+        let queue_config = QueueConfig {
+            capacity: env::var("WS_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse::<usize>().ok())
+                .filter(|v| *v > 0)
+                .unwrap_or(256),
+            policy: env::var("WS_SLOW_CONSUMER_POLICY")
+                .ok()
+                .and_then(|v| v.parse::<SlowConsumerPolicy>().ok())
+                .unwrap_or(SlowConsumerPolicy::DropOldest),
+        };
+
         // init chat server
         let (chat_server, chat_server_handler) = ChatServer::new(
             redis_pool.clone(),
             redis_client.clone(),
             conversation_repository.clone(),
             message_repository.clone(),
+            reaction_repository.clone(),
             notification_service_client.clone(),
+            client_message_dedupe_window,
+            live_room_config,
+            node_config,
+            pubsub_connections,
+            queue_config,
         )
         .await;
 
+        // time window in which the sender may still edit a message
+        let message_edit_window = chrono::Duration::seconds(
+            env::var("MESSAGE_EDIT_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(15 * 60),
+        );
+
+        // time window in which the sender may still unsend a message for everyone
+        let message_unsend_window = chrono::Duration::seconds(
+            env::var("MESSAGE_UNSEND_WINDOW_SECS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(60 * 60),
+        );
+
+        // init services
+        let membership_guard = MembershipGuard::new(conversation_repository.clone());
+        let conversation_service = Arc::new(ConversationService::new(
+            conversation_repository.clone(),
+            membership_guard.clone(),
+            chat_server_handler.clone(),
+        ));
+        let messages_service = Arc::new(MessageService::new(
+            message_repository.clone(),
+            attachment_repository.clone(),
+            membership_guard.clone(),
+            chat_server_handler.clone(),
+            message_edit_window,
+            message_unsend_window,
+        ));
+        let attachment_service = Arc::new(AttachmentService::new(
+            attachment_repository.clone(),
+            message_repository.clone(),
+            membership_guard.clone(),
+        ));
+        let reaction_service = Arc::new(ReactionService::new(
+            reaction_repository.clone(),
+            message_repository.clone(),
+            membership_guard,
+            chat_server_handler.clone(),
+        ));
+        let user_service = Arc::new(UserService::new(user_redis_repo.clone()));
+
         let app_services = AppServices {
             attachment_service,
             conversation_service,
             messages_service,
+            reaction_service,
             user_service,
         };
 
@@ -1,11 +1,10 @@
 use actix_files::NamedFile;
 use actix_multipart::form::MultipartForm;
-use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
-use uuid::Uuid;
+use actix_web::{http::StatusCode, web, HttpResponse, Responder};
 
 use crate::{
     app::AppServices,
-    errors::Error,
+    middlewares::auth_middleware::AuthUser,
     models::{
         attachment::AttachmentParams, response_wrapper::ResponseWrapper, upload_form::UploadForm,
     },
@@ -42,13 +41,10 @@ impl AttachmentController {
 
     pub async fn upload_file(
         services: web::Data<AppServices>,
-        _req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         MultipartForm(form): MultipartForm<UploadForm>,
         path: web::Path<i32>,
     ) -> impl Responder {
-        // let user_id = req.extensions();
-        let user_id = Uuid::parse_str("c8dd591b-4105-4608-869b-1dfb96f313b3").unwrap();
-
         let conversation_id = path.into_inner();
 
         match services
@@ -63,20 +59,20 @@ impl AttachmentController {
 
     pub async fn get_file(
         services: web::Data<AppServices>,
-        _req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         path: web::Path<String>,
     ) -> Result<NamedFile, actix_web::Error> {
-        // let user_id = _req
         let attachment_path = path.into_inner();
 
         services
             .attachment_service
-            .get_file_by_url(&attachment_path)
+            .get_file_by_url(user_id, &attachment_path)
             .await
             .map_err(|e| e.into())
     }
 
     pub async fn get_attachment_by_id(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         path: web::Path<i32>,
     ) -> impl Responder {
@@ -84,9 +80,8 @@ impl AttachmentController {
 
         match services
             .attachment_service
-            .get_attachment_by_id(attachmet_id)
+            .get_attachment_by_id(user_id, attachmet_id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => match result {
                 Some(result) => {
@@ -103,6 +98,7 @@ impl AttachmentController {
     }
 
     pub async fn get_attachments_by_conversation_id(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         path: web::Path<i32>,
         query: web::Query<AttachmentParams>,
@@ -113,9 +109,8 @@ impl AttachmentController {
 
         match services
             .attachment_service
-            .get_attachments_by_conversation_id(conversation_id, before, limit)
+            .get_attachments_by_conversation_id(user_id, conversation_id, before, limit)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => {
                 ResponseWrapper::build(StatusCode::OK, "Attachments retrieved", Some(result))
@@ -125,6 +120,7 @@ impl AttachmentController {
     }
 
     pub async fn get_attachments_by_message_id(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         path: web::Path<i64>,
     ) -> impl Responder {
@@ -132,9 +128,8 @@ impl AttachmentController {
 
         match services
             .attachment_service
-            .get_attachment_by_message_id(message_id)
+            .get_attachment_by_message_id(user_id, message_id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => {
                 ResponseWrapper::build(StatusCode::OK, "Attachments retrieved", Some(result))
@@ -1,12 +1,16 @@
-use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
+use actix_web::{http::StatusCode, web, HttpResponse, Responder};
 use uuid::Uuid;
 
 use crate::{
     app::AppServices,
     errors::Error,
+    middlewares::auth_middleware::AuthUser,
     models::{
-        conversation::{MessageParams, NewConversation, NewPrivateConversation},
+        conversation::{
+            MessageParams, NewConversation, NewPrivateConversation, SyncParams, UpdateConversation,
+        },
         response_wrapper::ResponseWrapper,
+        user_conversation::{NewParticipants, TransferOwnership, UpdateParticipantRole},
         Pagination,
     },
 };
@@ -27,6 +31,10 @@ impl ConversationController {
                     "/{conversation_id}",
                     web::get().to(Self::get_conversation_by_id),
                 )
+                .route(
+                    "/{conversation_id}",
+                    web::patch().to(Self::update_conversation),
+                )
                 .route(
                     "/{conversation_id}",
                     web::delete().to(Self::delete_conversation),
@@ -35,14 +43,35 @@ impl ConversationController {
                     "/{conversation_id}/participants",
                     web::get().to(Self::get_conversation_participants),
                 )
+                .route(
+                    "/{conversation_id}/participants",
+                    web::post().to(Self::add_participants),
+                )
+                .route(
+                    "/{conversation_id}/participants/{user_id}",
+                    web::patch().to(Self::update_participant_role),
+                )
+                .route(
+                    "/{conversation_id}/participants/{user_id}",
+                    web::delete().to(Self::remove_participant),
+                )
+                .route(
+                    "/{conversation_id}/owner",
+                    web::post().to(Self::transfer_ownership),
+                )
                 .route(
                     "/{conversation_id}/messages",
                     web::get().to(Self::get_conversation_messages),
+                )
+                .route(
+                    "/{conversation_id}/sync",
+                    web::get().to(Self::sync_conversation_messages),
                 ),
         );
     }
 
     async fn get_conversation_by_id(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         id: web::Path<i32>,
     ) -> impl Responder {
@@ -50,9 +79,8 @@ impl ConversationController {
 
         match services
             .conversation_service
-            .get_conversation_by_id(id)
+            .get_conversation_by_id(user_id, id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => match result {
                 Some(result) => {
@@ -69,12 +97,14 @@ impl ConversationController {
     }
 
     async fn create_conversation(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         new_conversation: web::Json<NewConversation>,
     ) -> impl Responder {
+        // the creator owns the conversation
         match services
             .conversation_service
-            .create_conversation(&new_conversation.title)
+            .create_conversation(Some(user_id), &new_conversation)
             .await
             .map_err(|e| Error::Db(e))
         {
@@ -86,15 +116,16 @@ impl ConversationController {
     }
 
     async fn delete_conversation(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         conversation_id: web::Path<i32>,
     ) -> impl Responder {
         let id = conversation_id.into_inner();
+
         match services
             .conversation_service
-            .delete_conversation(id)
+            .delete_conversation(user_id, id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(_) => ResponseWrapper::<()>::build(StatusCode::OK, "Conversation deleted", None),
             Err(e) => HttpResponse::from_error(e),
@@ -102,26 +133,16 @@ impl ConversationController {
     }
 
     async fn get_conversation_participants(
-        req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         conversation_id: web::Path<i32>,
     ) -> impl Responder {
         let id: i32 = conversation_id.into_inner();
 
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return HttpResponse::Unauthorized().finish(),
-            },
-            None => return HttpResponse::Unauthorized().finish(),
-        };
-
         match services
             .conversation_service
             .get_user_conversation_participants(user_id, id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => {
                 ResponseWrapper::build(StatusCode::OK, "Participants retrieved", Some(result))
@@ -131,20 +152,11 @@ impl ConversationController {
     }
 
     async fn get_conversation_messages(
-        req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         conversation_id: web::Path<i32>,
         params: web::Query<MessageParams>,
     ) -> impl Responder {
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return HttpResponse::Unauthorized().finish(),
-            },
-            None => return HttpResponse::Unauthorized().finish(),
-        };
-
         let conversation_id = conversation_id.into_inner();
         let limit = params.limit;
         let before = params.before;
@@ -153,7 +165,6 @@ impl ConversationController {
             .conversation_service
             .get_conversation_messages(user_id, conversation_id, limit, before)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => {
                 ResponseWrapper::build(StatusCode::OK, "Messages retrieved", Some(result))
@@ -162,20 +173,34 @@ impl ConversationController {
         }
     }
 
+    async fn sync_conversation_messages(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        conversation_id: web::Path<i32>,
+        params: web::Query<SyncParams>,
+    ) -> impl Responder {
+        let conversation_id = conversation_id.into_inner();
+
+        match services
+            .conversation_service
+            .sync_conversation_messages(
+                user_id,
+                conversation_id,
+                params.after_message_id,
+                params.limit,
+            )
+            .await
+        {
+            Ok(result) => ResponseWrapper::build(StatusCode::OK, "Messages synced", Some(result)),
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
     async fn get_user_conversation(
-        req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         query: web::Query<Pagination>,
     ) -> impl Responder {
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return HttpResponse::Unauthorized().finish(),
-            },
-            None => return HttpResponse::Unauthorized().finish(),
-        };
-
         let pagination = query.into_inner();
 
         match services
@@ -192,19 +217,10 @@ impl ConversationController {
     }
 
     async fn create_private_conversation(
-        req: HttpRequest,
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         new_conversation: web::Json<NewPrivateConversation>,
     ) -> impl Responder {
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return HttpResponse::Unauthorized().finish(),
-            },
-            None => return HttpResponse::Unauthorized().finish(),
-        };
-
         let new_conversation = new_conversation.into_inner();
 
         match services
@@ -215,11 +231,112 @@ impl ConversationController {
                 new_conversation.other_user_id,
             )
             .await
-            .map_err(|e| Error::Db(e))
         {
-            Ok(result) => {
+            Ok((result, true)) => {
                 ResponseWrapper::build(StatusCode::CREATED, "Conversation created", Some(result))
             }
+            Ok((result, false)) => {
+                ResponseWrapper::build(StatusCode::OK, "Conversation retrieved", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    async fn update_conversation(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        conversation_id: web::Path<i32>,
+        update: web::Json<UpdateConversation>,
+    ) -> impl Responder {
+        let conversation_id = conversation_id.into_inner();
+
+        match services
+            .conversation_service
+            .update_conversation(user_id, conversation_id, &update)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Conversation updated", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    async fn add_participants(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        conversation_id: web::Path<i32>,
+        new_participants: web::Json<NewParticipants>,
+    ) -> impl Responder {
+        let conversation_id = conversation_id.into_inner();
+
+        match services
+            .conversation_service
+            .add_participants(user_id, conversation_id, &new_participants.user_ids)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Participants added", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    async fn update_participant_role(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<(i32, Uuid)>,
+        update: web::Json<UpdateParticipantRole>,
+    ) -> impl Responder {
+        let (conversation_id, participant_id) = path.into_inner();
+
+        match services
+            .conversation_service
+            .update_participant_role(user_id, conversation_id, participant_id, update.role)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Participant role updated", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    async fn remove_participant(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<(i32, Uuid)>,
+    ) -> impl Responder {
+        let (conversation_id, participant_id) = path.into_inner();
+
+        match services
+            .conversation_service
+            .remove_participant(user_id, conversation_id, participant_id)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Participant removed", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    async fn transfer_ownership(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        conversation_id: web::Path<i32>,
+        transfer: web::Json<TransferOwnership>,
+    ) -> impl Responder {
+        let conversation_id = conversation_id.into_inner();
+
+        match services
+            .conversation_service
+            .transfer_ownership(user_id, conversation_id, transfer.user_id)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Ownership transferred", Some(result))
+            }
             Err(e) => HttpResponse::from_error(e),
         }
     }
@@ -1,7 +1,14 @@
-use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse, Responder};
-use uuid::Uuid;
+use actix_web::{http::StatusCode, web, HttpResponse, Responder};
 
-use crate::{app::AppServices, errors::Error, models::response_wrapper::ResponseWrapper};
+use crate::{
+    app::AppServices,
+    middlewares::auth_middleware::AuthUser,
+    models::{
+        conversation::MessageParams,
+        message::{DeleteMessageParams, UpdateMessage},
+        response_wrapper::ResponseWrapper,
+    },
+};
 
 pub struct MessageController;
 
@@ -10,11 +17,21 @@ impl MessageController {
         cfg.service(
             web::scope("/message")
                 .route("/{message_id}", web::get().to(Self::get_message_by_id))
-                .route("/{message_id}", web::delete().to(Self::delete_message)),
+                .route("/{message_id}", web::patch().to(Self::update_message))
+                .route("/{message_id}", web::delete().to(Self::delete_message))
+                .route(
+                    "/{message_id}/edits",
+                    web::get().to(Self::get_message_edits),
+                )
+                .route(
+                    "/{message_id}/replies",
+                    web::get().to(Self::get_message_replies),
+                ),
         );
     }
 
     pub async fn get_message_by_id(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         path: web::Path<i64>,
     ) -> impl Responder {
@@ -22,9 +39,8 @@ impl MessageController {
 
         match services
             .messages_service
-            .get_message_by_id(message_id)
+            .get_message_by_id(user_id, message_id)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(result) => match result {
                 Some(result) => {
@@ -38,27 +54,75 @@ impl MessageController {
         }
     }
 
-    pub async fn delete_message(
-        req: HttpRequest,
+    pub async fn update_message(
+        AuthUser(user_id): AuthUser,
         services: web::Data<AppServices>,
         path: web::Path<i64>,
+        update_message: web::Json<UpdateMessage>,
     ) -> impl Responder {
         let message_id = path.into_inner();
 
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return HttpResponse::Unauthorized().finish(),
-            },
-            None => return HttpResponse::Unauthorized().finish(),
-        };
+        match services
+            .messages_service
+            .update_message(user_id, message_id, &update_message.content)
+            .await
+        {
+            Ok(result) => ResponseWrapper::build(StatusCode::OK, "Message updated", Some(result)),
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    pub async fn get_message_edits(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<i64>,
+    ) -> impl Responder {
+        let message_id = path.into_inner();
+
+        match services
+            .messages_service
+            .get_message_edits(user_id, message_id)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Message edits retrieved", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    pub async fn get_message_replies(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<i64>,
+        params: web::Query<MessageParams>,
+    ) -> impl Responder {
+        let message_id = path.into_inner();
+
+        match services
+            .messages_service
+            .get_message_replies(user_id, message_id, params.limit, params.before)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Message replies retrieved", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    pub async fn delete_message(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<i64>,
+        params: web::Query<DeleteMessageParams>,
+    ) -> impl Responder {
+        let message_id = path.into_inner();
 
         match services
             .messages_service
-            .delete_message(user_id, message_id)
+            .delete_message(user_id, message_id, params.scope)
             .await
-            .map_err(|e| Error::Db(e))
         {
             Ok(_) => ResponseWrapper::<()>::build(StatusCode::OK, "Message deleted", None),
             Err(e) => HttpResponse::from_error(e),
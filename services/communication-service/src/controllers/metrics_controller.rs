@@ -0,0 +1,142 @@
+use std::{env, fmt::Write};
+
+use actix_web::{
+    http::{header::AUTHORIZATION, StatusCode},
+    web, HttpRequest, HttpResponse, Responder,
+};
+
+use crate::{
+    models::response_wrapper::ResponseWrapper,
+    ws::{chat_server_handler::ChatServerHandler, conn_queue},
+};
+
+// upper bounds of the queue depth histogram
+const QUEUE_DEPTH_BUCKETS: [usize; 9] = [0, 1, 5, 10, 25, 50, 100, 250, 500];
+
+// connections exported one by one, the ones with the deepest queues
+const TOP_QUEUES: usize = 10;
+
+pub struct MetricsController;
+
+impl MetricsController {
+    pub fn routes(cfg: &mut web::ServiceConfig) {
+        cfg.route("/metrics", web::get().to(Self::get_metrics));
+    }
+
+    /// Prometheus text exposition of the WebSocket connections of this node, aggregated so the
+    /// number of series does not grow with the connections. Scrapers authenticate with
+    /// `Authorization: Bearer <METRICS_TOKEN>`
+    async fn get_metrics(
+        req: HttpRequest,
+        chat_server_handler: web::Data<ChatServerHandler>,
+    ) -> impl Responder {
+        if !Self::is_authorized(&req) {
+            return ResponseWrapper::<()>::build(StatusCode::UNAUTHORIZED, "Unauthorized", None);
+        }
+
+        let mut stats = chat_server_handler.queue_stats().await;
+        let totals = conn_queue::totals();
+
+        let mut body = String::new();
+        let _ = writeln!(body, "# HELP ws_connections Open WebSocket connections");
+        let _ = writeln!(body, "# TYPE ws_connections gauge");
+        let _ = writeln!(body, "ws_connections {}", stats.len());
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_connection_queue_capacity Capacity of the outgoing queue of a connection"
+        );
+        let _ = writeln!(body, "# TYPE ws_connection_queue_capacity gauge");
+        if let Some(stat) = stats.first() {
+            let _ = writeln!(body, "ws_connection_queue_capacity {}", stat.capacity);
+        }
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_connection_queue_depth Messages waiting in the outgoing queues of the connections"
+        );
+        let _ = writeln!(body, "# TYPE ws_connection_queue_depth histogram");
+        for bucket in QUEUE_DEPTH_BUCKETS {
+            let count = stats.iter().filter(|stat| stat.depth <= bucket).count();
+            let _ = writeln!(
+                body,
+                "ws_connection_queue_depth_bucket{{le=\"{bucket}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            body,
+            "ws_connection_queue_depth_bucket{{le=\"+Inf\"}} {}",
+            stats.len()
+        );
+        let _ = writeln!(
+            body,
+            "ws_connection_queue_depth_sum {}",
+            stats.iter().map(|stat| stat.depth).sum::<usize>()
+        );
+        let _ = writeln!(body, "ws_connection_queue_depth_count {}", stats.len());
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_connection_queue_depth_top Messages waiting in the outgoing queue of the {TOP_QUEUES} most backed up connections"
+        );
+        let _ = writeln!(body, "# TYPE ws_connection_queue_depth_top gauge");
+        stats.sort_unstable_by(|a, b| b.depth.cmp(&a.depth));
+        for stat in stats.iter().take(TOP_QUEUES) {
+            let _ = writeln!(
+                body,
+                "ws_connection_queue_depth_top{{conn_id=\"{}\"}} {}",
+                stat.conn_id, stat.depth
+            );
+        }
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_slow_consumers Open connections being closed because the client fell behind"
+        );
+        let _ = writeln!(body, "# TYPE ws_slow_consumers gauge");
+        let _ = writeln!(
+            body,
+            "ws_slow_consumers {}",
+            stats.iter().filter(|stat| stat.too_slow).count()
+        );
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_slow_consumers_total Connections closed because the client fell behind"
+        );
+        let _ = writeln!(body, "# TYPE ws_slow_consumers_total counter");
+        let _ = writeln!(body, "ws_slow_consumers_total {}", totals.slow_consumers);
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_dropped_messages_total Messages dropped because the client fell behind"
+        );
+        let _ = writeln!(body, "# TYPE ws_dropped_messages_total counter");
+        let _ = writeln!(body, "ws_dropped_messages_total {}", totals.dropped);
+
+        let _ = writeln!(
+            body,
+            "# HELP ws_coalesced_messages_total Queued events replaced by newer ones"
+        );
+        let _ = writeln!(body, "# TYPE ws_coalesced_messages_total counter");
+        let _ = writeln!(body, "ws_coalesced_messages_total {}", totals.coalesced);
+
+        HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(body)
+    }
+
+    // the endpoint stays closed while no token is configured
+    fn is_authorized(req: &HttpRequest) -> bool {
+        let Some(token) = env::var("METRICS_TOKEN").ok().filter(|v| !v.is_empty()) else {
+            log::error!("METRICS_TOKEN must be set to scrape metrics");
+            return false;
+        };
+
+        req.headers()
+            .get(AUTHORIZATION)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| value.trim() == token)
+    }
+}
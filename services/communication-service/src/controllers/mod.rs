@@ -1,5 +1,7 @@
 pub mod attachment_controller;
 pub mod conversation_controller;
 pub mod message_controller;
+pub mod metrics_controller;
+pub mod reaction_controller;
 pub mod user_controller;
 pub mod ws_controller;
@@ -0,0 +1,80 @@
+use actix_web::{http::StatusCode, web, HttpResponse, Responder};
+
+use crate::{
+    app::AppServices,
+    middlewares::auth_middleware::AuthUser,
+    models::{reaction::NewReaction, response_wrapper::ResponseWrapper},
+};
+
+pub struct ReactionController;
+
+impl ReactionController {
+    pub fn routes(cfg: &mut web::ServiceConfig) {
+        cfg.service(
+            web::scope("/reaction")
+                .route(
+                    "/message/{message_id}",
+                    web::get().to(Self::get_message_reactions),
+                )
+                .route("/message/{message_id}", web::post().to(Self::add_reaction))
+                .route(
+                    "/message/{message_id}/{emoji}",
+                    web::delete().to(Self::remove_reaction),
+                ),
+        );
+    }
+
+    pub async fn get_message_reactions(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<i64>,
+    ) -> impl Responder {
+        let message_id = path.into_inner();
+
+        match services
+            .reaction_service
+            .get_message_reactions(user_id, message_id)
+            .await
+        {
+            Ok(result) => {
+                ResponseWrapper::build(StatusCode::OK, "Reactions retrieved", Some(result))
+            }
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    pub async fn add_reaction(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<i64>,
+        new_reaction: web::Json<NewReaction>,
+    ) -> impl Responder {
+        let message_id = path.into_inner();
+
+        match services
+            .reaction_service
+            .add_reaction(user_id, message_id, &new_reaction.emoji)
+            .await
+        {
+            Ok(_) => ResponseWrapper::<()>::build(StatusCode::CREATED, "Reaction added", None),
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+
+    pub async fn remove_reaction(
+        AuthUser(user_id): AuthUser,
+        services: web::Data<AppServices>,
+        path: web::Path<(i64, String)>,
+    ) -> impl Responder {
+        let (message_id, emoji) = path.into_inner();
+
+        match services
+            .reaction_service
+            .remove_reaction(user_id, message_id, &emoji)
+            .await
+        {
+            Ok(_) => ResponseWrapper::<()>::build(StatusCode::OK, "Reaction removed", None),
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
+}
@@ -13,7 +13,11 @@ pub struct UserController;
 
 impl UserController {
     pub fn routes(cfg: &mut web::ServiceConfig) {
-        cfg.service(web::scope("/user").route("/online", web::get().to(Self::check_online_user)));
+        cfg.service(
+            web::scope("/user")
+                .route("/online", web::get().to(Self::check_online_user))
+                .route("/presence", web::get().to(Self::get_user_presence)),
+        );
     }
 
     async fn check_online_user(
@@ -32,4 +36,21 @@ impl UserController {
             Err(e) => HttpResponse::from_error(e),
         }
     }
+
+    async fn get_user_presence(
+        services: web::Data<AppServices>,
+        query: web::Query<OnlineQuery>,
+    ) -> impl Responder {
+        let user_id = query.into_inner().user_id;
+
+        match services
+            .user_service
+            .get_user_presence(user_id)
+            .await
+            .map_err(|_| Error::InternalServerError)
+        {
+            Ok(result) => ResponseWrapper::build(StatusCode::OK, "success", Some(result)),
+            Err(e) => HttpResponse::from_error(e),
+        }
+    }
 }
@@ -1,14 +1,26 @@
-use actix_web::{web, Error, HttpRequest, HttpResponse, Result};
+use actix_web::{
+    http::header::{HeaderValue, SEC_WEBSOCKET_PROTOCOL},
+    web, Error, HttpMessage, HttpRequest, HttpResponse, Result,
+};
 use tokio::task::spawn_local;
-use uuid::Uuid;
 
-use crate::ws::{chat_server_handler, ws_handler};
+use crate::{
+    middlewares::auth_middleware::{
+        Auth, ACCESS_TOKEN_MARKER_PROTOCOL, ACCESS_TOKEN_PROTOCOL_PREFIX,
+    },
+    utils::jwt_utils::Claims,
+    ws::{chat_server_handler, protocol::WsProtocol, ws_handler},
+};
 
 pub struct WSController;
 
 impl WSController {
     pub fn routes(cfg: &mut web::ServiceConfig) {
-        cfg.service(web::resource("/ws").route(web::get().to(Self::chat_ws)));
+        cfg.service(
+            web::resource("/ws")
+                .wrap(Auth)
+                .route(web::get().to(Self::chat_ws)),
+        );
     }
 
     /// Handshake and start WebSocket
@@ -17,23 +29,69 @@ impl WSController {
         stream: web::Payload,
         chat_server_handler: web::Data<chat_server_handler::ChatServerHandler>,
     ) -> Result<HttpResponse, Error> {
-        let (res, session, msg_stream) = actix_ws::handle(&req, stream)?;
-
-        // get user id from req
-        let user_id = match req.headers().get("X-user-id").and_then(|v| v.to_str().ok()) {
-            Some(id_str) => match Uuid::parse_str(id_str) {
-                Ok(uuid) => uuid,
-                Err(_) => return Ok(HttpResponse::Unauthorized().finish()),
-            },
+        // draining nodes take no new sessions, the client retries against another node
+        if chat_server_handler.is_shutting_down() {
+            return Ok(HttpResponse::ServiceUnavailable()
+                .insert_header(("Retry-After", "1"))
+                .finish());
+        }
+
+        // encoding and version of the frames, clients without a subprotocol keep plain JSON.
+        // the access token offered as a subprotocol by browsers is not a frame encoding
+        let offered = req
+            .headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let frame_protocols: Vec<_> = offered
+            .split(',')
+            .map(str::trim)
+            .filter(|protocol| {
+                !protocol.is_empty()
+                    && *protocol != ACCESS_TOKEN_MARKER_PROTOCOL
+                    && !protocol.starts_with(ACCESS_TOKEN_PROTOCOL_PREFIX)
+            })
+            .collect();
+        let offered_marker = offered
+            .split(',')
+            .any(|protocol| protocol.trim() == ACCESS_TOKEN_MARKER_PROTOCOL);
+        // when nothing offered is supported none is selected and the connection falls back to JSON
+        let protocol = WsProtocol::negotiate(&frame_protocols.join(","));
+
+        let (mut res, session, msg_stream) = actix_ws::handle(&req, stream)?;
+
+        // get user id and token expiry from the verified access token
+        let (user_id, token_expires_at) = match req
+            .extensions()
+            .get::<Claims>()
+            .and_then(|claims| Some((claims.user_id()?, claims.exp)))
+        {
+            Some(claims) => claims,
             None => return Ok(HttpResponse::Unauthorized().finish()),
         };
 
+        // browsers close the socket when none of the offered subprotocols is echoed back, a
+        // client that only offered its token gets the marker it offered along with it. The token
+        // entry itself is never echoed
+        let selected_protocol = match protocol {
+            Some(protocol) => Some(protocol.to_string()),
+            None if offered_marker => Some(ACCESS_TOKEN_MARKER_PROTOCOL.to_string()),
+            None => None,
+        };
+        if let Some(selected_protocol) = selected_protocol {
+            if let Ok(value) = HeaderValue::from_str(&selected_protocol) {
+                res.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+            }
+        }
+
         // spawn websocket handler service
         spawn_local(ws_handler::WSHandler::handle_ws(
             (**chat_server_handler).clone(),
             session,
             msg_stream,
             user_id,
+            protocol.unwrap_or_default(),
+            token_expires_at,
         ));
 
         Ok(res)
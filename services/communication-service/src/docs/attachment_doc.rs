@@ -14,6 +14,10 @@ use crate::models::{attachment::Attachment, upload_form::UploadFormSchema};
             status = 200, 
             description = "Uploaded",
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Internal server error", 
@@ -35,6 +39,10 @@ pub async fn upload_file() {}
             status = 200, 
             description = "Success",
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Internal server error", 
@@ -57,6 +65,10 @@ pub async fn get_file() {}
             description = "Attachment found",
             body = Attachment,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 404, 
             description = "Attachment not found", 
@@ -83,6 +95,10 @@ pub async fn get_attachment_by_id() {}
             description = "Attachments found",
             body = Vec<Attachment>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Database error", 
@@ -105,6 +121,10 @@ pub async fn get_attachments_by_conversation_id() {}
             description = "Attachment found",
             body = Vec<Attachment>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Database error", 
@@ -1,4 +1,4 @@
-use crate::models::{conversation::{Conversation, ConversationList, ConversationMessages, NewConversation, NewPrivateConversation}, response_wrapper::{ResponseWrapper, UnitStruct}, user_conversation::UserConversation};
+use crate::models::{conversation::{Conversation, ConversationList, ConversationMessages, ConversationSync, GroupDetails, NewConversation, NewPrivateConversation, UpdateConversation}, response_wrapper::{ResponseWrapper, UnitStruct}, user_conversation::{NewParticipants, TransferOwnership, UpdateParticipantRole, UserConversation}};
 
 #[utoipa::path(
     get,
@@ -13,6 +13,10 @@ use crate::models::{conversation::{Conversation, ConversationList, ConversationM
             description = "Conversation found",
             body = ResponseWrapper<Conversation>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 404, 
             description = "Conversation not found", 
@@ -59,6 +63,10 @@ pub async fn create_conversation() {}
             description = "Deleted",
             body = ResponseWrapper<UnitStruct>
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation, or a group and not its owner", 
+        ),
         (
             status = 500, 
             description = "Delete failed", 
@@ -81,6 +89,10 @@ pub async fn delete_conversation() {}
             description = "Success operation",
             body = Vec<UserConversation>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Database error", 
@@ -105,6 +117,10 @@ pub async fn get_conversation_participants() {}
             description = "Success operation",
             body = ResponseWrapper<ConversationMessages>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 500, 
             description = "Database error", 
@@ -114,6 +130,42 @@ pub async fn get_conversation_participants() {}
 #[allow(dead_code)]
 pub async fn get_conversation_messages() {}
 
+#[utoipa::path(
+    get,
+    path = "/api/conversation/{conversation_id}/sync",
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation"),
+        ("after_message_id" = i64, Query, description = "Last message the client has, newer messages are returned in ascending order"),
+        ("limit" = Option<i32>, Query, description = "Limit the number of messages"),
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Missed messages, edits and deletions",
+            body = ResponseWrapper<ConversationSync>,
+        ),
+        (
+            status = 400, 
+            description = "after_message_id is not a message of the conversation, resync from scratch", 
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 404, 
+            description = "User not found in conversation", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn sync_conversation_messages() {}
+
 #[utoipa::path(
     get,
     path = "/api/conversation",
@@ -143,11 +195,20 @@ pub async fn get_user_conversations() {}
     request_body = NewPrivateConversation,
     tag = "Conversation",
     responses(
+        (
+            status = 200, 
+            description = "The two users already have a private conversation, it is returned",
+            body = ResponseWrapper<Conversation>,
+        ),
         (
             status = 201, 
             description = "Created",
             body = ResponseWrapper<Conversation>,
         ),
+        (
+            status = 400, 
+            description = "Private conversation with yourself", 
+        ),
         (
             status = 500, 
             description = "Create failed", 
@@ -155,4 +216,172 @@ pub async fn get_user_conversations() {}
     )
 )]
 #[allow(dead_code)]
-pub async fn create_private_conversation() {}
\ No newline at end of file
+pub async fn create_private_conversation() {}
+
+#[utoipa::path(
+    patch,
+    path = "/api/conversation/{conversation_id}",
+    request_body = UpdateConversation,
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Group settings updated",
+            body = ResponseWrapper<GroupDetails>,
+        ),
+        (
+            status = 400, 
+            description = "Nothing to update, empty title or not a group", 
+        ),
+        (
+            status = 403, 
+            description = "Requires the admin role", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn update_conversation() {}
+
+#[utoipa::path(
+    post,
+    path = "/api/conversation/{conversation_id}/participants",
+    request_body = NewParticipants,
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Participants added, users already in the group are skipped",
+            body = ResponseWrapper<GroupDetails>,
+        ),
+        (
+            status = 400, 
+            description = "No user to add or not a group", 
+        ),
+        (
+            status = 403, 
+            description = "Requires the admin role", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn add_participants() {}
+
+#[utoipa::path(
+    patch,
+    path = "/api/conversation/{conversation_id}/participants/{user_id}",
+    request_body = UpdateParticipantRole,
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation"),
+        ("user_id" = String, Path, description = "ID of the participant")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Participant role updated",
+            body = ResponseWrapper<GroupDetails>,
+        ),
+        (
+            status = 400, 
+            description = "Owner role requested or own role changed", 
+        ),
+        (
+            status = 403, 
+            description = "Requires the owner role", 
+        ),
+        (
+            status = 404, 
+            description = "Participant not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn update_participant_role() {}
+
+#[utoipa::path(
+    delete,
+    path = "/api/conversation/{conversation_id}/participants/{user_id}",
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation"),
+        ("user_id" = String, Path, description = "ID of the participant")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Participant removed, removing yourself leaves the group",
+            body = ResponseWrapper<GroupDetails>,
+        ),
+        (
+            status = 400, 
+            description = "The owner has to transfer the ownership before leaving", 
+        ),
+        (
+            status = 403, 
+            description = "Requires the admin role and a higher role than the participant", 
+        ),
+        (
+            status = 404, 
+            description = "Participant not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn remove_participant() {}
+
+#[utoipa::path(
+    post,
+    path = "/api/conversation/{conversation_id}/owner",
+    request_body = TransferOwnership,
+    tag = "Conversation",
+    params(
+        ("conversation_id" = i32, Path, description = "ID of the conversation")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "Ownership transferred, the previous owner becomes admin",
+            body = ResponseWrapper<GroupDetails>,
+        ),
+        (
+            status = 400, 
+            description = "Already the owner or not a group", 
+        ),
+        (
+            status = 403, 
+            description = "Requires the owner role", 
+        ),
+        (
+            status = 404, 
+            description = "Participant not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn transfer_ownership() {}
\ No newline at end of file
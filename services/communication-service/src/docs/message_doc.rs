@@ -1,4 +1,4 @@
-use crate::models::{message::Message, response_wrapper::{ResponseWrapper, UnitStruct}};
+use crate::models::{message::{DeleteScope, Message, MessageEdits, MessageReplies, UpdateMessage}, response_wrapper::{ResponseWrapper, UnitStruct}};
 
 #[utoipa::path(
     get,
@@ -13,6 +13,10 @@ use crate::models::{message::Message, response_wrapper::{ResponseWrapper, UnitSt
             description = "Message found",
             body = ResponseWrapper<Message>,
         ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
         (
             status = 404, 
             description = "Message not found", 
@@ -30,7 +34,8 @@ pub async fn get_message_by_id() {}
     delete,
     path = "/api/message/{message_id}",
     params(
-        ("message_id" = i64, Path, description = "ID of the message")
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("scope" = Option<DeleteScope>, Query, description = "`everyone` unsends the message (default), `me` hides it for the current user only")
     ),
     tag = "Message",
     responses(
@@ -39,6 +44,14 @@ pub async fn get_message_by_id() {}
             description = "Deleted",
             body = ResponseWrapper<UnitStruct>
         ),
+        (
+            status = 403, 
+            description = "Not the sender or unsend window has expired", 
+        ),
+        (
+            status = 404, 
+            description = "Message not found", 
+        ),
         (
             status = 500, 
             description = "Delete failed", 
@@ -47,3 +60,92 @@ pub async fn get_message_by_id() {}
 )]
 #[allow(dead_code)]
 pub async fn delete_message() {}
+
+#[utoipa::path(
+    patch,
+    path = "/api/message/{message_id}",
+    params(
+        ("message_id" = i64, Path, description = "ID of the message")
+    ),
+    request_body = UpdateMessage,
+    tag = "Message",
+    responses(
+        (
+            status = 200, 
+            description = "Message updated",
+            body = ResponseWrapper<Message>,
+        ),
+        (
+            status = 400, 
+            description = "Only text messages can be edited", 
+        ),
+        (
+            status = 403, 
+            description = "Not the sender or edit window has expired", 
+        ),
+        (
+            status = 404, 
+            description = "Message not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn update_message() {}
+
+#[utoipa::path(
+    get,
+    path = "/api/message/{message_id}/edits",
+    params(
+        ("message_id" = i64, Path, description = "ID of the message")
+    ),
+    tag = "Message",
+    responses(
+        (
+            status = 200, 
+            description = "Previous versions of the message",
+            body = ResponseWrapper<MessageEdits>,
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn get_message_edits() {}
+
+#[utoipa::path(
+    get,
+    path = "/api/message/{message_id}/replies",
+    params(
+        ("message_id" = i64, Path, description = "ID of the quoted message"),
+        ("limit" = Option<i32>, Query, description = "Limit the number of replies"),
+        ("before" = Option<DateTime<Utc>>, Query, description = "Timestamp to paginate before"),
+    ),
+    tag = "Message",
+    responses(
+        (
+            status = 200, 
+            description = "Replies to the message",
+            body = ResponseWrapper<MessageReplies>,
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn get_message_replies() {}
@@ -1,4 +1,5 @@
 pub mod attachment_doc;
 pub mod conversation_doc;
 pub mod message_doc;
+pub mod reaction_doc;
 pub mod user_doc;
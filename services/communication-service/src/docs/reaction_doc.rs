@@ -0,0 +1,97 @@
+use crate::models::{reaction::{MessageReactions, NewReaction}, response_wrapper::{ResponseWrapper, UnitStruct}};
+
+#[utoipa::path(
+    get,
+    path = "/api/reaction/message/{message_id}",
+    params(
+        ("message_id" = i64, Path, description = "ID of the message")
+    ),
+    tag = "Reaction",
+    responses(
+        (
+            status = 200, 
+            description = "Reactions retrieved",
+            body = ResponseWrapper<MessageReactions>,
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn get_message_reactions() {}
+
+#[utoipa::path(
+    post,
+    path = "/api/reaction/message/{message_id}",
+    params(
+        ("message_id" = i64, Path, description = "ID of the message")
+    ),
+    request_body = NewReaction,
+    tag = "Reaction",
+    responses(
+        (
+            status = 201, 
+            description = "Reaction added",
+            body = ResponseWrapper<UnitStruct>,
+        ),
+        (
+            status = 400, 
+            description = "Reaction is not a valid emoji", 
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 404, 
+            description = "Message not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn add_reaction() {}
+
+#[utoipa::path(
+    delete,
+    path = "/api/reaction/message/{message_id}/{emoji}",
+    params(
+        ("message_id" = i64, Path, description = "ID of the message"),
+        ("emoji" = String, Path, description = "URL encoded emoji to remove")
+    ),
+    tag = "Reaction",
+    responses(
+        (
+            status = 200, 
+            description = "Reaction removed",
+            body = ResponseWrapper<UnitStruct>,
+        ),
+        (
+            status = 400, 
+            description = "Reaction is not a valid emoji", 
+        ),
+        (
+            status = 403, 
+            description = "Not a participant of the conversation", 
+        ),
+        (
+            status = 404, 
+            description = "Message not found", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn remove_reaction() {}
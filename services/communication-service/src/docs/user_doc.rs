@@ -1,4 +1,4 @@
-use crate::models::response_wrapper::ResponseWrapper;
+use crate::models::{presence::UserPresence, response_wrapper::ResponseWrapper};
 
 #[utoipa::path(
     get,
@@ -24,4 +24,29 @@ use crate::models::response_wrapper::ResponseWrapper;
     )
 )]
 #[allow(dead_code)]
-pub async fn check_online_user() {}
\ No newline at end of file
+pub async fn check_online_user() {}
+#[utoipa::path(
+    get,
+    path = "/api/user/presence",
+    tag = "User",
+    params(
+        ("user_id" = String, Query, description = "ID of the user")
+    ),
+    responses(
+        (
+            status = 200, 
+            description = "User presence with last seen time",
+            body = ResponseWrapper<UserPresence>,
+        ),
+        (
+            status = 400, 
+            description = "Invalid user id", 
+        ),
+        (
+            status = 500, 
+            description = "Database error", 
+        )
+    )
+)]
+#[allow(dead_code)]
+pub async fn get_user_presence() {}
\ No newline at end of file
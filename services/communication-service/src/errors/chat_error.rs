@@ -6,6 +6,26 @@ pub enum ChatError {
     JoinError(String),
     #[error("Leaving conversation error: {}", _0)]
     LeaveError(String),
+    #[error("Removing participant error: {}", _0)]
+    RemoveParticipantError(String),
     #[error("Messaging error: {}", _0)]
     MessageError(String),
+    #[error("Typing indicator error: {}", _0)]
+    TypingError(String),
+    #[error("Read receipt error: {}", _0)]
+    ReceiptError(String),
+    #[error("Reaction error: {}", _0)]
+    ReactionError(String),
+    #[error("Sync error: {}", _0)]
+    SyncError(String),
+    #[error("Broadcast error: {}", _0)]
+    BroadcastError(String),
+    #[error("Inbox error: {}", _0)]
+    InboxError(String),
+    #[error("System message error: {}", _0)]
+    SystemMessageError(String),
+    #[error("Presence error: {}", _0)]
+    PresenceError(String),
+    #[error("Chat server is shutting down")]
+    ShuttingDown,
 }
@@ -4,6 +4,7 @@ use thiserror::Error;
 pub mod chat_error;
 pub mod db_error;
 pub mod file_error;
+pub mod jwt_error;
 pub mod redis_error;
 
 use db_error::DBError;
@@ -19,6 +20,12 @@ pub enum Error {
     #[error(transparent)]
     File(#[from] FileError),
 
+    #[error("Forbidden: {}", _0)]
+    Forbidden(String),
+
+    #[error("Bad request: {}", _0)]
+    BadRequest(String),
+
     #[error("Internal server error")]
     InternalServerError,
 }
@@ -47,9 +54,29 @@ impl ResponseError for Error {
             }
             Error::File(e) => json_error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
 
+            Error::Forbidden(e) => json_error(StatusCode::FORBIDDEN, e),
+            Error::BadRequest(e) => json_error(StatusCode::BAD_REQUEST, e),
+
             Error::InternalServerError => {
                 json_error(StatusCode::INTERNAL_SERVER_ERROR, "Server error")
             }
         }
     }
 }
+
+impl From<Error> for tonic::Status {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Db(DBError::NotFound(e)) => tonic::Status::not_found(e),
+            Error::Db(e) => tonic::Status::internal(e.to_string()),
+            Error::File(FileError::Forbidden) => {
+                tonic::Status::permission_denied("Access to file is forbidden")
+            }
+            Error::File(FileError::FileNotFound) => tonic::Status::not_found("File not found"),
+            Error::File(e) => tonic::Status::internal(e.to_string()),
+            Error::Forbidden(e) => tonic::Status::permission_denied(e),
+            Error::BadRequest(e) => tonic::Status::invalid_argument(e),
+            Error::InternalServerError => tonic::Status::internal("Internal server error"),
+        }
+    }
+}
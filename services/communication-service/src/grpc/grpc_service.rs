@@ -1,22 +1,34 @@
-use farmera_grpc_proto::communication::{
-    communication_service_server::CommunicationService, CheckOnlineUserRequest,
-    CheckOnlineUserResponse, CreateConversationRequest, CreateConversationResponse,
-    CreatePrivateConversationRequest, CreatePrivateConversationResponse, DeleteConversationRequest,
-    DeleteConversationResponse, DeleteMessageRequest, DeleteMessageResponse,
-    GetConversationMessagesRequest, GetConversationMessagesResponse,
-    GetConversationParticipantsRequest, GetConversationParticipantsResponse,
-    GetConversationRequest, GetConversationResponse, GetMessageRequest, GetMessageResponse,
-    GetUnreadCountRequest, GetUnreadCountResponse, ListConversationsRequest,
-    ListConversationsResponse, MarkAsReadRequest, MarkAsReadResponse,
+use farmera_grpc_proto::{
+    communication::{
+        communication_service_server::CommunicationService, AddParticipantsRequest,
+        AddParticipantsResponse, CheckOnlineUserRequest, CheckOnlineUserResponse,
+        CreateConversationRequest, CreateConversationResponse, CreatePrivateConversationRequest,
+        CreatePrivateConversationResponse, DeleteConversationRequest, DeleteConversationResponse,
+        DeleteMessageRequest, DeleteMessageResponse, GetConversationMessagesRequest,
+        GetConversationMessagesResponse, GetConversationParticipantsRequest,
+        GetConversationParticipantsResponse, GetConversationRequest, GetConversationResponse,
+        GetMessageRequest, GetMessageResponse, GetUnreadCountRequest, GetUnreadCountResponse,
+        GroupDetails, ListConversationsRequest, ListConversationsResponse, MarkAsReadRequest,
+        MarkAsReadResponse, PresenceEvent, RemoveParticipantRequest, RemoveParticipantResponse,
+        SendSystemMessageRequest, SendSystemMessageResponse, StreamUserPresenceRequest,
+        SyncConversationMessagesRequest, SyncConversationMessagesResponse,
+        TransferOwnershipRequest, TransferOwnershipResponse, UpdateConversationRequest,
+        UpdateConversationResponse, UpdateMessageRequest, UpdateMessageResponse,
+        UpdateParticipantRoleRequest, UpdateParticipantRoleResponse,
+    },
+    ParticipantRole,
 };
+use futures_util::{stream::BoxStream, StreamExt};
 use tonic::{Request, Response, Status};
 use uuid::Uuid;
 
 use crate::{
     app::AppServices,
     models::{
-        conversation::{MessageParams, NewConversation},
-        Pagination,
+        common_mapping_impl::PtcRole,
+        conversation::{MessageParams, NewConversation, UpdateConversation},
+        message::DeleteScope,
+        ConversationKind, Pagination,
     },
 };
 
@@ -32,20 +44,35 @@ impl GrpcCommunicationService {
 
 #[tonic::async_trait]
 impl CommunicationService for GrpcCommunicationService {
+    type StreamUserPresenceStream = BoxStream<'static, Result<PresenceEvent, Status>>;
+
     // Conversation methods
     async fn create_conversation(
         &self,
         request: Request<CreateConversationRequest>,
     ) -> Result<Response<CreateConversationResponse>, Status> {
         let create_req = request.into_inner();
+        let owner_id = create_req
+            .owner_id
+            .as_deref()
+            .map(Uuid::parse_str)
+            .transpose()
+            .map_err(|_| Status::invalid_argument("Invalid UUID for owner id"))?;
 
         let new_conversation =
             NewConversation::try_from(create_req).map_err(|e| Status::invalid_argument(e))?;
 
+        // nobody could manage a group without an owner
+        if new_conversation.kind == ConversationKind::Group && owner_id.is_none() {
+            return Err(Status::invalid_argument(
+                "Owner id is required for group conversations",
+            ));
+        }
+
         let result = self
             .app_services
             .conversation_service
-            .create_conversation(&new_conversation.title)
+            .create_conversation(owner_id, &new_conversation)
             .await
             .map_err(|e| Status::from_error(Box::new(e)))?;
 
@@ -58,13 +85,15 @@ impl CommunicationService for GrpcCommunicationService {
     ) -> Result<Response<GetConversationResponse>, Status> {
         let get_req = request.into_inner();
         let conversation_id = get_req.conversation_id;
+        let user_id = Uuid::parse_str(&get_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
 
         let result = self
             .app_services
             .conversation_service
-            .get_conversation_by_id(conversation_id)
+            .get_conversation_by_id(user_id, conversation_id)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         if result.is_none() {
             Err(Status::not_found("Conversation not found"))
@@ -108,13 +137,15 @@ impl CommunicationService for GrpcCommunicationService {
     ) -> Result<Response<DeleteConversationResponse>, Status> {
         let delete_req = request.into_inner();
         let conversation_id = delete_req.conversation_id;
+        let user_id = Uuid::parse_str(&delete_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
 
         let result = self
             .app_services
             .conversation_service
-            .delete_conversation(conversation_id)
+            .delete_conversation(user_id, conversation_id)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         if result > 0 {
             Ok(Response::new(DeleteConversationResponse { success: true }))
@@ -123,6 +154,128 @@ impl CommunicationService for GrpcCommunicationService {
         }
     }
 
+    async fn update_conversation(
+        &self,
+        request: Request<UpdateConversationRequest>,
+    ) -> Result<Response<UpdateConversationResponse>, Status> {
+        let update_req = request.into_inner();
+        let conversation_id = update_req.conversation_id;
+        let user_id = Uuid::parse_str(&update_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+
+        let update = UpdateConversation::from(update_req);
+
+        let result = self
+            .app_services
+            .conversation_service
+            .update_conversation(user_id, conversation_id, &update)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateConversationResponse {
+            group: Some(GroupDetails::from(result)),
+        }))
+    }
+
+    async fn add_participants(
+        &self,
+        request: Request<AddParticipantsRequest>,
+    ) -> Result<Response<AddParticipantsResponse>, Status> {
+        let add_req = request.into_inner();
+        let conversation_id = add_req.conversation_id;
+        let user_id = Uuid::parse_str(&add_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+        let participant_ids = add_req
+            .participant_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<Uuid>, _>>()
+            .map_err(|_| Status::invalid_argument("Invalid UUID for participant id"))?;
+
+        let result = self
+            .app_services
+            .conversation_service
+            .add_participants(user_id, conversation_id, &participant_ids)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(AddParticipantsResponse {
+            group: Some(GroupDetails::from(result)),
+        }))
+    }
+
+    async fn remove_participant(
+        &self,
+        request: Request<RemoveParticipantRequest>,
+    ) -> Result<Response<RemoveParticipantResponse>, Status> {
+        let remove_req = request.into_inner();
+        let conversation_id = remove_req.conversation_id;
+        let user_id = Uuid::parse_str(&remove_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+        let participant_id = Uuid::parse_str(&remove_req.participant_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for participant id"))?;
+
+        let result = self
+            .app_services
+            .conversation_service
+            .remove_participant(user_id, conversation_id, participant_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(RemoveParticipantResponse {
+            group: Some(GroupDetails::from(result)),
+        }))
+    }
+
+    async fn update_participant_role(
+        &self,
+        request: Request<UpdateParticipantRoleRequest>,
+    ) -> Result<Response<UpdateParticipantRoleResponse>, Status> {
+        let update_req = request.into_inner();
+        let conversation_id = update_req.conversation_id;
+        let user_id = Uuid::parse_str(&update_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+        let participant_id = Uuid::parse_str(&update_req.participant_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for participant id"))?;
+        let grpc_role = ParticipantRole::try_from(update_req.role)
+            .map_err(|_| Status::invalid_argument("Invalid participant role"))?;
+        let role = PtcRole::try_from(grpc_role).map_err(|e| Status::invalid_argument(e))?;
+
+        let result = self
+            .app_services
+            .conversation_service
+            .update_participant_role(user_id, conversation_id, participant_id, role)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateParticipantRoleResponse {
+            group: Some(GroupDetails::from(result)),
+        }))
+    }
+
+    async fn transfer_ownership(
+        &self,
+        request: Request<TransferOwnershipRequest>,
+    ) -> Result<Response<TransferOwnershipResponse>, Status> {
+        let transfer_req = request.into_inner();
+        let conversation_id = transfer_req.conversation_id;
+        let user_id = Uuid::parse_str(&transfer_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+        let new_owner_id = Uuid::parse_str(&transfer_req.new_owner_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for new owner id"))?;
+
+        let result = self
+            .app_services
+            .conversation_service
+            .transfer_ownership(user_id, conversation_id, new_owner_id)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(TransferOwnershipResponse {
+            group: Some(GroupDetails::from(result)),
+        }))
+    }
+
     async fn get_conversation_participants(
         &self,
         request: Request<GetConversationParticipantsRequest>,
@@ -137,7 +290,7 @@ impl CommunicationService for GrpcCommunicationService {
             .conversation_service
             .get_user_conversation_participants(user_id, conversation_id)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         Ok(Response::new(GetConversationParticipantsResponse::from(
             result,
@@ -163,11 +316,37 @@ impl CommunicationService for GrpcCommunicationService {
             .conversation_service
             .get_conversation_messages(user_id, conversation_id, params.limit, params.before)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         Ok(Response::new(GetConversationMessagesResponse::from(result)))
     }
 
+    async fn sync_conversation_messages(
+        &self,
+        request: Request<SyncConversationMessagesRequest>,
+    ) -> Result<Response<SyncConversationMessagesResponse>, Status> {
+        let sync_req = request.into_inner();
+
+        let user_id = Uuid::parse_str(&sync_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+
+        let result = self
+            .app_services
+            .conversation_service
+            .sync_conversation_messages(
+                user_id,
+                sync_req.conversation_id,
+                sync_req.after_message_id,
+                Some(sync_req.limit.unwrap_or(100)),
+            )
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(SyncConversationMessagesResponse::from(
+            result,
+        )))
+    }
+
     // Message methods
     async fn get_message(
         &self,
@@ -175,13 +354,15 @@ impl CommunicationService for GrpcCommunicationService {
     ) -> Result<Response<GetMessageResponse>, Status> {
         let get_msg_req = request.into_inner();
         let message_id = get_msg_req.message_id;
+        let user_id = Uuid::parse_str(&get_msg_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
 
         let result = self
             .app_services
             .messages_service
-            .get_message_by_id(message_id)
+            .get_message_by_id(user_id, message_id)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         if result.is_some() {
             Ok(Response::new(GetMessageResponse::from(result.unwrap())))
@@ -190,6 +371,25 @@ impl CommunicationService for GrpcCommunicationService {
         }
     }
 
+    async fn update_message(
+        &self,
+        request: Request<UpdateMessageRequest>,
+    ) -> Result<Response<UpdateMessageResponse>, Status> {
+        let update_msg_req = request.into_inner();
+
+        let user_id = Uuid::parse_str(&update_msg_req.user_id)
+            .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
+
+        let result = self
+            .app_services
+            .messages_service
+            .update_message(user_id, update_msg_req.message_id, &update_msg_req.content)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(UpdateMessageResponse::from(result)))
+    }
+
     async fn delete_message(
         &self,
         request: Request<DeleteMessageRequest>,
@@ -200,15 +400,37 @@ impl CommunicationService for GrpcCommunicationService {
             .map_err(|_| Status::invalid_argument("Invalid UUID for user id"))?;
 
         let message_id = delete_msg_req.message_id;
+        let scope = if delete_msg_req.only_for_me {
+            DeleteScope::Me
+        } else {
+            DeleteScope::Everyone
+        };
+
         self.app_services
             .messages_service
-            .delete_message(user_id, message_id)
+            .delete_message(user_id, message_id, scope)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         Ok(Response::new(DeleteMessageResponse { success: true }))
     }
 
+    async fn send_system_message(
+        &self,
+        request: Request<SendSystemMessageRequest>,
+    ) -> Result<Response<SendSystemMessageResponse>, Status> {
+        let req = request.into_inner();
+
+        let message_id = self
+            .app_services
+            .conversation_service
+            .send_system_note(req.conversation_id, &req.content)
+            .await
+            .map_err(Status::from)?;
+
+        Ok(Response::new(SendSystemMessageResponse { message_id }))
+    }
+
     async fn check_online_user(
         &self,
         request: Request<CheckOnlineUserRequest>,
@@ -221,11 +443,39 @@ impl CommunicationService for GrpcCommunicationService {
         let result = self
             .app_services
             .user_service
-            .check_online_user(user_id)
+            .get_user_presence(user_id)
+            .await
+            .map_err(|_| Status::internal("Internal server error"))?;
+
+        Ok(Response::new(CheckOnlineUserResponse::from(result)))
+    }
+
+    async fn stream_user_presence(
+        &self,
+        request: Request<StreamUserPresenceRequest>,
+    ) -> Result<Response<Self::StreamUserPresenceStream>, Status> {
+        let user_ids = request
+            .into_inner()
+            .user_ids
+            .iter()
+            .map(|id| Uuid::parse_str(id))
+            .collect::<Result<Vec<Uuid>, _>>()
+            .map_err(|_| Status::invalid_argument("Invalid user id"))?;
+
+        if user_ids.is_empty() {
+            return Err(Status::invalid_argument("User ids cannot be empty"));
+        }
+
+        let stream = self
+            .app_services
+            .user_service
+            .stream_user_presence(user_ids)
             .await
             .map_err(|_| Status::internal("Internal server error"))?;
 
-        Ok(Response::new(CheckOnlineUserResponse { is_online: result }))
+        Ok(Response::new(Box::pin(
+            stream.map(|presence| Ok(PresenceEvent::from(presence))),
+        )))
     }
 
     async fn create_private_conversation(
@@ -244,7 +494,7 @@ impl CommunicationService for GrpcCommunicationService {
             .conversation_service
             .create_private_conversation(&create_req.title, user_a, user_b)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         Ok(Response::new(CreatePrivateConversationResponse::from(
             result,
@@ -263,9 +513,9 @@ impl CommunicationService for GrpcCommunicationService {
         let result = self
             .app_services
             .conversation_service
-            .mark_as_read(conversation_id, user_id)
+            .mark_as_read(conversation_id, user_id, req.message_id)
             .await
-            .map_err(|e| Status::from_error(Box::new(e)))?;
+            .map_err(Status::from)?;
 
         Ok(Response::new(MarkAsReadResponse { success: result }))
     }
@@ -5,10 +5,13 @@ use communication_service::{
     controllers::{
         attachment_controller::AttachmentController,
         conversation_controller::ConversationController, message_controller::MessageController,
+        metrics_controller::MetricsController, reaction_controller::ReactionController,
         user_controller::UserController, ws_controller::WSController,
     },
     grpc::grpc_service::GrpcCommunicationService,
+    middlewares::auth_middleware::Auth,
     openapi::ApiDoc,
+    shutdown,
 };
 use dotenvy::dotenv;
 use env_logger::Env;
@@ -37,6 +40,7 @@ async fn main() -> std::io::Result<()> {
     let state = AppState::build().await;
 
     let app_data = web::Data::new(state.app_services.clone());
+    let shutdown_handler = state.chat_server_handler.clone();
     let chat_server_handler = web::Data::new(state.chat_server_handler);
 
     // start chat server
@@ -45,8 +49,11 @@ async fn main() -> std::io::Result<()> {
     // create the gRPC communication service instance
     let grpc_communication_service = GrpcCommunicationService::new(state.app_services.clone());
 
+    // the grpc server stops once the sessions are drained and the http server is stopped
+    let (grpc_shutdown_tx, grpc_shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
     // start grpc server
-    tokio::spawn(async move {
+    let grpc_server = tokio::spawn(async move {
         // Set up the gRPC server address and port from environment variables
         let grpc_server_addr =
             env::var("GRPC_SERVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1".to_string());
@@ -61,13 +68,10 @@ async fn main() -> std::io::Result<()> {
         tonic::transport::Server::builder()
             .add_service(CommunicationServiceServer::new(grpc_communication_service))
             .serve_with_shutdown(grpc_addr, async {
-                tokio::signal::ctrl_c()
-                    .await
-                    .expect("Failed to listen for ctrl_c");
+                let _ = grpc_shutdown_rx.await;
                 log::info!("Shutting down grpc server gracefully...");
             })
             .await
-            .unwrap();
     });
 
     // start http server
@@ -83,11 +87,15 @@ async fn main() -> std::io::Result<()> {
             // route configurations
             // websocket
             .configure(WSController::routes)
+            // connection metrics
+            .configure(MetricsController::routes)
             // apis
             .service(
                 web::scope("/api")
+                    .wrap(Auth)
                     .configure(ConversationController::routes)
                     .configure(MessageController::routes)
+                    .configure(ReactionController::routes)
                     .configure(AttachmentController::routes)
                     .configure(UserController::routes),
             )
@@ -99,9 +107,21 @@ async fn main() -> std::io::Result<()> {
     })
     .bind(format!("{server_addr}:{server_port}"))?
     .workers(3)
+    // signals are handled by `shutdown::drain` so sessions are closed before workers stop
+    .disable_signals()
     .run();
 
-    tokio::try_join!(http_server, async move { chat_server.await.unwrap() })?;
+    let http_handle = http_server.handle();
+    tokio::spawn(async move {
+        shutdown::drain(http_handle, shutdown_handler).await;
+        let _ = grpc_shutdown_tx.send(());
+    });
+
+    tokio::try_join!(
+        http_server,
+        async move { chat_server.await.unwrap() },
+        async move { grpc_server.await.unwrap().map_err(std::io::Error::other) }
+    )?;
 
     Ok(())
 }
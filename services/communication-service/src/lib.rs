@@ -10,4 +10,6 @@ pub mod openapi;
 pub mod redis_repositories;
 pub mod repositories;
 pub mod services;
+pub mod shutdown;
+pub mod utils;
 pub mod ws;
@@ -5,9 +5,12 @@ use communication_service::{
     controllers::{
         attachment_controller::AttachmentController,
         conversation_controller::ConversationController, message_controller::MessageController,
+        metrics_controller::MetricsController, reaction_controller::ReactionController,
         user_controller::UserController, ws_controller::WSController,
     },
+    middlewares::auth_middleware::Auth,
     openapi::ApiDoc,
+    shutdown,
 };
 use dotenvy::dotenv;
 use env_logger::Env;
@@ -31,6 +34,7 @@ async fn main() -> std::io::Result<()> {
     let state = AppState::build().await;
 
     let app_data = web::Data::new(state.app_services);
+    let shutdown_handler = state.chat_server_handler.clone();
     let chat_server_handler = web::Data::new(state.chat_server_handler);
 
     // start chat server
@@ -46,11 +50,15 @@ async fn main() -> std::io::Result<()> {
             // route configurations
             // websocket
             .configure(WSController::routes)
+            // connection metrics
+            .configure(MetricsController::routes)
             // apis
             .service(
                 web::scope("/api")
+                    .wrap(Auth)
                     .configure(ConversationController::routes)
                     .configure(MessageController::routes)
+                    .configure(ReactionController::routes)
                     .configure(AttachmentController::routes)
                     .configure(UserController::routes),
             )
@@ -62,8 +70,12 @@ async fn main() -> std::io::Result<()> {
     })
     .bind(format!("{server_addr}:{server_port}"))?
     .workers(3)
+    // signals are handled by `shutdown::drain` so sessions are closed before workers stop
+    .disable_signals()
     .run();
 
+    tokio::spawn(shutdown::drain(http_server.handle(), shutdown_handler));
+
     tokio::try_join!(http_server, async move { chat_server.await.unwrap() })?;
 
     Ok(())
@@ -1,14 +1,37 @@
-use std::future::{ready, Ready};
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+};
 
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    body::EitherBody,
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::InternalError,
+    http::{
+        header::{AUTHORIZATION, SEC_WEBSOCKET_PROTOCOL, UPGRADE},
+        StatusCode,
+    },
+    web, Error, FromRequest, HttpMessage, HttpRequest,
 };
-use futures_util::future::LocalBoxFuture;
+use futures_util::{future::LocalBoxFuture, FutureExt};
 
-pub struct Auth;
+use uuid::Uuid;
+
+use crate::{
+    models::response_wrapper::ResponseWrapper,
+    utils::jwt_utils::{Claims, JwtUtils},
+};
+
+// browsers cannot set headers on a WebSocket upgrade, they may offer the token as a subprotocol
+pub const ACCESS_TOKEN_PROTOCOL_PREFIX: &str = "access_token.";
+// offered next to the token subprotocol, echoed in its place so the token never ends up in a
+// response header
+pub const ACCESS_TOKEN_MARKER_PROTOCOL: &str = "farmera.auth";
 
-impl Auth {}
+/// Verifies the access token of a request and puts its `Claims` into the request extensions.
+/// The token is read from the `Authorization` header, or on a WebSocket upgrade from the
+/// `access_token` query parameter or an `access_token.<token>` subprotocol.
+pub struct Auth;
 
 impl<S, B> Transform<S, ServiceRequest> for Auth
 where
@@ -16,7 +39,7 @@ where
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Transform = AuthMiddleware<S>;
     type InitError = ();
@@ -28,31 +51,121 @@ where
     }
 }
 
+/// Id of the user whose access token was verified by [`Auth`], taken as a handler argument.
+/// Requests without verified claims are answered with 401.
+pub struct AuthUser(pub Uuid);
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user_id = req
+            .extensions()
+            .get::<Claims>()
+            .and_then(|claims| claims.user_id());
+
+        ready(user_id.map(AuthUser).ok_or_else(|| {
+            let http_res =
+                ResponseWrapper::<()>::build(StatusCode::UNAUTHORIZED, "Unauthorized", None);
+            InternalError::from_response("Unauthorized", http_res).into()
+        }))
+    }
+}
+
 pub struct AuthMiddleware<S> {
     service: S,
 }
 
+impl<S> AuthMiddleware<S> {
+    fn is_websocket_upgrade(req: &ServiceRequest) -> bool {
+        req.headers()
+            .get(UPGRADE)
+            .and_then(|header_value| header_value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"))
+    }
+
+    fn access_token(req: &ServiceRequest) -> Option<String> {
+        // get access token from header
+        let header_token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        if header_token.is_some() {
+            return header_token;
+        }
+
+        // query strings end up in access logs, only the WebSocket handshake may carry the token
+        // there because browsers cannot set headers on it
+        if !Self::is_websocket_upgrade(req) {
+            return None;
+        }
+
+        // get access token from query
+        let query_token = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.get("access_token").cloned());
+        if query_token.is_some() {
+            return query_token;
+        }
+
+        // get access token from websocket subprotocols
+        req.headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|protocols| {
+                protocols.split(',').find_map(|protocol| {
+                    protocol
+                        .trim()
+                        .strip_prefix(ACCESS_TOKEN_PROTOCOL_PREFIX)
+                        .map(|token| token.to_string())
+                })
+            })
+    }
+}
+
 impl<S, B> Service<ServiceRequest> for AuthMiddleware<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
     S::Future: 'static,
     B: 'static,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<EitherBody<B>>;
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        log::info!("{:?}", req.match_info());
+        // verify access token
+        if let Some(token) = Self::access_token(&req) {
+            match JwtUtils::verify_access_token(&token) {
+                Ok(claims) => {
+                    // insert claims into request extension
+                    req.extensions_mut().insert(claims);
+
+                    // continue to next service
+                    let fut = self.service.call(req);
+
+                    return Box::pin(async move {
+                        let res = fut.await?;
+                        Ok(res.map_into_left_body())
+                    });
+                }
+                Err(e) => {
+                    log::error!("Token verify failed: {e}");
+                }
+            }
+        }
 
-        let fut = self.service.call(req);
+        // return unauthorized
+        let http_res = ResponseWrapper::<()>::build(StatusCode::UNAUTHORIZED, "Unauthorized", None);
 
-        Box::pin(async move {
-            let res = fut.await?;
+        let (http_req, _) = req.into_parts();
+        let res = ServiceResponse::new(http_req, http_res);
 
-            Ok(res)
-        })
+        (async move { Ok(res.map_into_right_body()) }).boxed_local()
     }
 }
@@ -41,6 +41,7 @@ fn default_limit() -> Option<i32> {
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SentMedia {
+    pub conversation_id: i32,
     pub sender_id: Uuid,
     pub r#type: String,
     pub timestamp: DateTime<Utc>,
@@ -53,3 +54,24 @@ pub struct MediaContent {
     pub size: i32,
     pub r#type: String,
 }
+
+// Push notification text of a media message, e.g. "sent a photo" or "sent 3 videos"
+pub fn media_preview(media: &[MediaContent]) -> String {
+    let file_type = match media.first() {
+        Some(first) if media.iter().all(|m| m.r#type == first.r#type) => first.r#type.as_str(),
+        _ => "",
+    };
+
+    let (single, plural) = match file_type {
+        "image" => ("a photo", "photos"),
+        "video" => ("a video", "videos"),
+        "audio" => ("an audio message", "audio messages"),
+        _ => ("a file", "files"),
+    };
+
+    if media.len() > 1 {
+        format!("sent {} {}", media.len(), plural)
+    } else {
+        format!("sent {}", single)
+    }
+}
@@ -1,8 +1,13 @@
-use farmera_grpc_proto::{MessageType, NotificationType, PushMessageType};
+use farmera_grpc_proto::{
+    ConversationType, MessageType, NotificationType, ParticipantRole, PresenceStatus,
+    PushMessageType,
+};
 
-use crate::models::{common_mapping_impl::PushType, notification_mapping_impl::NotiType};
+use crate::models::{
+    common_mapping_impl::PushType, notification_mapping_impl::NotiType, ConversationKind,
+};
 
-use super::MsgType;
+use super::{MsgType, PrsStatus, PtcRole};
 
 impl TryFrom<MessageType> for MsgType {
     type Error = &'static str;
@@ -11,6 +16,7 @@ impl TryFrom<MessageType> for MsgType {
         match value {
             MessageType::Media => Ok(MsgType::Media),
             MessageType::Message => Ok(MsgType::Message),
+            MessageType::System => Ok(MsgType::System),
             MessageType::Unspecified => Err("MESSAGE_TYPE_UNSPECIFIED"),
         }
     }
@@ -21,6 +27,64 @@ impl From<MsgType> for MessageType {
         match value {
             MsgType::Media => MessageType::Media,
             MsgType::Message => MessageType::Message,
+            MsgType::System => MessageType::System,
+        }
+    }
+}
+
+impl TryFrom<ConversationType> for ConversationKind {
+    type Error = &'static str;
+
+    fn try_from(value: ConversationType) -> Result<Self, Self::Error> {
+        match value {
+            ConversationType::Group => Ok(ConversationKind::Group),
+            ConversationType::Live => Ok(ConversationKind::Live),
+            ConversationType::Direct => Ok(ConversationKind::Private),
+            ConversationType::Unspecified => Err("CONVERSATION_TYPE_UNSPECIFIED"),
+            _ => Err("Unsupported conversation type"),
+        }
+    }
+}
+
+impl From<ConversationKind> for ConversationType {
+    fn from(value: ConversationKind) -> Self {
+        match value {
+            ConversationKind::Group => ConversationType::Group,
+            ConversationKind::Live => ConversationType::Live,
+            ConversationKind::Private => ConversationType::Direct,
+        }
+    }
+}
+
+impl TryFrom<ParticipantRole> for PtcRole {
+    type Error = &'static str;
+
+    fn try_from(value: ParticipantRole) -> Result<Self, Self::Error> {
+        match value {
+            ParticipantRole::Owner => Ok(PtcRole::Owner),
+            ParticipantRole::Admin => Ok(PtcRole::Admin),
+            ParticipantRole::Member => Ok(PtcRole::Member),
+            ParticipantRole::Unspecified => Err("PARTICIPANT_ROLE_UNSPECIFIED"),
+        }
+    }
+}
+
+impl From<PtcRole> for ParticipantRole {
+    fn from(value: PtcRole) -> Self {
+        match value {
+            PtcRole::Owner => ParticipantRole::Owner,
+            PtcRole::Admin => ParticipantRole::Admin,
+            PtcRole::Member => ParticipantRole::Member,
+        }
+    }
+}
+
+impl From<PrsStatus> for PresenceStatus {
+    fn from(value: PrsStatus) -> Self {
+        match value {
+            PrsStatus::Online => PresenceStatus::Online,
+            PrsStatus::Away => PresenceStatus::Away,
+            PrsStatus::Offline => PresenceStatus::Offline,
         }
     }
 }
@@ -1,8 +1,13 @@
 use chrono::{DateTime, Utc};
 
 use crate::models::{
-    conversation::ConversationMessages, notification_models::push,
-    user_conversation::UserConversation, MessageType,
+    conversation::{ConversationMessages, ConversationSync, MessageChange},
+    message::ReplyPreview,
+    notification_models::push,
+    presence::PresenceStatus,
+    reaction::ReactionCount,
+    user_conversation::{ParticipantRole, UserConversation},
+    MessageType,
 };
 
 pub mod enums;
@@ -13,6 +18,12 @@ pub type MsgType = MessageType;
 pub type PushType = push::PushMessageType;
 pub type UsrCvs = UserConversation;
 pub type CvsMsg = ConversationMessages;
+pub type CvsSync = ConversationSync;
+pub type MsgChange = MessageChange;
+pub type MsgReaction = ReactionCount;
+pub type MsgReply = ReplyPreview;
+pub type PrsStatus = PresenceStatus;
+pub type PtcRole = ParticipantRole;
 
 /// Helper functions
 
@@ -1,14 +1,21 @@
-use farmera_grpc_proto::communication::{
-    ConversationDto, ConversationMessage, CreateConversationRequest, CreateConversationResponse,
-    CreatePrivateConversationResponse, GetConversationMessagesRequest,
-    GetConversationMessagesResponse, GetConversationResponse, ListConversationsResponse,
+use farmera_grpc_proto::{
+    communication::{
+        ConversationDto, ConversationMessage, CreateConversationRequest,
+        CreateConversationResponse, CreatePrivateConversationResponse,
+        GetConversationMessagesRequest, GetConversationMessagesResponse, GetConversationResponse,
+        GroupDetails as GrpcGroupDetails, ListConversationsResponse, MessageChange,
+        SyncConversationMessagesResponse, UpdateConversationRequest, UserConversation,
+    },
+    ConversationType,
 };
 
 use crate::models::{
     common_mapping_impl::*,
     conversation::{
-        Conversation, ConversationList, GetConversationDTO, MessageParams, NewConversation,
+        Conversation, ConversationList, GetConversationDTO, GroupDetails, MessageParams,
+        NewConversation, UpdateConversation, MAX_SLOW_MODE_SECS,
     },
+    ConversationKind,
 };
 
 // Convert grpc CreateConversationRequest to NewConversation model
@@ -20,7 +27,28 @@ impl TryFrom<CreateConversationRequest> for NewConversation {
             return Err("Title cannot be empty");
         }
 
-        Ok(NewConversation { title: value.title })
+        let kind = match value.r#type {
+            Some(kind) => {
+                let grpc_kind =
+                    ConversationType::try_from(kind).map_err(|_| "Invalid conversation type")?;
+                ConversationKind::try_from(grpc_kind)?
+            }
+            None => ConversationKind::default(),
+        };
+
+        if kind == ConversationKind::Private {
+            return Err("Private conversations are created with CreatePrivateConversation");
+        }
+
+        if !(0..=MAX_SLOW_MODE_SECS).contains(&value.slow_mode_secs) {
+            return Err("Invalid slow mode value");
+        }
+
+        Ok(NewConversation {
+            title: value.title,
+            kind,
+            slow_mode_secs: value.slow_mode_secs,
+        })
     }
 }
 
@@ -32,6 +60,10 @@ impl From<Conversation> for CreateConversationResponse {
             title: value.title,
             latest_message: value.latest_message,
             created_at: Some(datetime_to_grpc_timestamp(value.created_at)),
+            r#type: ConversationType::from(value.kind).into(),
+            slow_mode_secs: value.slow_mode_secs,
+            description: value.description,
+            avatar_url: value.avatar_url,
         }
     }
 }
@@ -44,6 +76,39 @@ impl From<Conversation> for GetConversationResponse {
             title: value.title,
             latest_message: value.latest_message,
             created_at: Some(datetime_to_grpc_timestamp(value.created_at)),
+            r#type: ConversationType::from(value.kind).into(),
+            slow_mode_secs: value.slow_mode_secs,
+            description: value.description,
+            avatar_url: value.avatar_url,
+        }
+    }
+}
+
+// Convert grpc UpdateConversationRequest to UpdateConversation model
+impl From<UpdateConversationRequest> for UpdateConversation {
+    fn from(value: UpdateConversationRequest) -> Self {
+        UpdateConversation {
+            title: value.title,
+            description: value.description,
+            avatar_url: value.avatar_url,
+        }
+    }
+}
+
+// Convert GroupDetails model to grpc GroupDetails
+impl From<GroupDetails> for GrpcGroupDetails {
+    fn from(value: GroupDetails) -> Self {
+        GrpcGroupDetails {
+            conversation_id: value.conversation.conversation_id,
+            title: value.conversation.title,
+            description: value.conversation.description,
+            avatar_url: value.conversation.avatar_url,
+            created_at: Some(datetime_to_grpc_timestamp(value.conversation.created_at)),
+            participants: value
+                .participants
+                .into_iter()
+                .map(UserConversation::from)
+                .collect(),
         }
     }
 }
@@ -85,6 +150,42 @@ impl From<CvsMsg> for GetConversationMessagesResponse {
     }
 }
 
+// convert MessageChange model to grpc MessageChange
+impl From<MsgChange> for MessageChange {
+    fn from(value: MsgChange) -> Self {
+        MessageChange {
+            message_id: value.message_id,
+            content: value.content,
+            edited_at: value.edited_at.map(|v| datetime_to_grpc_timestamp(v)),
+            deleted_at: value.deleted_at.map(|v| datetime_to_grpc_timestamp(v)),
+        }
+    }
+}
+
+// convert ConversationSync model to grpc SyncConversationMessagesResponse
+impl From<CvsSync> for SyncConversationMessagesResponse {
+    fn from(value: CvsSync) -> Self {
+        let messages = value
+            .messages
+            .into_iter()
+            .map(ConversationMessage::from)
+            .collect::<Vec<ConversationMessage>>();
+
+        let changes = value
+            .changes
+            .into_iter()
+            .map(MessageChange::from)
+            .collect::<Vec<MessageChange>>();
+
+        SyncConversationMessagesResponse {
+            conversation_id: value.conversation_id,
+            messages,
+            changes,
+            has_more: value.has_more,
+        }
+    }
+}
+
 // convert GetConversationDTO model to grpc ConversationDTO
 impl From<GetConversationDTO> for ConversationDto {
     fn from(value: GetConversationDTO) -> Self {
@@ -121,13 +222,15 @@ impl From<ConversationList> for ListConversationsResponse {
     }
 }
 
-impl From<Conversation> for CreatePrivateConversationResponse {
-    fn from(value: Conversation) -> Self {
+// Convert the private conversation and whether it was just created to grpc CreatePrivateConversationResponse
+impl From<(Conversation, bool)> for CreatePrivateConversationResponse {
+    fn from((value, created): (Conversation, bool)) -> Self {
         CreatePrivateConversationResponse {
             conversation_id: value.conversation_id,
             title: value.title,
             latest_message: value.latest_message,
             created_at: Some(datetime_to_grpc_timestamp(value.created_at)),
+            created,
         }
     }
 }
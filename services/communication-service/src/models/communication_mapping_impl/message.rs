@@ -1,7 +1,10 @@
 use farmera_grpc_proto::{
-    communication::{ConversationMessage, GetMessageResponse},
+    communication::{
+        ConversationMessage, GetMessageResponse, ReactionCount, ReplyPreview, UpdateMessageResponse,
+    },
     MessageType,
 };
+use sqlx::types::Json;
 use uuid::Uuid;
 
 use crate::models::common_mapping_impl::*;
@@ -24,6 +27,18 @@ impl TryFrom<ConversationMessage> for Message {
         let sent_at = grpc_timestamp_to_datetime(value.sent_at.unwrap())
             .map_err(|_| "Invalid timestamp value")?;
 
+        let edited_at = match value.edited_at {
+            Some(edited_at) => {
+                Some(grpc_timestamp_to_datetime(edited_at).map_err(|_| "Invalid timestamp value")?)
+            }
+            None => None,
+        };
+
+        let reply_to = match value.reply_to {
+            Some(reply_to) => Some(Json(MsgReply::try_from(reply_to)?)),
+            None => None,
+        };
+
         Ok(Message {
             message_id: value.message_id,
             conversation_id: value.conversation_id,
@@ -31,6 +46,9 @@ impl TryFrom<ConversationMessage> for Message {
             content: value.content,
             sent_at: sent_at,
             r#type: msg_type,
+            edited_at: edited_at,
+            reactions: Json(value.reactions.into_iter().map(MsgReaction::from).collect()),
+            reply_to: reply_to,
             is_read: value.is_read,
         })
     }
@@ -46,6 +64,65 @@ impl From<Message> for ConversationMessage {
             sent_at: Some(datetime_to_grpc_timestamp(value.sent_at)),
             r#type: MessageType::from(value.r#type) as i32,
             is_read: value.is_read,
+            edited_at: value.edited_at.map(|v| datetime_to_grpc_timestamp(v)),
+            reactions: value
+                .reactions
+                .0
+                .into_iter()
+                .map(ReactionCount::from)
+                .collect(),
+            reply_to: value.reply_to.map(|v| ReplyPreview::from(v.0)),
+        }
+    }
+}
+
+impl From<ReactionCount> for MsgReaction {
+    fn from(value: ReactionCount) -> Self {
+        MsgReaction {
+            emoji: value.emoji,
+            count: value.count,
+        }
+    }
+}
+
+impl From<MsgReaction> for ReactionCount {
+    fn from(value: MsgReaction) -> Self {
+        ReactionCount {
+            emoji: value.emoji,
+            count: value.count,
+        }
+    }
+}
+
+impl TryFrom<ReplyPreview> for MsgReply {
+    type Error = &'static str;
+
+    fn try_from(value: ReplyPreview) -> Result<Self, Self::Error> {
+        let grpc_msg_type =
+            MessageType::try_from(value.r#type).map_err(|_| "Invalid channel value")?;
+
+        let msg_type = MsgType::try_from(grpc_msg_type).map_err(|_| "Unsupported channel type")?;
+
+        let user_id = Uuid::parse_str(&value.sender_id).map_err(|_| "Invalid UUID for user id")?;
+
+        Ok(MsgReply {
+            message_id: value.message_id,
+            sender_id: user_id,
+            r#type: msg_type,
+            content: value.content,
+            deleted: value.deleted,
+        })
+    }
+}
+
+impl From<MsgReply> for ReplyPreview {
+    fn from(value: MsgReply) -> Self {
+        ReplyPreview {
+            message_id: value.message_id,
+            sender_id: value.sender_id.to_string(),
+            r#type: MessageType::from(value.r#type) as i32,
+            content: value.content,
+            deleted: value.deleted,
         }
     }
 }
@@ -59,6 +136,21 @@ impl From<Message> for GetMessageResponse {
             content: value.content,
             sent_at: Some(datetime_to_grpc_timestamp(value.sent_at)),
             r#type: MessageType::from(value.r#type) as i32,
+            edited_at: value.edited_at.map(|v| datetime_to_grpc_timestamp(v)),
+        }
+    }
+}
+
+impl From<Message> for UpdateMessageResponse {
+    fn from(value: Message) -> Self {
+        UpdateMessageResponse {
+            message_id: value.message_id,
+            conversation_id: value.conversation_id,
+            sender_id: value.sender_id.to_string(),
+            content: value.content,
+            sent_at: Some(datetime_to_grpc_timestamp(value.sent_at)),
+            r#type: MessageType::from(value.r#type) as i32,
+            edited_at: value.edited_at.map(|v| datetime_to_grpc_timestamp(v)),
         }
     }
 }
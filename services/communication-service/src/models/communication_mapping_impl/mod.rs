@@ -1,3 +1,4 @@
 pub mod conversation;
 pub mod message;
+pub mod presence;
 pub mod user_conversation;
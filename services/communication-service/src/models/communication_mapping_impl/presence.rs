@@ -0,0 +1,26 @@
+use farmera_grpc_proto::{
+    communication::{CheckOnlineUserResponse, PresenceEvent},
+    PresenceStatus,
+};
+
+use crate::models::{common_mapping_impl::*, presence::UserPresence};
+
+impl From<UserPresence> for PresenceEvent {
+    fn from(value: UserPresence) -> Self {
+        PresenceEvent {
+            user_id: value.user_id.to_string(),
+            status: PresenceStatus::from(value.status).into(),
+            last_seen_at: value.last_seen_at.map(datetime_to_grpc_timestamp),
+        }
+    }
+}
+
+impl From<UserPresence> for CheckOnlineUserResponse {
+    fn from(value: UserPresence) -> Self {
+        CheckOnlineUserResponse {
+            is_online: value.status != PrsStatus::Offline,
+            status: PresenceStatus::from(value.status).into(),
+            last_seen_at: value.last_seen_at.map(datetime_to_grpc_timestamp),
+        }
+    }
+}
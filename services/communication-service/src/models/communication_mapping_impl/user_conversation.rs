@@ -1,4 +1,7 @@
-use farmera_grpc_proto::communication::{GetConversationParticipantsResponse, UserConversation};
+use farmera_grpc_proto::{
+    communication::{GetConversationParticipantsResponse, UserConversation},
+    ParticipantRole,
+};
 
 use crate::models::{common_mapping_impl::*, user_conversation::Participants};
 
@@ -15,6 +18,9 @@ impl From<UsrCvs> for UserConversation {
             conversation_id: value.conversation_id,
             user_id: value.user_id.to_string(),
             deleted_at: deleted_at,
+            last_read_message_id: value.last_read_message_id,
+            last_delivered_message_id: value.last_delivered_message_id,
+            role: ParticipantRole::from(value.role).into(),
         }
     }
 }
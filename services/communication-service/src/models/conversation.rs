@@ -4,10 +4,15 @@ use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::{message::Message, MessageType};
+use crate::models::{
+    message::Message, user_conversation::UserConversation, ConversationKind, MessageType,
+};
 
 use super::reject_empty_string;
 
+// upper bound of the slow mode delay a room can be created with
+pub const MAX_SLOW_MODE_SECS: i32 = 60 * 60;
+
 #[derive(Debug, Serialize, FromRow, ToSchema)]
 pub struct Conversation {
     #[schema(example = 1)]
@@ -21,6 +26,18 @@ pub struct Conversation {
 
     #[schema(example = "2025-04-15T08:14:17.923998Z")]
     pub created_at: DateTime<Utc>,
+
+    #[schema(example = "group")]
+    pub kind: ConversationKind,
+
+    #[schema(example = 0)]
+    pub slow_mode_secs: i32,
+
+    #[schema(example = "Weekly harvest planning of the co-op")]
+    pub description: Option<String>,
+
+    #[schema(example = "uploads/image/1744704857-avatar.png")]
+    pub avatar_url: Option<String>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -28,6 +45,62 @@ pub struct NewConversation {
     #[schema(example = "New conversation")]
     #[serde(deserialize_with = "reject_empty_string")]
     pub title: String,
+
+    #[schema(example = "group")]
+    #[serde(default, deserialize_with = "reject_private_kind")]
+    pub kind: ConversationKind,
+
+    // minimum seconds between two messages of the same user
+    #[schema(example = 0)]
+    #[serde(default, deserialize_with = "reject_invalid_slow_mode")]
+    pub slow_mode_secs: i32,
+}
+
+// private conversations are created per pair of users through the private endpoint
+fn reject_private_kind<'de, D>(deserializer: D) -> Result<ConversationKind, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: ConversationKind = Deserialize::deserialize(deserializer)?;
+    if value == ConversationKind::Private {
+        return Err(serde::de::Error::custom(
+            "private conversations are created with the private endpoint",
+        ));
+    }
+    Ok(value)
+}
+
+fn reject_invalid_slow_mode<'de, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: i32 = Deserialize::deserialize(deserializer)?;
+    if !(0..=MAX_SLOW_MODE_SECS).contains(&value) {
+        return Err(serde::de::Error::custom(format!(
+            "slow_mode_secs must be between 0 and {MAX_SLOW_MODE_SECS}"
+        )));
+    }
+    Ok(value)
+}
+
+// Group settings to change, fields left out are kept and an empty description or avatar clears it
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateConversation {
+    #[schema(example = "Renamed conversation")]
+    pub title: Option<String>,
+
+    #[schema(example = "Weekly harvest planning of the co-op")]
+    pub description: Option<String>,
+
+    #[schema(example = "uploads/image/1744704857-avatar.png")]
+    pub avatar_url: Option<String>,
+}
+
+// Settings and members of a group, sent to the participants whenever one of them changes
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GroupDetails {
+    pub conversation: Conversation,
+    pub participants: Vec<UserConversation>,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -51,6 +124,17 @@ fn default_limit() -> Option<i32> {
     Some(20)
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SyncParams {
+    pub after_message_id: i64,
+    #[serde(default = "default_sync_limit")]
+    pub limit: Option<i32>,
+}
+
+fn default_sync_limit() -> Option<i32> {
+    Some(100)
+}
+
 // Message wrapper returns when retrieving messages from a conversation
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ConversationMessages {
@@ -81,3 +165,47 @@ pub struct GetConversationDTO {
 pub struct ConversationList {
     pub conversations: Vec<GetConversationDTO>,
 }
+
+// Edit or deletion of a message at or before the sync cursor that happened after the cursor was sent
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct MessageChange {
+    #[schema(example = 1)]
+    pub message_id: i64,
+
+    // current content, empty once the message is deleted
+    #[schema(example = "this is the edited message")]
+    pub content: Option<String>,
+
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub edited_at: Option<DateTime<Utc>>,
+
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+// Unread messages of a participant, the payload of `unread_changed` inbox events
+#[derive(Debug, Serialize, FromRow)]
+pub struct UnreadCount {
+    #[serde(skip)]
+    pub user_id: Uuid,
+    // in the conversation of the event
+    pub unread_count: i64,
+    // across all of the user's conversations, the app badge
+    pub total_unread_count: i64,
+}
+
+// Everything a client missed in a conversation after its last known message
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ConversationSync {
+    #[schema(example = 1)]
+    pub conversation_id: i32,
+
+    // newer messages in ascending order
+    pub messages: Vec<Message>,
+
+    pub changes: Vec<MessageChange>,
+
+    // more messages remain after the last returned one
+    #[schema(example = false)]
+    pub has_more: bool,
+}
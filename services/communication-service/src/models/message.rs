@@ -1,10 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use sqlx::{prelude::FromRow, types::Json};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
-use crate::models::MessageType;
+use crate::models::{reaction::ReactionCount, reject_empty_string, MessageType};
 
 #[derive(Debug, Serialize, FromRow, ToSchema)]
 pub struct Message {
@@ -25,19 +25,149 @@ pub struct Message {
 
     pub r#type: MessageType,
 
+    #[schema(value_type = Option<String>, format = "date-time")]
+    pub edited_at: Option<DateTime<Utc>>,
+
+    #[schema(value_type = Vec<ReactionCount>)]
+    pub reactions: Json<Vec<ReactionCount>>,
+
+    #[schema(value_type = Option<ReplyPreview>)]
+    pub reply_to: Option<Json<ReplyPreview>>,
+
+    // read by every participant other than the sender
     #[schema(example = true)]
     pub is_read: bool,
 }
 
 #[derive(Serialize)]
 pub struct SentMessage {
+    pub conversation_id: i32,
     pub sender_id: Uuid,
     pub r#type: String,
     pub message: String,
     pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<ReplyPreview>,
 }
 
 #[derive(Deserialize)]
 pub struct MessageContent {
     pub message: String,
+    // id of the quoted message, must belong to the same conversation
+    #[serde(default)]
+    pub reply_to: Option<i64>,
+}
+
+// Short preview of the quoted message, content is cut to 100 chars and empty once the message is deleted
+#[derive(Debug, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct ReplyPreview {
+    #[schema(example = 1)]
+    pub message_id: i64,
+
+    #[schema(value_type = String, format = "uuid", example = "c8dd591b-4105-4608-869b-1dfb96f313b3")]
+    pub sender_id: Uuid,
+
+    pub r#type: MessageType,
+
+    #[schema(example = "this is the first message")]
+    pub content: Option<String>,
+
+    #[schema(example = false)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateMessage {
+    #[schema(example = "this is the edited message")]
+    #[serde(deserialize_with = "reject_empty_string")]
+    pub content: String,
+}
+
+// Previous version of an edited message
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct MessageEdit {
+    #[schema(example = 1)]
+    pub edit_id: i64,
+
+    #[schema(example = 1)]
+    pub message_id: i64,
+
+    #[schema(example = "this is the first message")]
+    pub previous_content: Option<String>,
+
+    #[schema(example = "2025-04-15T08:14:17.923998Z")]
+    pub edited_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageEdits {
+    pub edits: Vec<MessageEdit>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageReplies {
+    pub replies: Vec<Message>,
+}
+
+// Edit event broadcast to the message's room
+#[derive(Serialize)]
+pub struct EditedMessage {
+    pub r#type: String,
+    pub conversation_id: i32,
+    pub message_id: i64,
+    pub sender_id: Uuid,
+    pub message: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteScope {
+    // unsend the message for every participant
+    #[default]
+    Everyone,
+    // hide the message from the requesting user only
+    Me,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteMessageParams {
+    #[serde(default)]
+    pub scope: DeleteScope,
+}
+
+// Deletion event broadcast to the message's room
+#[derive(Serialize)]
+pub struct DeletedMessage {
+    pub r#type: String,
+    pub conversation_id: i32,
+    pub message_id: i64,
+    pub deleted_at: DateTime<Utc>,
+}
+
+// sender id of messages generated by the server
+pub const SYSTEM_SENDER_ID: Uuid = Uuid::nil();
+
+// Server generated event stored as the content of a system message
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SystemEvent {
+    ConversationCreated { title: String },
+    ConversationRenamed { title: String },
+    ParticipantAdded { user_id: Uuid },
+    ParticipantRemoved { user_id: Uuid },
+    OwnershipTransferred { user_id: Uuid },
+    // free text posted by other services, e.g. order updates
+    Note { text: String },
+}
+
+// System message broadcast to a room
+#[derive(Serialize)]
+pub struct SentSystemMessage {
+    pub conversation_id: i32,
+    pub message_id: i64,
+    pub sender_id: Uuid,
+    pub r#type: String,
+    pub event: SystemEvent,
+    pub timestamp: DateTime<Utc>,
 }
@@ -8,6 +8,8 @@ pub mod conversation;
 pub mod message;
 pub mod notification_mapping_impl;
 pub mod notification_models;
+pub mod presence;
+pub mod reaction;
 pub mod response_wrapper;
 pub mod upload_form;
 pub mod user_conversation;
@@ -19,6 +21,19 @@ pub mod ws;
 pub enum MessageType {
     Media,
     Message,
+    System,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, sqlx::Type, Clone, Copy, PartialEq, Default)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationKind {
+    #[default]
+    Group,
+    // public live-stream room, messages are not all persisted
+    Live,
+    // one-to-one conversation, a pair of users has at most one
+    Private,
 }
 
 pub fn reject_empty_string<'de, D>(deserializer: D) -> Result<String, D::Error>
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, Display, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    // at least one session is active
+    #[display("online")]
+    Online,
+
+    // connected, but every session reported being idle
+    #[display("away")]
+    Away,
+
+    #[display("offline")]
+    Offline,
+}
+
+// Activity of a single session as reported by the client
+#[derive(Debug, Deserialize, Serialize, Display, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityState {
+    #[display("active")]
+    Active,
+
+    #[display("idle")]
+    Idle,
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct UserPresence {
+    #[schema(value_type = String, format = "uuid", example = "c8dd591b-4105-4608-869b-1dfb96f313b3")]
+    pub user_id: Uuid,
+
+    #[schema(example = "online")]
+    pub status: PresenceStatus,
+
+    #[schema(example = "2025-04-15T08:14:17.923998Z")]
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+// Presence change pushed to the user's conversation partners and presence streams
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PresenceChanged {
+    pub r#type: String,
+    #[serde(flatten)]
+    pub presence: UserPresence,
+}
+
+impl PresenceChanged {
+    pub fn new(presence: UserPresence) -> Self {
+        Self {
+            r#type: "presence_changed".to_string(),
+            presence,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::reject_empty_string;
+
+const MAX_EMOJI_LENGTH: usize = 16; // in chars, enough for multi code point emojis
+
+#[derive(Debug, Deserialize, Serialize, FromRow, ToSchema)]
+pub struct ReactionCount {
+    #[schema(example = "👍")]
+    pub emoji: String,
+
+    #[schema(example = 2)]
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MessageReactions {
+    pub reactions: Vec<ReactionCount>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewReaction {
+    #[schema(example = "👍")]
+    #[serde(deserialize_with = "reject_empty_string")]
+    pub emoji: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Display, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ReactionAction {
+    #[display("add")]
+    Add,
+
+    #[display("remove")]
+    Remove,
+}
+
+// Reaction change broadcast to the message's room
+#[derive(Debug, Serialize)]
+pub struct ReactionEvent {
+    pub r#type: String,
+    pub conversation_id: i32,
+    pub message_id: i64,
+    pub user_id: Uuid,
+    pub emoji: String,
+    pub action: ReactionAction,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ReactionEvent {
+    pub fn new(
+        conversation_id: i32,
+        message_id: i64,
+        user_id: Uuid,
+        emoji: String,
+        action: ReactionAction,
+    ) -> Self {
+        Self {
+            r#type: "reaction".to_string(),
+            conversation_id,
+            message_id,
+            user_id,
+            emoji,
+            action,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+pub fn validate_emoji(emoji: &str) -> Result<(), &'static str> {
+    if emoji.is_empty() || emoji.chars().count() > MAX_EMOJI_LENGTH {
+        return Err("Invalid emoji length");
+    }
+
+    if emoji
+        .chars()
+        .any(|c| c.is_ascii_alphanumeric() || c.is_whitespace())
+    {
+        return Err("Reaction must be an emoji");
+    }
+
+    Ok(())
+}
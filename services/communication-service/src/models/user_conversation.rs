@@ -1,9 +1,42 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+// Role of a participant in a group, ordered by the permissions it grants
+#[derive(
+    Debug,
+    Deserialize,
+    Serialize,
+    ToSchema,
+    sqlx::Type,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Default,
+)]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ParticipantRole {
+    #[default]
+    #[display("member")]
+    Member,
+
+    // manages members and the group settings
+    #[display("admin")]
+    Admin,
+
+    // one per group, also manages admins and may hand the group over
+    #[display("owner")]
+    Owner,
+}
+
 #[derive(Debug, Serialize, FromRow, ToSchema)]
 pub struct UserConversation {
     #[schema(example = 1)]
@@ -17,9 +50,37 @@ pub struct UserConversation {
 
     #[schema(value_type = Option<String>, format = "date-time")]
     pub deleted_at: Option<DateTime<Utc>>,
+
+    #[schema(example = 1)]
+    pub last_read_message_id: Option<i64>,
+
+    #[schema(example = 1)]
+    pub last_delivered_message_id: Option<i64>,
+
+    #[schema(example = "member")]
+    pub role: ParticipantRole,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct Participants {
     pub participants: Vec<UserConversation>,
 }
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewParticipants {
+    #[schema(value_type = Vec<String>, format = "uuid", example = json!["c8dd591b-4105-4608-869b-1dfb96f313b3"])]
+    pub user_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateParticipantRole {
+    // ownership is handed over through the transfer endpoint
+    #[schema(example = "admin")]
+    pub role: ParticipantRole,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransferOwnership {
+    #[schema(value_type = String, format = "uuid", example = "c8dd591b-4105-4608-869b-1dfb96f313b3")]
+    pub user_id: Uuid,
+}
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Serialize, Display)]
 pub enum Event {
@@ -15,6 +17,21 @@ pub enum Event {
     #[display("leave")]
     Leave,
 
+    #[display("typing")]
+    Typing,
+
+    #[display("read")]
+    Read,
+
+    #[display("reaction")]
+    Reaction,
+
+    #[display("sync")]
+    Sync,
+
+    #[display("presence")]
+    Presence,
+
     #[display("error")]
     Error,
 }
@@ -33,3 +50,117 @@ pub struct WSResponse {
     pub data: serde_json::Value,
     pub status: String,
 }
+
+// Typing indicator broadcast to the other participants of a room
+#[derive(Debug, Serialize)]
+pub struct TypingIndicator {
+    pub conversation_id: i32,
+    pub sender_id: Uuid,
+    pub r#type: String,
+    pub is_typing: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Acknowledgement returned to the sender of a message, also replayed for retried sends
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MessageAck {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Display, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptState {
+    #[display("delivered")]
+    Delivered,
+
+    #[display("read")]
+    Read,
+}
+
+// Per-recipient delivery state broadcast to a room
+#[derive(Debug, Serialize)]
+pub struct ReadReceipt {
+    pub r#type: String,
+    pub conversation_id: i32,
+    pub user_id: Uuid,
+    pub message_id: i64,
+    pub state: ReceiptState,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ReadReceipt {
+    pub fn new(conversation_id: i32, user_id: Uuid, message_id: i64, state: ReceiptState) -> Self {
+        Self {
+            r#type: "read".to_string(),
+            conversation_id,
+            user_id,
+            message_id,
+            state,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Display, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum InboxEventType {
+    #[display("conversation_updated")]
+    ConversationUpdated,
+
+    #[display("new_conversation")]
+    NewConversation,
+
+    #[display("unread_changed")]
+    UnreadChanged,
+
+    #[display("group_updated")]
+    GroupUpdated,
+
+    // the user was removed from the group, its sessions leave the room
+    #[display("removed_from_conversation")]
+    RemovedFromConversation,
+}
+
+// Conversation list event published to the `user:{id}:inbox` channel of a participant
+#[derive(Debug, Serialize)]
+pub struct InboxEvent {
+    pub r#type: InboxEventType,
+    pub conversation_id: i32,
+    pub data: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl InboxEvent {
+    pub fn new(r#type: InboxEventType, conversation_id: i32, data: serde_json::Value) -> Self {
+        Self {
+            r#type,
+            conversation_id,
+            data,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+// Number of viewers of a live room, broadcast periodically
+#[derive(Debug, Serialize)]
+pub struct ViewerCount {
+    pub r#type: String,
+    pub conversation_id: i32,
+    pub viewers: i64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ViewerCount {
+    pub fn new(conversation_id: i32, viewers: i64) -> Self {
+        Self {
+            r#type: "viewer_count".to_string(),
+            conversation_id,
+            viewers,
+            timestamp: Utc::now(),
+        }
+    }
+}
@@ -1,20 +1,33 @@
 use utoipa::OpenApi;
 
-use crate::docs::{attachment_doc, conversation_doc, message_doc, user_doc};
+use crate::docs::{attachment_doc, conversation_doc, message_doc, reaction_doc, user_doc};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         message_doc::get_message_by_id,
         message_doc::delete_message,
+        message_doc::update_message,
+        message_doc::get_message_edits,
+        message_doc::get_message_replies,
+
+        reaction_doc::get_message_reactions,
+        reaction_doc::add_reaction,
+        reaction_doc::remove_reaction,
 
         conversation_doc::get_conversation_by_id,
         conversation_doc::create_conversation,
         conversation_doc::delete_conversation,
         conversation_doc::get_conversation_participants,
         conversation_doc::get_conversation_messages,
+        conversation_doc::sync_conversation_messages,
         conversation_doc::get_user_conversations,
         conversation_doc::create_private_conversation,
+        conversation_doc::update_conversation,
+        conversation_doc::add_participants,
+        conversation_doc::update_participant_role,
+        conversation_doc::remove_participant,
+        conversation_doc::transfer_ownership,
 
         attachment_doc::upload_file,
         attachment_doc::get_file,
@@ -22,10 +35,12 @@ use crate::docs::{attachment_doc, conversation_doc, message_doc, user_doc};
         attachment_doc::get_attachments_by_conversation_id,
         attachment_doc::get_attachments_by_message_id,
 
-        user_doc::check_online_user
+        user_doc::check_online_user,
+        user_doc::get_user_presence
     ),
     tags(
         (name = "Message", description = "Message operations"),
+        (name = "Reaction", description = "Reaction operations"),
         (name = "Conversation", description = "Conversation operations"),
         (name = "Attachment", description = "Attachment operations"),
         (name = "User", description = "User operations"),
@@ -1,16 +1,24 @@
 use std::{error, sync::Arc};
 
+use chrono::{DateTime, Utc};
 use deadpool_redis::Pool;
+use futures_util::{stream::BoxStream, StreamExt};
 use redis::AsyncCommands;
 use uuid::Uuid;
 
+use crate::models::presence::{PresenceChanged, PresenceStatus, UserPresence};
+
 pub struct UserRedisRepo {
     redis_pool: Arc<Pool>,
+    redis_client: Arc<redis::Client>,
 }
 
 impl UserRedisRepo {
-    pub fn new(redis_pool: Arc<Pool>) -> Self {
-        Self { redis_pool }
+    pub fn new(redis_pool: Arc<Pool>, redis_client: Arc<redis::Client>) -> Self {
+        Self {
+            redis_pool,
+            redis_client,
+        }
     }
 
     pub async fn get_online_users(&self) -> Result<Vec<String>, Box<dyn error::Error>> {
@@ -28,4 +36,51 @@ impl UserRedisRepo {
 
         Ok(is_online)
     }
+
+    pub async fn get_user_presence(
+        &self,
+        user_id: Uuid,
+    ) -> Result<UserPresence, Box<dyn error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        let (status, last_seen_at): (Option<String>, Option<String>) = redis_conn
+            .hget(&format!("user:{user_id}"), &["status", "last_seen_at"])
+            .await?;
+
+        // users that never connected have no presence yet
+        let status = status
+            .and_then(|status| serde_json::from_value(serde_json::Value::String(status)).ok())
+            .unwrap_or(PresenceStatus::Offline);
+
+        let last_seen_at = last_seen_at
+            .and_then(|last_seen_at| DateTime::parse_from_rfc3339(&last_seen_at).ok())
+            .map(|last_seen_at| last_seen_at.with_timezone(&Utc));
+
+        Ok(UserPresence {
+            user_id,
+            status,
+            last_seen_at,
+        })
+    }
+
+    /// Presence changes of the given users, published by the chat servers on `user:{id}:presence`
+    pub async fn subscribe_presence(
+        &self,
+        user_ids: &[Uuid],
+    ) -> Result<BoxStream<'static, UserPresence>, Box<dyn error::Error>> {
+        let mut pubsub = self.redis_client.get_async_pubsub().await?;
+
+        for user_id in user_ids {
+            pubsub.subscribe(format!("user:{user_id}:presence")).await?;
+        }
+
+        let stream = pubsub.into_on_message().filter_map(|msg| async move {
+            let payload: String = msg.get_payload().ok()?;
+            serde_json::from_str::<PresenceChanged>(&payload)
+                .ok()
+                .map(|event| event.presence)
+        });
+
+        Ok(Box::pin(stream))
+    }
 }
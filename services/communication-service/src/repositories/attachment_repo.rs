@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
     errors::db_error::DBError,
@@ -45,6 +46,7 @@ impl AttachmentRepo {
         &self,
         message_id: Option<i64>,
         conversation_id: Option<i32>,
+        uploader_id: Option<Uuid>,
         media_contents: &Vec<MediaContent>,
     ) -> Result<Vec<i32>, DBError> {
         if media_contents.is_empty() {
@@ -52,7 +54,7 @@ impl AttachmentRepo {
         }
 
         let mut query = String::from(
-            "INSERT INTO attachments (message_id, conversation_id, file_url, file_size, file_type) VALUES ",
+            "INSERT INTO attachments (message_id, conversation_id, file_url, file_size, file_type, uploader_id) VALUES ",
         );
         let mut params: Vec<String> = vec![];
         let mut msg_id = vec![];
@@ -62,14 +64,15 @@ impl AttachmentRepo {
         let mut types = vec![];
 
         for (i, media) in media_contents.iter().enumerate() {
-            let base = i * 5;
+            let base = i * 6;
             params.push(format!(
-                "(${}, ${}, ${}, ${}, ${})",
+                "(${}, ${}, ${}, ${}, ${}, ${})",
                 base + 1,
                 base + 2,
                 base + 3,
                 base + 4,
-                base + 5
+                base + 5,
+                base + 6
             ));
 
             msg_id.push(message_id);
@@ -90,7 +93,8 @@ impl AttachmentRepo {
                 .bind(conversation_ids[i])
                 .bind(urls[i].clone())
                 .bind(sizes[i])
-                .bind(types[i].clone());
+                .bind(types[i].clone())
+                .bind(uploader_id);
         }
 
         let row_ids: Vec<i32> = q.fetch_all(&*self.pg_pool).await.map_err(|e| {
@@ -119,7 +123,7 @@ impl AttachmentRepo {
         Ok(result)
     }
 
-    pub async fn _get_attachment_by_url(
+    pub async fn get_attachment_by_url(
         &self,
         file_url: &str,
     ) -> Result<Option<Attachment>, DBError> {
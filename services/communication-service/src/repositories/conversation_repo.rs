@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
@@ -7,9 +7,12 @@ use uuid::Uuid;
 use crate::{
     errors::db_error::DBError,
     models::{
-        conversation::{Conversation, GetConversationDTO},
+        conversation::{
+            Conversation, ConversationSync, GetConversationDTO, MessageChange, UnreadCount,
+        },
         message::Message,
-        user_conversation::UserConversation,
+        user_conversation::{ParticipantRole, UserConversation},
+        ConversationKind,
     },
 };
 
@@ -37,18 +40,78 @@ impl ConversationRepo {
         Ok(result)
     }
 
-    pub async fn insert_conversation(&self, title: &str) -> Result<Conversation, DBError> {
-        let stm = include_str!("./queries/conversation/insert_conversation.sql");
+    /// Creates the conversation, owned by `owner_id` when given
+    pub async fn insert_conversation(
+        &self,
+        title: &str,
+        kind: ConversationKind,
+        slow_mode_secs: i32,
+        owner_id: Option<Uuid>,
+    ) -> Result<Conversation, DBError> {
+        let insert_conversation_stm =
+            include_str!("./queries/conversation/insert_conversation.sql");
+        let insert_participants_stm =
+            include_str!("./queries/user_conversation/insert_participants.sql");
 
-        let result = sqlx::query_as(stm)
+        let mut tx = self.pg_db_pool.begin().await.map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            DBError::TransactionError("Failed to begin transaction".to_string())
+        })?;
+
+        let conversation: Conversation = sqlx::query_as(insert_conversation_stm)
             .bind(title)
-            .fetch_one(&*self.pg_db_pool)
+            .bind(kind)
+            .bind(slow_mode_secs)
+            .fetch_one(&mut *tx)
             .await
             .map_err(|e| {
                 log::error!("Insert conversation error: {e}");
                 DBError::QueryError(e)
             })?;
 
+        if let Some(owner_id) = owner_id {
+            sqlx::query(insert_participants_stm)
+                .bind(conversation.conversation_id)
+                .bind(vec![owner_id])
+                .bind(ParticipantRole::Owner)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    log::error!("Insert conversation owner error: {e}");
+                    DBError::QueryError(e)
+                })?;
+        }
+
+        tx.commit().await.map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            DBError::TransactionError("Failed to commit transaction".to_string())
+        })?;
+
+        Ok(conversation)
+    }
+
+    /// Changes the given group settings, returns the updated conversation
+    pub async fn update_conversation(
+        &self,
+        conversation_id: i32,
+        title: Option<&str>,
+        description: Option<&str>,
+        avatar_url: Option<&str>,
+    ) -> Result<Option<Conversation>, DBError> {
+        let stm = include_str!("./queries/conversation/update_conversation.sql");
+
+        let result = sqlx::query_as(stm)
+            .bind(conversation_id)
+            .bind(title)
+            .bind(description)
+            .bind(avatar_url)
+            .fetch_optional(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Update conversation error: {e}");
+                DBError::QueryError(e)
+            })?;
+
         Ok(result)
     }
 
@@ -72,6 +135,148 @@ impl ConversationRepo {
         Ok(result)
     }
 
+    pub async fn find_participant(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+    ) -> Result<Option<UserConversation>, DBError> {
+        let stm = include_str!("./queries/user_conversation/find_participant.sql");
+
+        let result = sqlx::query_as(stm)
+            .bind(conversation_id)
+            .bind(user_id)
+            .fetch_optional(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching participant error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    /// Adds the users with the given role, returns the ones that were not participants yet
+    pub async fn insert_participants(
+        &self,
+        conversation_id: i32,
+        user_ids: &[Uuid],
+        role: ParticipantRole,
+    ) -> Result<Vec<Uuid>, DBError> {
+        if user_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let stm = include_str!("./queries/user_conversation/insert_participants.sql");
+
+        let result: Vec<Uuid> = sqlx::query_scalar(stm)
+            .bind(conversation_id)
+            .bind(user_ids)
+            .bind(role)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Insert participants error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    pub async fn delete_participant(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+    ) -> Result<u64, DBError> {
+        let stm = include_str!("./queries/user_conversation/delete_participant.sql");
+
+        let result = sqlx::query(stm)
+            .bind(conversation_id)
+            .bind(user_id)
+            .execute(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Delete participant error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        if result.rows_affected() == 0 {
+            log::error!("Delete participant returns 0 rows affected");
+            Err(DBError::NotFound("Participant not found".to_string()))
+        } else {
+            Ok(result.rows_affected())
+        }
+    }
+
+    pub async fn update_participant_role(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+        role: ParticipantRole,
+    ) -> Result<u64, DBError> {
+        let stm = include_str!("./queries/user_conversation/update_participant_role.sql");
+
+        let result = sqlx::query(stm)
+            .bind(conversation_id)
+            .bind(user_id)
+            .bind(role)
+            .execute(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Update participant role error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        if result.rows_affected() == 0 {
+            log::error!("Update participant role returns 0 rows affected");
+            Err(DBError::NotFound("Participant not found".to_string()))
+        } else {
+            Ok(result.rows_affected())
+        }
+    }
+
+    /// Hands the group over to `new_owner_id`, the previous owner stays on as admin
+    pub async fn transfer_ownership(
+        &self,
+        conversation_id: i32,
+        owner_id: Uuid,
+        new_owner_id: Uuid,
+    ) -> Result<(), DBError> {
+        let stm = include_str!("./queries/user_conversation/update_participant_role.sql");
+
+        let mut tx = self.pg_db_pool.begin().await.map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            DBError::TransactionError("Failed to begin transaction".to_string())
+        })?;
+
+        // demote first, a group may only have one owner at a time
+        for (user_id, role) in [
+            (owner_id, ParticipantRole::Admin),
+            (new_owner_id, ParticipantRole::Owner),
+        ] {
+            let result = sqlx::query(stm)
+                .bind(conversation_id)
+                .bind(user_id)
+                .bind(role)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    log::error!("Transfer ownership error: {e}");
+                    DBError::QueryError(e)
+                })?;
+
+            if result.rows_affected() == 0 {
+                return Err(DBError::NotFound("Participant not found".to_string()));
+            }
+        }
+
+        tx.commit().await.map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            DBError::TransactionError("Failed to commit transaction".to_string())
+        })?;
+
+        Ok(())
+    }
+
     pub async fn find_users_by_conversation_id(
         &self,
         conversation_id: i32,
@@ -132,6 +337,22 @@ impl ConversationRepo {
         Ok(result)
     }
 
+    /// Users who share at least one conversation with the given user
+    pub async fn find_conversation_partners(&self, user_id: Uuid) -> Result<Vec<Uuid>, DBError> {
+        let stm = include_str!("./queries/user_conversation/find_conversation_partners.sql");
+
+        let result: Vec<Uuid> = sqlx::query_scalar(stm)
+            .bind(user_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching conversation partners error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
     pub async fn check_user_in_conversation(
         &self,
         conversation_id: i32,
@@ -172,12 +393,14 @@ impl ConversationRepo {
         }
     }
 
+    /// Pages backwards by `before`, or forwards in ascending order when `after_message_id` is set
     pub async fn get_messages_by_conversation_id(
         &self,
         user_id: Uuid,
         conversation_id: i32,
         limit: Option<i32>,
         before: Option<DateTime<Utc>>,
+        after_message_id: Option<i64>,
     ) -> Result<Vec<Message>, DBError> {
         // check user in conversation
         let existed = self
@@ -194,6 +417,8 @@ impl ConversationRepo {
             .bind(conversation_id)
             .bind(before)
             .bind(limit)
+            .bind(user_id)
+            .bind(after_message_id)
             .fetch_all(&*self.pg_db_pool)
             .await
             .map_err(|e| {
@@ -204,6 +429,73 @@ impl ConversationRepo {
         Ok(result)
     }
 
+    /// Messages newer than `after_message_id` plus edits and deletions of older ones since it was
+    /// sent. Returns `None` if the cursor is not a message of the conversation, the client has to
+    /// resync from scratch. A cursor of 0 syncs from the first message
+    pub async fn sync_messages(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        after_message_id: i64,
+        limit: Option<i32>,
+    ) -> Result<Option<ConversationSync>, DBError> {
+        if after_message_id != 0 && !self.has_message(conversation_id, after_message_id).await? {
+            return Ok(None);
+        }
+
+        let messages = self
+            .get_messages_by_conversation_id(
+                user_id,
+                conversation_id,
+                limit,
+                None,
+                Some(after_message_id),
+            )
+            .await?;
+
+        let stm = include_str!("./queries/conversation/get_message_changes_after.sql");
+
+        let changes: Vec<MessageChange> = sqlx::query_as(stm)
+            .bind(conversation_id)
+            .bind(after_message_id)
+            .bind(user_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching message changes in conversation error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        let has_more = limit.is_some_and(|limit| messages.len() as i32 >= limit);
+
+        Ok(Some(ConversationSync {
+            conversation_id,
+            messages,
+            changes,
+            has_more,
+        }))
+    }
+
+    pub async fn has_message(
+        &self,
+        conversation_id: i32,
+        message_id: i64,
+    ) -> Result<bool, DBError> {
+        let stm = include_str!("./queries/conversation/message_in_conversation.sql");
+
+        let result: bool = sqlx::query_scalar(stm)
+            .bind(conversation_id)
+            .bind(message_id)
+            .fetch_one(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Checking message in conversation error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
     pub async fn get_conversation_by_user_id(
         &self,
         user_id: Uuid,
@@ -226,35 +518,60 @@ impl ConversationRepo {
         Ok(result)
     }
 
+    /// Returns the private conversation of the two users, creating it when they have none yet.
+    /// The flag tells whether it was created by this call.
     pub async fn insert_private_conversation(
         &self,
         title: &str,
         user_a: Uuid,
         user_b: Uuid,
-    ) -> Result<Conversation, DBError> {
+    ) -> Result<(Conversation, bool), DBError> {
         let insert_conversation_stm =
-            include_str!("./queries/conversation/insert_conversation.sql");
-        let insert_user_convsersation_stm =
-            include_str!("./queries/user_conversation/insert_user_conversation.sql");
+            include_str!("./queries/conversation/insert_private_conversation.sql");
+        let insert_participants_stm =
+            include_str!("./queries/user_conversation/insert_participants.sql");
+
+        let pair_key = Self::pair_key(user_a, user_b);
 
         let mut tx = self.pg_db_pool.begin().await.map_err(|e| {
             log::error!("Failed to begin transaction: {}", e);
             DBError::TransactionError("Failed to begin transaction".to_string())
         })?;
 
-        let conversation: Conversation = sqlx::query_as(insert_conversation_stm)
+        let conversation: Option<Conversation> = sqlx::query_as(insert_conversation_stm)
             .bind(title)
-            .fetch_one(&mut *tx)
+            .bind(&pair_key)
+            .fetch_optional(&mut *tx)
             .await
             .map_err(|e| {
-                log::error!("Insert user in conversation error: {e}");
+                log::error!("Insert private conversation error: {e}");
                 DBError::QueryError(e)
             })?;
 
-        let conversation_id = conversation.conversation_id;
-        sqlx::query(insert_user_convsersation_stm)
-            .bind(conversation_id)
-            .bind(user_a)
+        let conversation = match conversation {
+            Some(conversation) => conversation,
+            None => {
+                tx.rollback().await.map_err(|e| {
+                    log::error!("Failed to rollback transaction: {}", e);
+                    DBError::TransactionError("Failed to rollback transaction".to_string())
+                })?;
+
+                // the conflicting insert has committed by now, so the conversation is visible
+                let conversation = self
+                    .find_private_conversation(&pair_key)
+                    .await?
+                    .ok_or_else(|| {
+                        DBError::NotFound("Private conversation not found".to_string())
+                    })?;
+
+                return Ok((conversation, false));
+            }
+        };
+
+        sqlx::query(insert_participants_stm)
+            .bind(conversation.conversation_id)
+            .bind(vec![user_a, user_b])
+            .bind(ParticipantRole::Member)
             .execute(&mut *tx)
             .await
             .map_err(|e| {
@@ -262,23 +579,76 @@ impl ConversationRepo {
                 DBError::QueryError(e)
             })?;
 
-        let conversation_id = conversation.conversation_id;
-        sqlx::query(insert_user_convsersation_stm)
+        tx.commit().await.map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            DBError::TransactionError("Failed to commit transaction".to_string())
+        })?;
+
+        Ok((conversation, true))
+    }
+
+    async fn find_private_conversation(
+        &self,
+        pair_key: &str,
+    ) -> Result<Option<Conversation>, DBError> {
+        let stm = include_str!("./queries/conversation/find_private_conversation.sql");
+
+        let result = sqlx::query_as(stm)
+            .bind(pair_key)
+            .fetch_optional(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching private conversation error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    // same key for either order of the two users
+    fn pair_key(user_a: Uuid, user_b: Uuid) -> String {
+        let (low, high) = if user_a <= user_b {
+            (user_a, user_b)
+        } else {
+            (user_b, user_a)
+        };
+        format!("{low}:{high}")
+    }
+
+    /// Unread counts of several users in a conversation and overall, users without
+    /// conversations are left out
+    pub async fn get_unread_counts(
+        &self,
+        conversation_id: i32,
+        user_ids: &[Uuid],
+    ) -> Result<HashMap<Uuid, UnreadCount>, DBError> {
+        let stm = include_str!("./queries/conversation/unread_counts.sql");
+
+        let result: Vec<UnreadCount> = sqlx::query_as(stm)
             .bind(conversation_id)
-            .bind(user_b)
-            .execute(&mut *tx)
+            .bind(user_ids)
+            .fetch_all(&*self.pg_db_pool)
             .await
             .map_err(|e| {
-                log::error!("Insert user in conversation error: {e}");
+                log::error!("Get unread message counts error: {e}");
                 DBError::QueryError(e)
             })?;
 
-        tx.commit().await.map_err(|e| {
-            log::error!("Failed to commit transaction: {}", e);
-            DBError::TransactionError("Failed to commit transaction".to_string())
-        })?;
+        Ok(result
+            .into_iter()
+            .map(|count| (count.user_id, count))
+            .collect())
+    }
 
-        Ok(conversation)
+    pub async fn get_conversation_unread_count(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+    ) -> Result<UnreadCount, DBError> {
+        self.get_unread_counts(conversation_id, &[user_id])
+            .await?
+            .remove(&user_id)
+            .ok_or_else(|| DBError::NotFound("Participant not found".to_string()))
     }
 
     pub async fn get_unread_count(&self, user_id: Uuid) -> Result<i64, DBError> {
@@ -296,25 +666,58 @@ impl ConversationRepo {
         Ok(count)
     }
 
-    pub async fn mark_as_read(&self, conversation_id: i32, user_id: Uuid) -> Result<bool, DBError> {
+    /// Moves the user's read cursor forward up to `message_id` (or the latest message), returns the new cursor if it moved
+    pub async fn mark_as_read(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+        message_id: Option<i64>,
+    ) -> Result<Option<i64>, DBError> {
         let stm = include_str!("./queries/conversation/mark_as_read.sql");
 
-        let result = sqlx::query(stm)
+        let result: Option<i64> = sqlx::query_scalar(stm)
             .bind(conversation_id)
             .bind(user_id)
-            .execute(&*self.pg_db_pool)
+            .bind(message_id)
+            .fetch_optional(&*self.pg_db_pool)
             .await
             .map_err(|e| {
-                log::error!("Get unread message count error: {e}");
+                log::error!("Mark message as read error: {e}");
                 DBError::QueryError(e)
             })?;
 
-        if result.rows_affected() == 0 {
+        if result.is_none() {
             log::warn!("Mark message as read returns 0 rows affected");
-            Ok(false)
-        } else {
-            Ok(true)
         }
+
+        Ok(result)
+    }
+
+    /// Moves the delivery cursor of the given users forward up to `message_id`, returns the users whose cursor moved
+    pub async fn mark_as_delivered(
+        &self,
+        conversation_id: i32,
+        user_ids: &[Uuid],
+        message_id: i64,
+    ) -> Result<Vec<(Uuid, i64)>, DBError> {
+        if user_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let stm = include_str!("./queries/conversation/mark_as_delivered.sql");
+
+        let result: Vec<(Uuid, i64)> = sqlx::query_as(stm)
+            .bind(conversation_id)
+            .bind(user_ids)
+            .bind(message_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Mark message as delivered error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
     }
 
     pub async fn update_latest_message(
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use chrono::{DateTime, Utc};
 use sqlx::PgPool;
@@ -6,7 +6,11 @@ use uuid::Uuid;
 
 use crate::{
     errors::db_error::DBError,
-    models::{message::Message, MessageType},
+    models::{
+        attachment::Attachment,
+        message::{Message, MessageEdit, ReplyPreview},
+        MessageType,
+    },
 };
 
 pub struct MessageRepo {
@@ -18,6 +22,10 @@ impl MessageRepo {
         Self { pg_db_pool }
     }
 
+    /// Returns the id and send time of the new message, a retried insert with the same
+    /// `client_message_id` returns the ones of the stored message.
+    /// The flag is false when the message was already stored, an id reused for a different
+    /// message fails.
     pub async fn insert_message(
         &self,
         conversation_id: i32,
@@ -25,25 +33,121 @@ impl MessageRepo {
         content: Option<String>,
         r#type: MessageType,
         sent_at: DateTime<Utc>,
-        is_read: bool,
-    ) -> Result<i64, DBError> {
+        reply_to_message_id: Option<i64>,
+        client_message_id: Option<String>,
+    ) -> Result<(i64, DateTime<Utc>, bool), DBError> {
         let stm = include_str!("./queries/message/insert_message.sql");
 
-        let result: i64 = sqlx::query_scalar(stm)
+        let result: Option<(i64, DateTime<Utc>, bool)> = sqlx::query_as(stm)
             .bind(conversation_id)
             .bind(sender_id)
             .bind(content)
             .bind(r#type)
             .bind(sent_at)
-            .bind(is_read)
-            .fetch_one(&*self.pg_db_pool)
+            .bind(reply_to_message_id)
+            .bind(client_message_id)
+            .fetch_optional(&*self.pg_db_pool)
             .await
             .map_err(|e| {
                 log::error!("Insert message error: {e}");
                 DBError::QueryError(e)
             })?;
 
-        Ok(result)
+        result.ok_or_else(Self::client_message_id_reused)
+    }
+
+    /// Stores a media message and links the sender's uploaded attachments of the conversation to it by URL,
+    /// fails if any of the URLs is not such an attachment. The flag is false when the message was already stored,
+    /// an id reused for a message with other attachments fails.
+    pub async fn insert_media_message(
+        &self,
+        conversation_id: i32,
+        sender_id: Uuid,
+        sent_at: DateTime<Utc>,
+        client_message_id: Option<String>,
+        urls: &[String],
+    ) -> Result<(i64, DateTime<Utc>, bool), DBError> {
+        let insert_message_stm = include_str!("./queries/message/insert_message.sql");
+        let link_attachments_stm =
+            include_str!("./queries/attachment/link_attachments_to_message.sql");
+        let get_attachments_stm =
+            include_str!("./queries/attachment/get_attachment_by_message_id.sql");
+
+        let mut tx = self.pg_db_pool.begin().await.map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            DBError::TransactionError("Failed to begin transaction".to_string())
+        })?;
+
+        let (message_id, sent_at, inserted): (i64, DateTime<Utc>, bool) =
+            sqlx::query_as(insert_message_stm)
+                .bind(conversation_id)
+                .bind(sender_id)
+                .bind(None::<String>)
+                .bind(MessageType::Media)
+                .bind(sent_at)
+                .bind(None::<i64>)
+                .bind(client_message_id)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| {
+                    log::error!("Insert media message error: {e}");
+                    DBError::QueryError(e)
+                })?
+                .ok_or_else(Self::client_message_id_reused)?;
+
+        // the attachments of a retried message are linked already, they have to be the same ones
+        if !inserted {
+            let linked: Vec<Attachment> = sqlx::query_as(get_attachments_stm)
+                .bind(message_id)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| {
+                    log::error!("Get message attachments error: {e}");
+                    DBError::QueryError(e)
+                })?;
+
+            tx.rollback().await.map_err(|e| {
+                log::error!("Failed to rollback transaction: {}", e);
+                DBError::TransactionError("Failed to rollback transaction".to_string())
+            })?;
+
+            let linked_urls: HashSet<&str> = linked.iter().map(|a| a.file_url.as_str()).collect();
+            let urls: HashSet<&str> = urls.iter().map(String::as_str).collect();
+            if linked_urls != urls {
+                return Err(Self::client_message_id_reused());
+            }
+            return Ok((message_id, sent_at, false));
+        }
+
+        let result = sqlx::query(link_attachments_stm)
+            .bind(message_id)
+            .bind(conversation_id)
+            .bind(urls)
+            .bind(sender_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Link attachments to message error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        let mut unique_urls = urls.to_vec();
+        unique_urls.sort();
+        unique_urls.dedup();
+
+        if result.rows_affected() < unique_urls.len() as u64 {
+            log::error!("Link attachments returns fewer rows than media urls");
+            return Err(DBError::NotFound(
+                "Attachment not found in conversation".to_string(),
+            ));
+        }
+
+        tx.commit().await.map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            DBError::TransactionError("Failed to commit transaction".to_string())
+        })?;
+
+        Ok((message_id, sent_at, true))
     }
 
     pub async fn delete_message(&self, user_id: Uuid, message_id: i64) -> Result<u64, DBError> {
@@ -67,6 +171,23 @@ impl MessageRepo {
         }
     }
 
+    /// Hides the message from the user's own view, the user must be a participant of the conversation
+    pub async fn hide_message(&self, user_id: Uuid, message_id: i64) -> Result<u64, DBError> {
+        let stm = include_str!("./queries/message/hide_message.sql");
+
+        let result = sqlx::query(stm)
+            .bind(message_id)
+            .bind(user_id)
+            .execute(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Hide message error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn find_message_by_id(&self, message_id: i64) -> Result<Option<Message>, DBError> {
         let stm = include_str!("./queries/message/find_message_by_id.sql");
 
@@ -81,4 +202,117 @@ impl MessageRepo {
 
         Ok(result)
     }
+
+    /// Returns `None` if the message does not exist in the conversation or was deleted
+    pub async fn find_reply_preview(
+        &self,
+        message_id: i64,
+        conversation_id: i32,
+    ) -> Result<Option<ReplyPreview>, DBError> {
+        let stm = include_str!("./queries/message/find_reply_preview.sql");
+
+        let result: Option<ReplyPreview> = sqlx::query_as(stm)
+            .bind(message_id)
+            .bind(conversation_id)
+            .fetch_optional(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching reply preview error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    pub async fn get_replies_by_message_id(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        limit: Option<i32>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Message>, DBError> {
+        let stm = include_str!("./queries/message/get_replies_by_message_id.sql");
+
+        let result: Vec<Message> = sqlx::query_as(stm)
+            .bind(message_id)
+            .bind(before)
+            .bind(limit)
+            .bind(user_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching message replies error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    /// Replaces the message content and keeps the previous version in `message_edits`
+    pub async fn update_message(
+        &self,
+        message_id: i64,
+        content: &str,
+        edited_at: DateTime<Utc>,
+    ) -> Result<(), DBError> {
+        let insert_edit_stm = include_str!("./queries/message/insert_message_edit.sql");
+        let update_message_stm = include_str!("./queries/message/update_message.sql");
+
+        let mut tx = self.pg_db_pool.begin().await.map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            DBError::TransactionError("Failed to begin transaction".to_string())
+        })?;
+
+        sqlx::query(insert_edit_stm)
+            .bind(message_id)
+            .bind(edited_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Insert message edit error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        let result = sqlx::query(update_message_stm)
+            .bind(message_id)
+            .bind(content)
+            .bind(edited_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Update message error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        if result.rows_affected() == 0 {
+            log::error!("Update message returns 0 rows affected");
+            return Err(DBError::QueryFailed("0 rows affected".to_string()));
+        }
+
+        tx.commit().await.map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            DBError::TransactionError("Failed to commit transaction".to_string())
+        })?;
+
+        Ok(())
+    }
+
+    pub async fn get_message_edits(&self, message_id: i64) -> Result<Vec<MessageEdit>, DBError> {
+        let stm = include_str!("./queries/message/get_message_edits.sql");
+
+        let result: Vec<MessageEdit> = sqlx::query_as(stm)
+            .bind(message_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching message edits error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+
+    fn client_message_id_reused() -> DBError {
+        DBError::QueryFailed("Client message id is already used by another message".to_string())
+    }
 }
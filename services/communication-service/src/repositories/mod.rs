@@ -1,3 +1,4 @@
 pub mod attachment_repo;
 pub mod conversation_repo;
 pub mod message_repo;
+pub mod reaction_repo;
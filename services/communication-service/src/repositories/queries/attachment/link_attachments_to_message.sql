@@ -0,0 +1,12 @@
+UPDATE attachments
+SET
+    message_id = $1
+WHERE
+    conversation_id = $2
+    AND file_url = ANY($3)
+    AND uploader_id = $4
+    AND deleted = FALSE
+    AND (
+        message_id IS NULL
+        OR message_id = $1
+    );
\ No newline at end of file
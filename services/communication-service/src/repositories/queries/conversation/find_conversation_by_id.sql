@@ -2,7 +2,11 @@ SELECT
     conversation_id,
     title,
     latest_message,
-    created_at
+    created_at,
+    kind,
+    slow_mode_secs,
+    description,
+    avatar_url
 FROM conversations
 WHERE
     conversation_id = $1
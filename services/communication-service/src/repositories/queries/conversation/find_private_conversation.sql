@@ -0,0 +1,14 @@
+SELECT
+    conversation_id,
+    title,
+    latest_message,
+    created_at,
+    kind,
+    slow_mode_secs,
+    description,
+    avatar_url
+FROM conversations
+WHERE
+    pair_key = $1
+    AND kind = 'private'
+    AND is_deleted = FALSE;
\ No newline at end of file
@@ -6,7 +6,11 @@ SELECT
     m.sender_id,
     m.content,
     m.sent_at,
-    m.is_read,
+    CASE
+        WHEN m.message_id IS NULL THEN NULL
+        ELSE m.sender_id = $1
+        OR m.message_id <= COALESCE(us.last_read_message_id, 0)
+    END AS is_read,
     m.type,
     ARRAY (
         SELECT uc.user_id
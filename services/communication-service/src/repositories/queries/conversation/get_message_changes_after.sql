@@ -0,0 +1,31 @@
+SELECT
+    m.message_id,
+    CASE
+        WHEN m.deleted
+        OR h.hidden_at IS NOT NULL THEN NULL
+        ELSE m.content
+    END AS content,
+    m.edited_at,
+    COALESCE(m.deleted_at, h.hidden_at) AS deleted_at
+FROM
+    messages m
+    CROSS JOIN (
+        SELECT sent_at
+        FROM messages
+        WHERE
+            message_id = $2
+            AND conversation_id = $1
+    ) c
+    LEFT JOIN hidden_messages h ON h.message_id = m.message_id
+    AND h.user_id = $3
+WHERE
+    m.conversation_id = $1
+    AND m.message_id <= $2
+    AND (
+        m.edited_at > c.sent_at
+        OR m.deleted_at > c.sent_at
+        OR h.hidden_at > c.sent_at
+    )
+ORDER BY GREATEST(
+        m.edited_at, m.deleted_at, h.hidden_at
+    ) ASC;
\ No newline at end of file
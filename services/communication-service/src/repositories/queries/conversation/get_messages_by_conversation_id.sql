@@ -1,18 +1,70 @@
 SELECT
-    message_id,
-    conversation_id,
-    sender_id,
-    content,
-    sent_at,
-    type,
-    is_read
-FROM messages
+    m.message_id,
+    m.conversation_id,
+    m.sender_id,
+    m.content,
+    m.sent_at,
+    m.type,
+    m.edited_at,
+    COALESCE(
+        (
+            SELECT json_agg(
+                    json_build_object('emoji', r.emoji, 'count', r.count)
+                    ORDER BY r.count DESC, r.first_reacted_at
+                )
+            FROM (
+                    SELECT emoji, COUNT(*) AS count, MIN(created_at) AS first_reacted_at
+                    FROM message_reactions
+                    WHERE message_id = m.message_id
+                    GROUP BY emoji
+                ) r
+        ),
+        '[]'
+    ) AS reactions,
+    (
+        SELECT json_build_object(
+                'message_id', q.message_id,
+                'sender_id', q.sender_id,
+                'type', LOWER(q.type),
+                'content', CASE WHEN q.deleted THEN NULL ELSE LEFT(q.content, 100) END,
+                'deleted', q.deleted
+            )
+        FROM messages q
+        WHERE q.message_id = m.reply_to_message_id
+    ) AS reply_to,
+    m.message_id <= COALESCE(
+        (
+            SELECT MIN(COALESCE(uc.last_read_message_id, 0))
+            FROM users_conversations uc
+            WHERE
+                uc.conversation_id = m.conversation_id
+                AND uc.user_id != m.sender_id
+        ),
+        0
+    ) AS is_read
+FROM messages m
 WHERE
-    conversation_id = $1
-    AND deleted = FALSE
+    m.conversation_id = $1
+    AND m.deleted = FALSE
+    AND NOT EXISTS (
+        SELECT 1
+        FROM hidden_messages h
+        WHERE
+            h.message_id = m.message_id
+            AND h.user_id = $4
+    )
     AND (
         $2 IS NULL
-        OR sent_at < $2
+        OR m.sent_at < $2
+    )
+    AND (
+        $5::BIGINT IS NULL
+        OR m.message_id > $5
     )
-ORDER BY sent_at DESC NULLS LAST
+-- pages backwards by default, forwards when syncing after a message
+ORDER BY
+    CASE
+        WHEN $5::BIGINT IS NULL THEN m.sent_at
+    END DESC NULLS LAST,
+    m.message_id ASC
 LIMIT $3;
\ No newline at end of file
@@ -1 +1 @@
-INSERT INTO conversations (title) VALUES ($1) RETURNING *;
\ No newline at end of file
+INSERT INTO conversations (title, kind, slow_mode_secs) VALUES ($1, $2, $3) RETURNING *;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- nothing is returned when the pair already has a private conversation
+INSERT INTO
+    conversations (title, kind, pair_key)
+VALUES ($1, 'private', $2)
+ON CONFLICT (pair_key)
+WHERE
+    kind = 'private'
+    AND is_deleted = FALSE DO NOTHING
+RETURNING
+    conversation_id,
+    title,
+    latest_message,
+    created_at,
+    kind,
+    slow_mode_secs,
+    description,
+    avatar_url;
\ No newline at end of file
@@ -0,0 +1,18 @@
+UPDATE users_conversations uc
+SET
+    last_delivered_message_id = m.message_id
+FROM (
+        SELECT MAX(message_id) AS message_id
+        FROM messages
+        WHERE
+            conversation_id = $1
+            AND message_id <= $3
+    ) m
+WHERE
+    uc.conversation_id = $1
+    AND uc.user_id = ANY ($2)
+    AND m.message_id IS NOT NULL
+    AND m.message_id > COALESCE(uc.last_delivered_message_id, 0)
+RETURNING
+    uc.user_id,
+    uc.last_delivered_message_id;
\ No newline at end of file
@@ -1,7 +1,24 @@
-UPDATE messages
+UPDATE users_conversations uc
 SET
-    is_read = TRUE
+    last_read_message_id = m.message_id,
+    last_delivered_message_id = GREATEST(
+        COALESCE(uc.last_delivered_message_id, 0),
+        m.message_id
+    )
+FROM (
+        SELECT MAX(message_id) AS message_id
+        FROM messages
+        WHERE
+            conversation_id = $1
+            AND (
+                $3 IS NULL
+                OR message_id <= $3
+            )
+    ) m
 WHERE
-    conversation_id = $1
-    AND is_read = FALSE
-    AND sender_id != $2;
\ No newline at end of file
+    uc.conversation_id = $1
+    AND uc.user_id = $2
+    AND m.message_id IS NOT NULL
+    AND m.message_id > COALESCE(uc.last_read_message_id, 0)
+RETURNING
+    uc.last_read_message_id;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- the sync cursor has to be a message of the synced conversation, deleted ones included
+SELECT EXISTS (
+        SELECT 1
+        FROM messages
+        WHERE
+            message_id = $2
+            AND conversation_id = $1
+    );
\ No newline at end of file
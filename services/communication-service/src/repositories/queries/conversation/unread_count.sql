@@ -1,11 +1,9 @@
 SELECT COUNT(*)
-FROM messages
+FROM
+    messages m
+    JOIN users_conversations uc ON uc.conversation_id = m.conversation_id
 WHERE
-    is_read = FALSE
-    AND sender_id != $1
-    AND conversation_id IN (
-        SELECT conversation_id
-        FROM users_conversations
-        WHERE
-            user_id = $1
-    )
\ No newline at end of file
+    uc.user_id = $1
+    AND m.sender_id != $1
+    AND m.deleted = FALSE
+    AND m.message_id > COALESCE(uc.last_read_message_id, 0)
\ No newline at end of file
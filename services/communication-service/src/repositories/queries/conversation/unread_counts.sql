@@ -0,0 +1,18 @@
+-- unread messages of each user in one conversation and across all of their conversations
+SELECT
+    uc.user_id,
+    COUNT(m.message_id) FILTER (
+        WHERE
+            uc.conversation_id = $1
+    ) AS unread_count,
+    COUNT(m.message_id) AS total_unread_count
+FROM
+    users_conversations uc
+    LEFT JOIN messages m ON m.conversation_id = uc.conversation_id
+    AND m.sender_id != uc.user_id
+    AND m.deleted = FALSE
+    AND m.message_id > COALESCE(uc.last_read_message_id, 0)
+WHERE
+    uc.user_id = ANY ($2)
+GROUP BY
+    uc.user_id;
\ No newline at end of file
@@ -0,0 +1,24 @@
+-- fields left NULL are kept, an empty description or avatar clears it
+UPDATE conversations
+SET
+    title = COALESCE($2, title),
+    description = CASE
+        WHEN $3::TEXT IS NULL THEN description
+        ELSE NULLIF($3, '')
+    END,
+    avatar_url = CASE
+        WHEN $4::TEXT IS NULL THEN avatar_url
+        ELSE NULLIF($4, '')
+    END
+WHERE
+    conversation_id = $1
+    AND is_deleted = FALSE
+RETURNING
+    conversation_id,
+    title,
+    latest_message,
+    created_at,
+    kind,
+    slow_mode_secs,
+    description,
+    avatar_url;
\ No newline at end of file
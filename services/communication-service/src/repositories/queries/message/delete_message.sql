@@ -1,6 +1,8 @@
 UPDATE messages
 SET
-    deleted = true
+    deleted = true,
+    deleted_at = NOW()
 WHERE
     message_id = $1
-    AND sender_id = $2;
\ No newline at end of file
+    AND sender_id = $2
+    AND deleted = FALSE;
\ No newline at end of file
@@ -1,3 +1,46 @@
-SELECT message_id, conversation_id, sender_id, content, type, sent_at, is_read
-FROM messages 
-WHERE message_id = $1 AND deleted = FALSE;
\ No newline at end of file
+SELECT
+    m.message_id,
+    m.conversation_id,
+    m.sender_id,
+    m.content,
+    m.type,
+    m.edited_at,
+    COALESCE(
+        (
+            SELECT json_agg(
+                    json_build_object('emoji', r.emoji, 'count', r.count)
+                    ORDER BY r.count DESC, r.first_reacted_at
+                )
+            FROM (
+                    SELECT emoji, COUNT(*) AS count, MIN(created_at) AS first_reacted_at
+                    FROM message_reactions
+                    WHERE message_id = m.message_id
+                    GROUP BY emoji
+                ) r
+        ),
+        '[]'
+    ) AS reactions,
+    (
+        SELECT json_build_object(
+                'message_id', q.message_id,
+                'sender_id', q.sender_id,
+                'type', LOWER(q.type),
+                'content', CASE WHEN q.deleted THEN NULL ELSE LEFT(q.content, 100) END,
+                'deleted', q.deleted
+            )
+        FROM messages q
+        WHERE q.message_id = m.reply_to_message_id
+    ) AS reply_to,
+    m.sent_at,
+    m.message_id <= COALESCE(
+        (
+            SELECT MIN(COALESCE(uc.last_read_message_id, 0))
+            FROM users_conversations uc
+            WHERE
+                uc.conversation_id = m.conversation_id
+                AND uc.user_id != m.sender_id
+        ),
+        0
+    ) AS is_read
+FROM messages m
+WHERE m.message_id = $1 AND m.deleted = FALSE;
\ No newline at end of file
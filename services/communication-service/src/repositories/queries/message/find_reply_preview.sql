@@ -0,0 +1,11 @@
+SELECT
+    message_id,
+    sender_id,
+    type,
+    LEFT(content, 100) AS content,
+    deleted
+FROM messages
+WHERE
+    message_id = $1
+    AND conversation_id = $2
+    AND deleted = FALSE;
\ No newline at end of file
@@ -0,0 +1,4 @@
+SELECT edit_id, message_id, previous_content, edited_at
+FROM message_edits
+WHERE message_id = $1
+ORDER BY edited_at DESC;
\ No newline at end of file
@@ -0,0 +1,68 @@
+SELECT
+    m.message_id,
+    m.conversation_id,
+    m.sender_id,
+    m.content,
+    m.sent_at,
+    m.type,
+    m.edited_at,
+    COALESCE(
+        (
+            SELECT json_agg(
+                    json_build_object('emoji', r.emoji, 'count', r.count)
+                    ORDER BY r.count DESC, r.first_reacted_at
+                )
+            FROM (
+                    SELECT emoji, COUNT(*) AS count, MIN(created_at) AS first_reacted_at
+                    FROM message_reactions
+                    WHERE message_id = m.message_id
+                    GROUP BY emoji
+                ) r
+        ),
+        '[]'
+    ) AS reactions,
+    (
+        SELECT json_build_object(
+                'message_id', q.message_id,
+                'sender_id', q.sender_id,
+                'type', LOWER(q.type),
+                'content', CASE WHEN q.deleted THEN NULL ELSE LEFT(q.content, 100) END,
+                'deleted', q.deleted
+            )
+        FROM messages q
+        WHERE q.message_id = m.reply_to_message_id
+    ) AS reply_to,
+    m.message_id <= COALESCE(
+        (
+            SELECT MIN(COALESCE(uc.last_read_message_id, 0))
+            FROM users_conversations uc
+            WHERE
+                uc.conversation_id = m.conversation_id
+                AND uc.user_id != m.sender_id
+        ),
+        0
+    ) AS is_read
+FROM messages m
+WHERE
+    m.reply_to_message_id = $1
+    AND m.deleted = FALSE
+    AND EXISTS (
+        SELECT 1
+        FROM users_conversations uc
+        WHERE
+            uc.conversation_id = m.conversation_id
+            AND uc.user_id = $4
+    )
+    AND NOT EXISTS (
+        SELECT 1
+        FROM hidden_messages h
+        WHERE
+            h.message_id = m.message_id
+            AND h.user_id = $4
+    )
+    AND (
+        $2 IS NULL
+        OR m.sent_at < $2
+    )
+ORDER BY m.sent_at DESC NULLS LAST
+LIMIT $3;
\ No newline at end of file
@@ -0,0 +1,8 @@
+INSERT INTO hidden_messages (message_id, user_id)
+SELECT m.message_id, uc.user_id
+FROM messages m
+    JOIN users_conversations uc ON uc.conversation_id = m.conversation_id
+WHERE
+    m.message_id = $1
+    AND uc.user_id = $2
+ON CONFLICT DO NOTHING;
\ No newline at end of file
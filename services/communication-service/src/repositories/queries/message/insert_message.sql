@@ -1,2 +1,10 @@
-INSERT INTO messages (conversation_id, sender_id, content, type, sent_at, is_read) 
-VALUES ($1, $2, $3, $4, $5, COALESCE($6, false)) RETURNING message_id;
\ No newline at end of file
+INSERT INTO messages (conversation_id, sender_id, content, type, sent_at, reply_to_message_id, client_message_id) 
+VALUES ($1, $2, $3, $4, $5, $6, $7)
+ON CONFLICT ON CONSTRAINT unique_client_message DO UPDATE
+SET client_message_id = EXCLUDED.client_message_id
+-- a client message id reused for a different message returns no row
+WHERE messages.type = EXCLUDED.type
+AND messages.content IS NOT DISTINCT FROM EXCLUDED.content
+AND messages.reply_to_message_id IS NOT DISTINCT FROM EXCLUDED.reply_to_message_id
+-- xmax is 0 only for a freshly inserted row, a retried client message id returns the stored one
+RETURNING message_id, sent_at, (xmax = 0) AS inserted;
\ No newline at end of file
@@ -0,0 +1,4 @@
+INSERT INTO message_edits (message_id, previous_content, edited_at)
+SELECT message_id, content, $2
+FROM messages
+WHERE message_id = $1;
\ No newline at end of file
@@ -0,0 +1,7 @@
+UPDATE messages
+SET
+    content = $2,
+    edited_at = $3
+WHERE
+    message_id = $1
+    AND deleted = FALSE;
\ No newline at end of file
@@ -0,0 +1,5 @@
+SELECT emoji, COUNT(*) AS count
+FROM message_reactions
+WHERE message_id = $1
+GROUP BY emoji
+ORDER BY count DESC, MIN(created_at);
\ No newline at end of file
@@ -0,0 +1,9 @@
+INSERT INTO message_reactions (message_id, user_id, emoji)
+SELECT m.message_id, uc.user_id, $3
+FROM messages m
+    JOIN users_conversations uc ON uc.conversation_id = m.conversation_id
+WHERE
+    m.message_id = $1
+    AND uc.user_id = $2
+    AND m.deleted = FALSE
+ON CONFLICT DO NOTHING;
\ No newline at end of file
@@ -0,0 +1,9 @@
+SELECT DISTINCT partner.user_id
+FROM
+    users_conversations me
+    JOIN users_conversations partner ON partner.conversation_id = me.conversation_id
+    JOIN conversations c ON c.conversation_id = me.conversation_id
+WHERE
+    me.user_id = $1
+    AND partner.user_id != $1
+    AND c.is_deleted = FALSE;
\ No newline at end of file
@@ -0,0 +1,3 @@
+SELECT id, conversation_id, user_id, deleted_at, last_read_message_id, last_delivered_message_id, role 
+FROM users_conversations 
+WHERE conversation_id = $1 AND user_id = $2;
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- users already in the conversation are skipped, only the added ones are returned
+INSERT INTO
+    users_conversations (conversation_id, user_id, role)
+SELECT $1, user_id, $3
+FROM UNNEST($2::UUID[]) AS user_id
+ON CONFLICT (user_id, conversation_id) DO NOTHING
+RETURNING
+    user_id;
\ No newline at end of file
@@ -0,0 +1 @@
+UPDATE users_conversations SET role = $3 WHERE conversation_id = $1 AND user_id = $2;
\ No newline at end of file
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{errors::db_error::DBError, models::reaction::ReactionCount};
+
+pub struct ReactionRepo {
+    pg_db_pool: Arc<PgPool>,
+}
+
+impl ReactionRepo {
+    pub fn new(pg_db_pool: Arc<PgPool>) -> Self {
+        Self { pg_db_pool }
+    }
+
+    /// Returns `false` if the user already reacted with this emoji or is not a participant of the conversation
+    pub async fn insert_reaction(
+        &self,
+        message_id: i64,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<bool, DBError> {
+        let stm = include_str!("./queries/reaction/insert_reaction.sql");
+
+        let result = sqlx::query(stm)
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .execute(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Insert reaction error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_reaction(
+        &self,
+        message_id: i64,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<bool, DBError> {
+        let stm = include_str!("./queries/reaction/delete_reaction.sql");
+
+        let result = sqlx::query(stm)
+            .bind(message_id)
+            .bind(user_id)
+            .bind(emoji)
+            .execute(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Delete reaction error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_reactions_by_message_id(
+        &self,
+        message_id: i64,
+    ) -> Result<Vec<ReactionCount>, DBError> {
+        let stm = include_str!("./queries/reaction/get_reactions_by_message_id.sql");
+
+        let result: Vec<ReactionCount> = sqlx::query_as(stm)
+            .bind(message_id)
+            .fetch_all(&*self.pg_db_pool)
+            .await
+            .map_err(|e| {
+                log::error!("Fetching reactions error: {e}");
+                DBError::QueryError(e)
+            })?;
+
+        Ok(result)
+    }
+}
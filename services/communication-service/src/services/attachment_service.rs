@@ -9,9 +9,9 @@ use crate::{
     models::{
         attachment::{Attachment, MediaContent},
         upload_form::UploadForm,
-        MessageType,
     },
     repositories::{attachment_repo::AttachmentRepo, message_repo::MessageRepo},
+    services::membership_guard::MembershipGuard,
 };
 
 const MAXSIZE: usize = 20 * 1024 * 1024; // file max size - 20MB
@@ -20,10 +20,15 @@ const MEDIA_TYPE: [&str; 4] = ["text", "video", "image", "audio"]; // valid file
 pub struct AttachmentService {
     attachment_repo: Arc<AttachmentRepo>,
     message_repo: Arc<MessageRepo>,
+    membership_guard: MembershipGuard,
 }
 
 impl AttachmentService {
-    pub fn new(attachment_repo: Arc<AttachmentRepo>, message_repo: Arc<MessageRepo>) -> Self {
+    pub fn new(
+        attachment_repo: Arc<AttachmentRepo>,
+        message_repo: Arc<MessageRepo>,
+        membership_guard: MembershipGuard,
+    ) -> Self {
         // create top level media type upload folder
         for file_type in MEDIA_TYPE {
             std::fs::create_dir_all(format!("./uploads/{}", file_type)).unwrap();
@@ -32,6 +37,7 @@ impl AttachmentService {
         Self {
             attachment_repo,
             message_repo,
+            membership_guard,
         }
     }
 
@@ -41,6 +47,10 @@ impl AttachmentService {
         conversation_id: i32,
         sender_id: Uuid,
     ) -> Result<Vec<MediaContent>, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, sender_id)
+            .await?;
+
         let mut result: Vec<MediaContent> = vec![];
         let timestamp = Utc::now();
 
@@ -79,22 +89,11 @@ impl AttachmentService {
             }
         }
 
-        // save meatadata to database
+        // save meatadata to database, the media message linking these attachments is sent over the socket
         if !result.is_empty() {
-            let message_id = self
-                .message_repo
-                .insert_message(
-                    conversation_id,
-                    sender_id,
-                    None,
-                    MessageType::Media,
-                    timestamp,
-                    false,
-                )
-                .await?;
             let _ = self
                 .attachment_repo
-                .bulk_insert_attachments(Some(message_id), Some(conversation_id), &result)
+                .bulk_insert_attachments(None, Some(conversation_id), Some(sender_id), &result)
                 .await?;
         } else {
             return Err(FileError::InvalidFile("Empty body".to_string()).into());
@@ -103,37 +102,101 @@ impl AttachmentService {
         Ok(result)
     }
 
-    pub async fn get_file_by_url(&self, attachment_path: &str) -> Result<NamedFile, Error> {
+    pub async fn get_file_by_url(
+        &self,
+        user_id: Uuid,
+        attachment_path: &str,
+    ) -> Result<NamedFile, Error> {
+        // only files recorded as attachments are served, to the participants of their conversation
+        let attachment = self
+            .attachment_repo
+            .get_attachment_by_url(attachment_path)
+            .await?
+            .ok_or(FileError::FileNotFound)?;
+        self.ensure_attachment_access(user_id, &attachment).await?;
+
         let path = format!("./{}", attachment_path);
         return Ok(NamedFile::open(path).map_err(|e| FileError::OpenError(e.to_string()))?);
     }
 
     pub async fn get_attachment_by_id(
         &self,
+        user_id: Uuid,
         attachment_id: i32,
-    ) -> Result<Option<Attachment>, DBError> {
-        self.attachment_repo
+    ) -> Result<Option<Attachment>, Error> {
+        let attachment = self
+            .attachment_repo
             .get_attachment_by_id(attachment_id)
-            .await
+            .await?;
+
+        if let Some(attachment) = &attachment {
+            self.ensure_attachment_access(user_id, attachment).await?;
+        }
+
+        Ok(attachment)
     }
 
     pub async fn get_attachments_by_conversation_id(
         &self,
+        user_id: Uuid,
         conversation_id: i32,
         before: Option<chrono::DateTime<Utc>>,
         limit: Option<i32>,
-    ) -> Result<Vec<Attachment>, DBError> {
-        self.attachment_repo
+    ) -> Result<Vec<Attachment>, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        Ok(self
+            .attachment_repo
             .get_attachments_by_conversation_id(conversation_id, before, limit)
-            .await
+            .await?)
     }
 
     pub async fn get_attachment_by_message_id(
         &self,
+        user_id: Uuid,
         message_id: i64,
-    ) -> Result<Vec<Attachment>, DBError> {
-        self.attachment_repo
+    ) -> Result<Vec<Attachment>, Error> {
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await?;
+
+        Ok(self
+            .attachment_repo
             .get_attachment_by_message_id(message_id)
-            .await
+            .await?)
+    }
+
+    // an attachment belongs to the conversation it was uploaded to, or else to the one of its message
+    async fn ensure_attachment_access(
+        &self,
+        user_id: Uuid,
+        attachment: &Attachment,
+    ) -> Result<(), Error> {
+        let conversation_id = match (attachment.conversation_id, attachment.message_id) {
+            (Some(conversation_id), _) => Some(conversation_id),
+            (None, Some(message_id)) => self
+                .message_repo
+                .find_message_by_id(message_id)
+                .await?
+                .map(|message| message.conversation_id),
+            (None, None) => None,
+        };
+
+        match conversation_id {
+            Some(conversation_id) => {
+                self.membership_guard
+                    .ensure_member(conversation_id, user_id)
+                    .await
+            }
+            None => Err(FileError::Forbidden.into()),
+        }
     }
 }
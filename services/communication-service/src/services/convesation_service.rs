@@ -4,41 +4,119 @@ use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
 use crate::{
-    errors::db_error::DBError,
+    errors::{db_error::DBError, Error},
     models::{
-        conversation::{Conversation, ConversationList, ConversationMessages},
-        user_conversation::Participants,
-        Pagination,
+        conversation::{
+            Conversation, ConversationList, ConversationMessages, ConversationSync, GroupDetails,
+            NewConversation, UpdateConversation,
+        },
+        message::SystemEvent,
+        user_conversation::{ParticipantRole, Participants, UserConversation},
+        ws::{InboxEvent, InboxEventType, ReadReceipt, ReceiptState},
+        ConversationKind, Pagination,
     },
     repositories::conversation_repo::ConversationRepo,
+    services::membership_guard::MembershipGuard,
+    ws::chat_server_handler::ChatServerHandler,
 };
 
 pub struct ConversationService {
     conversation_repo: Arc<ConversationRepo>,
+    membership_guard: MembershipGuard,
+    chat_server_handler: ChatServerHandler,
 }
 
 impl ConversationService {
-    pub fn new(conversation_repo: Arc<ConversationRepo>) -> Self {
-        Self { conversation_repo }
+    pub fn new(
+        conversation_repo: Arc<ConversationRepo>,
+        membership_guard: MembershipGuard,
+        chat_server_handler: ChatServerHandler,
+    ) -> Self {
+        Self {
+            conversation_repo,
+            membership_guard,
+            chat_server_handler,
+        }
     }
 
     pub async fn get_conversation_by_id(
         &self,
+        user_id: Uuid,
         conversation_id: i32,
-    ) -> Result<Option<Conversation>, DBError> {
-        self.conversation_repo
+    ) -> Result<Option<Conversation>, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        Ok(self
+            .conversation_repo
             .find_conversation_by_id(conversation_id)
-            .await
+            .await?)
     }
 
-    pub async fn create_conversation(&self, title: &str) -> Result<Conversation, DBError> {
-        self.conversation_repo.insert_conversation(title).await
+    pub async fn create_conversation(
+        &self,
+        owner_id: Option<Uuid>,
+        new_conversation: &NewConversation,
+    ) -> Result<Conversation, DBError> {
+        let conversation = self
+            .conversation_repo
+            .insert_conversation(
+                &new_conversation.title,
+                new_conversation.kind,
+                new_conversation.slow_mode_secs,
+                owner_id,
+            )
+            .await?;
+
+        self.send_created_message(&conversation).await;
+
+        Ok(conversation)
     }
 
-    pub async fn delete_conversation(&self, conversation_id: i32) -> Result<u64, DBError> {
-        self.conversation_repo
+    pub async fn delete_conversation(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+    ) -> Result<u64, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        let conversation = self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+            .ok_or_else(|| Error::Db(DBError::NotFound("Conversation not found".to_string())))?;
+
+        // any participant may delete a private conversation, only the owner a group
+        if conversation.kind == ConversationKind::Group {
+            self.require_role(conversation_id, user_id, ParticipantRole::Owner)
+                .await?;
+        }
+
+        let participants = self
+            .conversation_repo
+            .find_users_by_conversation_id(conversation_id)
+            .await?;
+
+        let deleted = self
+            .conversation_repo
             .delete_conversation(conversation_id)
-            .await
+            .await?;
+
+        // every participant's sessions leave the room and their lists drop the conversation
+        for participant in participants {
+            let _ = self
+                .chat_server_handler
+                .remove_participant(participant.user_id, conversation_id)
+                .await
+                .map_err(|e| {
+                    log::error!("Remove participant from deleted conversation error: {e}");
+                });
+        }
+
+        Ok(deleted)
     }
 
     pub async fn get_conversation_participants(
@@ -56,7 +134,11 @@ impl ConversationService {
         &self,
         user_id: Uuid,
         conversation_id: i32,
-    ) -> Result<Participants, DBError> {
+    ) -> Result<Participants, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
         let participants = self
             .conversation_repo
             .find_participants_by_conversation_id(user_id, conversation_id)
@@ -70,14 +152,39 @@ impl ConversationService {
         conversation_id: i32,
         limit: Option<i32>,
         before: Option<DateTime<Utc>>,
-    ) -> Result<ConversationMessages, DBError> {
+    ) -> Result<ConversationMessages, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
         let messages = self
             .conversation_repo
-            .get_messages_by_conversation_id(user_id, conversation_id, limit, before)
+            .get_messages_by_conversation_id(user_id, conversation_id, limit, before, None)
             .await?;
         Ok(ConversationMessages { messages })
     }
 
+    pub async fn sync_conversation_messages(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        after_message_id: i64,
+        limit: Option<i32>,
+    ) -> Result<ConversationSync, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        self.conversation_repo
+            .sync_messages(user_id, conversation_id, after_message_id, limit)
+            .await?
+            .ok_or_else(|| {
+                Error::BadRequest(
+                    "after_message_id is not a message of this conversation".to_string(),
+                )
+            })
+    }
+
     pub async fn get_user_conversation(
         &self,
         user_id: Uuid,
@@ -93,24 +200,454 @@ impl ConversationService {
         Ok(ConversationList { conversations })
     }
 
+    /// Returns the private conversation of the two users, creating it when they have none yet.
+    /// The flag tells whether it was just created.
     pub async fn create_private_conversation(
         &self,
         title: &str,
         user_a: Uuid,
         user_b: Uuid,
-    ) -> Result<Conversation, DBError> {
-        self.conversation_repo
-            .insert_private_conversation(&title, user_a, user_b)
+    ) -> Result<(Conversation, bool), Error> {
+        if user_a == user_b {
+            return Err(Error::BadRequest(
+                "Cannot create a private conversation with yourself".to_string(),
+            ));
+        }
+
+        let (conversation, created) = self
+            .conversation_repo
+            .insert_private_conversation(title, user_a, user_b)
+            .await?;
+
+        if !created {
+            return Ok((conversation, false));
+        }
+
+        self.send_created_message(&conversation).await;
+
+        // show the conversation in both conversation lists right away
+        let event = InboxEvent::new(
+            InboxEventType::NewConversation,
+            conversation.conversation_id,
+            serde_json::json!(conversation),
+        );
+        let _ = self
+            .chat_server_handler
+            .notify_inbox(vec![user_a, user_b], event)
             .await
+            .map_err(|e| {
+                log::error!("Publish new conversation error: {e}");
+            });
+
+        Ok((conversation, true))
     }
 
     pub async fn get_unread_count(&self, user_id: Uuid) -> Result<i64, DBError> {
         self.conversation_repo.get_unread_count(user_id).await
     }
 
-    pub async fn mark_as_read(&self, conversation_id: i32, user_id: Uuid) -> Result<bool, DBError> {
+    pub async fn mark_as_read(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+        message_id: Option<i64>,
+    ) -> Result<bool, Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        let cursor = self
+            .conversation_repo
+            .mark_as_read(conversation_id, user_id, message_id)
+            .await?;
+
+        match cursor {
+            Some(cursor) => {
+                // let connected senders know their messages were read
+                let receipt =
+                    ReadReceipt::new(conversation_id, user_id, cursor, ReceiptState::Read);
+                let _ = self
+                    .chat_server_handler
+                    .broadcast(conversation_id, serde_json::json!(receipt).to_string())
+                    .await
+                    .map_err(|e| {
+                        log::error!("Broadcast read receipt error: {e}");
+                    });
+
+                let unread_count = self
+                    .conversation_repo
+                    .get_conversation_unread_count(conversation_id, user_id)
+                    .await?;
+                let event = InboxEvent::new(
+                    InboxEventType::UnreadChanged,
+                    conversation_id,
+                    serde_json::json!(unread_count),
+                );
+                let _ = self
+                    .chat_server_handler
+                    .notify_inbox(vec![user_id], event)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Publish unread count error: {e}");
+                    });
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub async fn update_conversation(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        update: &UpdateConversation,
+    ) -> Result<GroupDetails, Error> {
+        let (conversation, _) = self
+            .require_role(conversation_id, user_id, ParticipantRole::Admin)
+            .await?;
+
+        if update.title.is_none() && update.description.is_none() && update.avatar_url.is_none() {
+            return Err(Error::BadRequest("Nothing to update".to_string()));
+        }
+
+        let title = update.title.as_deref().map(str::trim);
+        if title.is_some_and(str::is_empty) {
+            return Err(Error::BadRequest("Title cannot be empty".to_string()));
+        }
+
+        let updated = self
+            .conversation_repo
+            .update_conversation(
+                conversation_id,
+                title,
+                update.description.as_deref().map(str::trim),
+                update.avatar_url.as_deref().map(str::trim),
+            )
+            .await?
+            .ok_or_else(|| Error::Db(DBError::NotFound("Conversation not found".to_string())))?;
+
+        if updated.title != conversation.title {
+            self.send_group_event(
+                conversation_id,
+                SystemEvent::ConversationRenamed {
+                    title: updated.title.clone(),
+                },
+            )
+            .await;
+        }
+
+        self.publish_group_details(conversation_id, &[]).await
+    }
+
+    /// Adds users to a group, users already in it are skipped
+    pub async fn add_participants(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        user_ids: &[Uuid],
+    ) -> Result<GroupDetails, Error> {
+        self.require_role(conversation_id, user_id, ParticipantRole::Admin)
+            .await?;
+
+        if user_ids.is_empty() {
+            return Err(Error::BadRequest("No user to add".to_string()));
+        }
+
+        let added = self
+            .conversation_repo
+            .insert_participants(conversation_id, user_ids, ParticipantRole::Member)
+            .await?;
+
+        for added_user_id in &added {
+            self.send_group_event(
+                conversation_id,
+                SystemEvent::ParticipantAdded {
+                    user_id: *added_user_id,
+                },
+            )
+            .await;
+        }
+
+        let details = self.publish_group_details(conversation_id, &added).await?;
+
+        // show the group in the conversation list of the new members right away
+        if !added.is_empty() {
+            let event = InboxEvent::new(
+                InboxEventType::NewConversation,
+                conversation_id,
+                serde_json::json!(details.conversation),
+            );
+            let _ = self
+                .chat_server_handler
+                .notify_inbox(added, event)
+                .await
+                .map_err(|e| {
+                    log::error!("Publish new conversation error: {e}");
+                });
+        }
+
+        Ok(details)
+    }
+
+    /// Removes a participant from a group, removing yourself leaves the group
+    pub async fn remove_participant(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        participant_id: Uuid,
+    ) -> Result<GroupDetails, Error> {
+        let min_role = if participant_id == user_id {
+            ParticipantRole::Member
+        } else {
+            ParticipantRole::Admin
+        };
+        let (_, actor) = self
+            .require_role(conversation_id, user_id, min_role)
+            .await?;
+
+        if participant_id == user_id {
+            if actor.role == ParticipantRole::Owner {
+                return Err(Error::BadRequest(
+                    "The owner has to transfer the ownership before leaving".to_string(),
+                ));
+            }
+        } else {
+            let participant = self
+                .find_participant(conversation_id, participant_id)
+                .await?;
+            if participant.role >= actor.role {
+                return Err(Error::Forbidden(
+                    "You cannot remove a participant with the same or a higher role".to_string(),
+                ));
+            }
+        }
+
         self.conversation_repo
-            .mark_as_read(conversation_id, user_id)
+            .delete_participant(conversation_id, participant_id)
+            .await?;
+
+        // the removed user's sessions leave the room and stop receiving its messages
+        let _ = self
+            .chat_server_handler
+            .remove_participant(participant_id, conversation_id)
+            .await
+            .map_err(|e| {
+                log::error!("Remove participant from room error: {e}");
+            });
+
+        self.send_group_event(
+            conversation_id,
+            SystemEvent::ParticipantRemoved {
+                user_id: participant_id,
+            },
+        )
+        .await;
+
+        self.publish_group_details(conversation_id, &[]).await
+    }
+
+    /// Promotes a member to admin or demotes an admin, only the owner may do it
+    pub async fn update_participant_role(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        participant_id: Uuid,
+        role: ParticipantRole,
+    ) -> Result<GroupDetails, Error> {
+        self.require_role(conversation_id, user_id, ParticipantRole::Owner)
+            .await?;
+
+        if role == ParticipantRole::Owner {
+            return Err(Error::BadRequest(
+                "Ownership is handed over by transferring it".to_string(),
+            ));
+        }
+        if participant_id == user_id {
+            return Err(Error::BadRequest(
+                "The owner cannot change their own role".to_string(),
+            ));
+        }
+
+        self.conversation_repo
+            .update_participant_role(conversation_id, participant_id, role)
+            .await?;
+
+        self.publish_group_details(conversation_id, &[]).await
+    }
+
+    /// Hands the group over to another participant, the previous owner stays on as admin
+    pub async fn transfer_ownership(
+        &self,
+        user_id: Uuid,
+        conversation_id: i32,
+        new_owner_id: Uuid,
+    ) -> Result<GroupDetails, Error> {
+        self.require_role(conversation_id, user_id, ParticipantRole::Owner)
+            .await?;
+
+        if new_owner_id == user_id {
+            return Err(Error::BadRequest(
+                "You already own this conversation".to_string(),
+            ));
+        }
+        self.find_participant(conversation_id, new_owner_id).await?;
+
+        self.conversation_repo
+            .transfer_ownership(conversation_id, user_id, new_owner_id)
+            .await?;
+
+        self.send_group_event(
+            conversation_id,
+            SystemEvent::OwnershipTransferred {
+                user_id: new_owner_id,
+            },
+        )
+        .await;
+
+        self.publish_group_details(conversation_id, &[]).await
+    }
+
+    /// Posts a note from another service into the conversation as a system message, returns the message id
+    pub async fn send_system_note(&self, conversation_id: i32, text: &str) -> Result<i64, Error> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::BadRequest("System note cannot be empty".to_string()));
+        }
+
+        if self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+            .is_none()
+        {
+            return Err(Error::Db(DBError::NotFound(
+                "Conversation not found".to_string(),
+            )));
+        }
+
+        self.chat_server_handler
+            .send_system_message(
+                conversation_id,
+                SystemEvent::Note {
+                    text: text.to_string(),
+                },
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Send system note error: {e}");
+                Error::InternalServerError
+            })
+    }
+
+    // checks the user holds at least `min_role` in the group, returns the group and the user's membership
+    async fn require_role(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+        min_role: ParticipantRole,
+    ) -> Result<(Conversation, UserConversation), Error> {
+        self.membership_guard
+            .ensure_member(conversation_id, user_id)
+            .await?;
+
+        let conversation = self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+            .ok_or_else(|| Error::Db(DBError::NotFound("Conversation not found".to_string())))?;
+
+        if conversation.kind != ConversationKind::Group {
+            return Err(Error::BadRequest(
+                "Only group conversations can be managed".to_string(),
+            ));
+        }
+
+        let participant = self.find_participant(conversation_id, user_id).await?;
+        if participant.role < min_role {
+            return Err(Error::Forbidden(format!(
+                "This action requires the {min_role} role"
+            )));
+        }
+
+        Ok((conversation, participant))
+    }
+
+    async fn find_participant(
+        &self,
+        conversation_id: i32,
+        user_id: Uuid,
+    ) -> Result<UserConversation, Error> {
+        self.conversation_repo
+            .find_participant(conversation_id, user_id)
+            .await?
+            .ok_or_else(|| Error::Db(DBError::NotFound("Participant not found".to_string())))
+    }
+
+    // sends the current settings and members to every participant, except the ones in `skip`
+    async fn publish_group_details(
+        &self,
+        conversation_id: i32,
+        skip: &[Uuid],
+    ) -> Result<GroupDetails, Error> {
+        let conversation = self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+            .ok_or_else(|| Error::Db(DBError::NotFound("Conversation not found".to_string())))?;
+        let participants = self
+            .conversation_repo
+            .find_users_by_conversation_id(conversation_id)
+            .await?;
+
+        let details = GroupDetails {
+            conversation,
+            participants,
+        };
+
+        let user_ids = details
+            .participants
+            .iter()
+            .map(|participant| participant.user_id)
+            .filter(|user_id| !skip.contains(user_id))
+            .collect();
+        let event = InboxEvent::new(
+            InboxEventType::GroupUpdated,
+            conversation_id,
+            serde_json::json!(details),
+        );
+        let _ = self
+            .chat_server_handler
+            .notify_inbox(user_ids, event)
+            .await
+            .map_err(|e| {
+                log::error!("Publish group update error: {e}");
+            });
+
+        Ok(details)
+    }
+
+    async fn send_group_event(&self, conversation_id: i32, event: SystemEvent) {
+        let _ = self
+            .chat_server_handler
+            .send_system_message(conversation_id, event)
+            .await
+            .map_err(|e| {
+                log::error!("Send group event message error: {e}");
+            });
+    }
+
+    async fn send_created_message(&self, conversation: &Conversation) {
+        let _ = self
+            .chat_server_handler
+            .send_system_message(
+                conversation.conversation_id,
+                SystemEvent::ConversationCreated {
+                    title: conversation.title.clone(),
+                },
+            )
             .await
+            .map_err(|e| {
+                log::error!("Send conversation created message error: {e}");
+            });
     }
 }
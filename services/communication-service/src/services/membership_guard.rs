@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{errors::Error, repositories::conversation_repo::ConversationRepo};
+
+/// Checks that a user takes part in a conversation before any of its data is served
+#[derive(Clone)]
+pub struct MembershipGuard {
+    conversation_repo: Arc<ConversationRepo>,
+}
+
+impl MembershipGuard {
+    pub fn new(conversation_repo: Arc<ConversationRepo>) -> Self {
+        Self { conversation_repo }
+    }
+
+    // unknown conversations are refused the same way, so their existence is not leaked
+    pub async fn ensure_member(&self, conversation_id: i32, user_id: Uuid) -> Result<(), Error> {
+        let existed = self
+            .conversation_repo
+            .check_user_in_conversation(conversation_id, user_id)
+            .await?;
+
+        if existed.is_none() {
+            return Err(Error::Forbidden(
+                "You are not a participant of this conversation".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -1,31 +1,196 @@
 use std::sync::Arc;
 
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
 use crate::{
-    errors::db_error::DBError,
-    models::message::Message,
+    errors::{db_error::DBError, Error},
+    models::{
+        message::{
+            DeleteScope, DeletedMessage, EditedMessage, Message, MessageEdits, MessageReplies,
+        },
+        MessageType,
+    },
     repositories::{attachment_repo::AttachmentRepo, message_repo::MessageRepo},
+    services::membership_guard::MembershipGuard,
+    ws::chat_server_handler::ChatServerHandler,
 };
 
 pub struct MessageService {
     message_repo: Arc<MessageRepo>,
     attachment_repo: Arc<AttachmentRepo>,
+    membership_guard: MembershipGuard,
+    chat_server_handler: ChatServerHandler,
+    edit_window: Duration,
+    unsend_window: Duration,
 }
 
 impl MessageService {
-    pub fn new(message_repo: Arc<MessageRepo>, attachment_repo: Arc<AttachmentRepo>) -> Self {
+    pub fn new(
+        message_repo: Arc<MessageRepo>,
+        attachment_repo: Arc<AttachmentRepo>,
+        membership_guard: MembershipGuard,
+        chat_server_handler: ChatServerHandler,
+        edit_window: Duration,
+        unsend_window: Duration,
+    ) -> Self {
         Self {
             message_repo,
             attachment_repo,
+            membership_guard,
+            chat_server_handler,
+            edit_window,
+            unsend_window,
         }
     }
 
-    pub async fn get_message_by_id(&self, message_id: i64) -> Result<Option<Message>, DBError> {
-        self.message_repo.find_message_by_id(message_id).await
+    pub async fn get_message_by_id(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+    ) -> Result<Option<Message>, Error> {
+        let message = self.message_repo.find_message_by_id(message_id).await?;
+
+        if let Some(message) = &message {
+            self.membership_guard
+                .ensure_member(message.conversation_id, user_id)
+                .await?;
+        }
+
+        Ok(message)
+    }
+
+    pub async fn update_message(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        content: &str,
+    ) -> Result<Message, Error> {
+        let content = content.trim();
+        if content.is_empty() {
+            return Err(Error::BadRequest(
+                "Message content cannot be empty".to_string(),
+            ));
+        }
+
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await?;
+
+        if message.sender_id != user_id {
+            return Err(Error::Forbidden(
+                "Only the sender can edit this message".to_string(),
+            ));
+        }
+
+        if !matches!(message.r#type, MessageType::Message) {
+            return Err(Error::BadRequest(
+                "Only text messages can be edited".to_string(),
+            ));
+        }
+
+        let edited_at = Utc::now();
+        if edited_at - message.sent_at > self.edit_window {
+            return Err(Error::Forbidden("Edit window has expired".to_string()));
+        }
+
+        self.message_repo
+            .update_message(message_id, content, edited_at)
+            .await?;
+
+        // update connected sessions in place
+        let edited = EditedMessage {
+            r#type: "edit".to_string(),
+            conversation_id: message.conversation_id,
+            message_id,
+            sender_id: user_id,
+            message: content.to_string(),
+            edited_at,
+        };
+        let _ = self
+            .chat_server_handler
+            .broadcast(
+                message.conversation_id,
+                serde_json::json!(edited).to_string(),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Broadcast message edit error: {e}");
+            });
+
+        self.message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()).into())
+    }
+
+    pub async fn get_message_edits(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+    ) -> Result<MessageEdits, Error> {
+        self.ensure_message_access(user_id, message_id).await?;
+
+        let edits = self.message_repo.get_message_edits(message_id).await?;
+        Ok(MessageEdits { edits })
+    }
+
+    pub async fn get_message_replies(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        limit: Option<i32>,
+        before: Option<DateTime<Utc>>,
+    ) -> Result<MessageReplies, Error> {
+        self.ensure_message_access(user_id, message_id).await?;
+
+        let replies = self
+            .message_repo
+            .get_replies_by_message_id(user_id, message_id, limit, before)
+            .await?;
+        Ok(MessageReplies { replies })
     }
 
-    pub async fn delete_message(&self, user_id: Uuid, message_id: i64) -> Result<(), DBError> {
+    pub async fn delete_message(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        scope: DeleteScope,
+    ) -> Result<(), Error> {
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await?;
+
+        if let DeleteScope::Me = scope {
+            let _ = self.message_repo.hide_message(user_id, message_id).await?;
+            return Ok(());
+        }
+
+        if message.sender_id != user_id {
+            return Err(Error::Forbidden(
+                "Only the sender can unsend this message".to_string(),
+            ));
+        }
+
+        let deleted_at = Utc::now();
+        if deleted_at - message.sent_at > self.unsend_window {
+            return Err(Error::Forbidden(
+                "Unsend window has expired, the message can only be deleted for you".to_string(),
+            ));
+        }
+
         let _ = self
             .message_repo
             .delete_message(user_id, message_id)
@@ -34,6 +199,38 @@ impl MessageService {
             .attachment_repo
             .delete_attachment_by_message_id(message_id)
             .await;
+
+        // remove the message from connected sessions
+        let deleted = DeletedMessage {
+            r#type: "message_deleted".to_string(),
+            conversation_id: message.conversation_id,
+            message_id,
+            deleted_at,
+        };
+        let _ = self
+            .chat_server_handler
+            .broadcast(
+                message.conversation_id,
+                serde_json::json!(deleted).to_string(),
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Broadcast message deletion error: {e}");
+            });
+
         Ok(())
     }
+
+    // the message has to exist and belong to a conversation of the user
+    async fn ensure_message_access(&self, user_id: Uuid, message_id: i64) -> Result<(), Error> {
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await
+    }
 }
@@ -1,4 +1,6 @@
 pub mod attachment_service;
 pub mod convesation_service;
+pub mod membership_guard;
 pub mod message_service;
+pub mod reaction_service;
 pub mod user_service;
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use crate::{
+    errors::{db_error::DBError, Error},
+    models::reaction::{validate_emoji, MessageReactions, ReactionAction, ReactionEvent},
+    repositories::{message_repo::MessageRepo, reaction_repo::ReactionRepo},
+    services::membership_guard::MembershipGuard,
+    ws::chat_server_handler::ChatServerHandler,
+};
+
+pub struct ReactionService {
+    reaction_repo: Arc<ReactionRepo>,
+    message_repo: Arc<MessageRepo>,
+    membership_guard: MembershipGuard,
+    chat_server_handler: ChatServerHandler,
+}
+
+impl ReactionService {
+    pub fn new(
+        reaction_repo: Arc<ReactionRepo>,
+        message_repo: Arc<MessageRepo>,
+        membership_guard: MembershipGuard,
+        chat_server_handler: ChatServerHandler,
+    ) -> Self {
+        Self {
+            reaction_repo,
+            message_repo,
+            membership_guard,
+            chat_server_handler,
+        }
+    }
+
+    pub async fn get_message_reactions(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+    ) -> Result<MessageReactions, Error> {
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await?;
+
+        let reactions = self
+            .reaction_repo
+            .get_reactions_by_message_id(message_id)
+            .await?;
+        Ok(MessageReactions { reactions })
+    }
+
+    pub async fn add_reaction(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        emoji: &str,
+    ) -> Result<(), Error> {
+        self.update_reaction(user_id, message_id, emoji, ReactionAction::Add)
+            .await
+    }
+
+    pub async fn remove_reaction(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        emoji: &str,
+    ) -> Result<(), Error> {
+        self.update_reaction(user_id, message_id, emoji, ReactionAction::Remove)
+            .await
+    }
+
+    async fn update_reaction(
+        &self,
+        user_id: Uuid,
+        message_id: i64,
+        emoji: &str,
+        action: ReactionAction,
+    ) -> Result<(), Error> {
+        let emoji = emoji.trim();
+        validate_emoji(emoji).map_err(|e| Error::BadRequest(e.to_string()))?;
+
+        let message = self
+            .message_repo
+            .find_message_by_id(message_id)
+            .await?
+            .ok_or_else(|| DBError::NotFound("Message not found".to_string()))?;
+
+        self.membership_guard
+            .ensure_member(message.conversation_id, user_id)
+            .await?;
+
+        let changed = match action {
+            ReactionAction::Add => {
+                self.reaction_repo
+                    .insert_reaction(message_id, user_id, emoji)
+                    .await?
+            }
+            ReactionAction::Remove => {
+                self.reaction_repo
+                    .delete_reaction(message_id, user_id, emoji)
+                    .await?
+            }
+        };
+
+        // nothing to broadcast when the reaction already was in the requested state
+        if changed {
+            let event = ReactionEvent::new(
+                message.conversation_id,
+                message_id,
+                user_id,
+                emoji.to_string(),
+                action,
+            );
+            let _ = self
+                .chat_server_handler
+                .broadcast(
+                    message.conversation_id,
+                    serde_json::json!(event).to_string(),
+                )
+                .await
+                .map_err(|e| {
+                    log::error!("Broadcast reaction error: {e}");
+                });
+        }
+
+        Ok(())
+    }
+}
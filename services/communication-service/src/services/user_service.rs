@@ -1,8 +1,12 @@
 use std::{error, sync::Arc};
 
+use futures_util::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
 use uuid::Uuid;
 
-use crate::redis_repositories::user_redis_repo::UserRedisRepo;
+use crate::{models::presence::UserPresence, redis_repositories::user_redis_repo::UserRedisRepo};
 
 pub struct UserService {
     user_redis_repo: Arc<UserRedisRepo>,
@@ -16,4 +20,27 @@ impl UserService {
     pub async fn check_online_user(&self, user_id: Uuid) -> Result<bool, Box<dyn error::Error>> {
         self.user_redis_repo.is_user_online(user_id).await
     }
+
+    pub async fn get_user_presence(
+        &self,
+        user_id: Uuid,
+    ) -> Result<UserPresence, Box<dyn error::Error>> {
+        self.user_redis_repo.get_user_presence(user_id).await
+    }
+
+    /// Current presence of every user followed by their presence changes
+    pub async fn stream_user_presence(
+        &self,
+        user_ids: Vec<Uuid>,
+    ) -> Result<BoxStream<'static, UserPresence>, Box<dyn error::Error>> {
+        // subscribe before reading the snapshot so no change in between is missed
+        let changes = self.user_redis_repo.subscribe_presence(&user_ids).await?;
+
+        let mut snapshot = Vec::with_capacity(user_ids.len());
+        for user_id in user_ids {
+            snapshot.push(self.user_redis_repo.get_user_presence(user_id).await?);
+        }
+
+        Ok(Box::pin(stream::iter(snapshot).chain(changes)))
+    }
 }
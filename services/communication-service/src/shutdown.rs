@@ -0,0 +1,41 @@
+use actix_web::dev::ServerHandle;
+use tokio::signal;
+
+use crate::ws::chat_server_handler::ChatServerHandler;
+
+// resolves on ctrl-c or SIGTERM, whichever comes first
+pub async fn wait_for_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("Failed to listen for ctrl_c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Coordinated shutdown of the HTTP server and the chat server.
+/// New connections and upgrades are refused first, then every session is closed with a
+/// reconnect hint and the node's redis state is removed, and only then the HTTP server stops.
+pub async fn drain(server_handle: ServerHandle, chat_server_handler: ChatServerHandler) {
+    wait_for_signal().await;
+    log::info!("Shutdown signal received, draining chat sessions");
+
+    server_handle.pause().await;
+    chat_server_handler.shutdown().await;
+    server_handle.stop(true).await;
+
+    log::info!("Http server stopped");
+}
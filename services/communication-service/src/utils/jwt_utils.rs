@@ -0,0 +1,54 @@
+use std::env;
+
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::errors::jwt_error::JWTError;
+
+// Claims of the access tokens issued by the user service
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Claims {
+    pub id: String,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: String,
+    pub role: String,
+    pub status: String,
+    pub avatar: String,
+    // expiry as unix timestamp in seconds
+    pub exp: i64,
+}
+
+impl Claims {
+    pub fn user_id(&self) -> Option<Uuid> {
+        Uuid::parse_str(&self.id).ok()
+    }
+}
+
+pub struct JwtUtils;
+
+impl JwtUtils {
+    pub fn verify_access_token(token: &str) -> Result<Claims, JWTError> {
+        let secret_key = env::var("JWT_ACCESS_SECRET").map_err(|e| {
+            log::error!("JWT_ACCESS_SECRET must be set");
+            JWTError::MissingEnvError(e)
+        })?;
+
+        // decode & validate access token
+        let token_data = jsonwebtoken::decode(
+            token,
+            &DecodingKey::from_secret(secret_key.as_ref()),
+            &Validation::default(),
+        )
+        .map_err(|e| {
+            log::error!("Jwt decode error: {e}");
+            JWTError::Error(e)
+        })?
+        .claims;
+
+        // Return the valid token claims.
+        Ok(token_data)
+    }
+}
@@ -1,22 +1,18 @@
 use std::{
     collections::{HashMap, HashSet},
     io,
-    pin::pin,
     sync::Arc,
     time::Duration,
 };
 
 use chrono::{DateTime, Utc};
 use deadpool_redis::Pool;
-use futures_util::{
-    future::{select, Either},
-    StreamExt,
-};
 use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
 use tokio::{
     sync::{
         mpsc::{self},
-        watch, RwLock,
+        RwLock,
     },
     time::{interval, sleep},
 };
@@ -26,30 +22,82 @@ use crate::{
     errors::chat_error::ChatError,
     grpc::noti_client::NotificationGrpcClient,
     models::{
-        attachment::{MediaContent, SentMedia},
-        message::{MessageContent, SentMessage},
+        attachment::{media_preview, MediaContent, SentMedia},
+        message::{MessageContent, SentMessage, SentSystemMessage, SystemEvent, SYSTEM_SENDER_ID},
         notification_mapping_impl::NotificationType,
         notification_models::push::{PushMessage, PushMessageType},
-        MessageType,
+        presence::{ActivityState, PresenceChanged, PresenceStatus, UserPresence},
+        reaction::{validate_emoji, ReactionAction, ReactionEvent},
+        ws::{
+            InboxEvent, InboxEventType, MessageAck, ReadReceipt, ReceiptState, TypingIndicator,
+            ViewerCount,
+        },
+        ConversationKind, MessageType,
+    },
+    repositories::{
+        conversation_repo::ConversationRepo, message_repo::MessageRepo, reaction_repo::ReactionRepo,
     },
-    repositories::{conversation_repo::ConversationRepo, message_repo::MessageRepo},
 };
 
 use super::{
-    chat_server_handler::ChatServerHandler, Command, ConnId, ConversationId, SendMsg, UserId,
+    chat_server_handler::ChatServerHandler,
+    conn_queue::{ConnSender, PushError, QueueConfig},
+    pubsub::{PubSubManager, PubSubMessage},
+    room_index::RoomIndex,
+    Command, ConnId, ConversationId, SendMsg, UserId,
 };
 
+type LocalRoomIndex = RoomIndex<ConversationId, ConnId, UserId>;
+
 const INTERVAL: Duration = Duration::from_secs(20);
 
+// how long a typing indicator stays alive without being refreshed by the client
+const TYPING_TIMEOUT: Duration = Duration::from_secs(5);
+
+// a sent message remembered under its client message id, a retry is only answered with the ack
+// when it sends the same message again
+#[derive(Serialize, Deserialize)]
+struct ClientMessage {
+    content: String,
+    r#type: String,
+    ack: MessageAck,
+}
+
+// limits of live-stream rooms
+pub struct LiveRoomConfig {
+    // every n-th message of a live room is persisted, 0 keeps all of them ephemeral
+    pub sample_rate: u64,
+    // messages a viewer may send per window, 0 disables the limit
+    pub rate_limit: u64,
+    pub rate_limit_window: Duration,
+    pub viewer_count_interval: Duration,
+}
+
+// identity of this process among the chat nodes sharing redis
+pub struct NodeConfig {
+    pub node_id: String,
+    // a node whose heartbeat key expired is considered dead and its sessions are purged
+    pub heartbeat_ttl: Duration,
+    pub reaper_interval: Duration,
+}
+
 pub struct ChatServer {
-    sessions: Arc<RwLock<HashMap<ConnId, mpsc::UnboundedSender<SendMsg>>>>,
-    subscribed_channels: Arc<RwLock<HashMap<String, watch::Sender<bool>>>>,
-    redis_client: Arc<redis::Client>,
+    sessions: Arc<RwLock<HashMap<ConnId, ConnSender>>>,
+    // room and inbox channels shared over a few multiplexed pub/sub connections
+    pubsub: PubSubManager,
+    // rooms joined by the connections of this node, used for pub/sub fan-out
+    room_index: Arc<RwLock<LocalRoomIndex>>,
     redis_pool: Arc<Pool>,
     conversation_repo: Arc<ConversationRepo>,
     message_repo: Arc<MessageRepo>,
+    reaction_repo: Arc<ReactionRepo>,
     notification_service_client: NotificationGrpcClient,
+    dedupe_window: Duration,
+    live_config: LiveRoomConfig,
+    node_config: NodeConfig,
     pub cmd_rx: mpsc::UnboundedReceiver<Command>,
+    // commands sent to the loop by the server's own background tasks
+    cmd_tx: mpsc::UnboundedSender<Command>,
 }
 
 impl ChatServer {
@@ -58,36 +106,99 @@ impl ChatServer {
         redis_client: Arc<redis::Client>,
         conversation_repo: Arc<ConversationRepo>,
         message_repo: Arc<MessageRepo>,
+        reaction_repo: Arc<ReactionRepo>,
         notification_service_client: NotificationGrpcClient,
+        dedupe_window: Duration,
+        live_config: LiveRoomConfig,
+        node_config: NodeConfig,
+        pubsub_connections: usize,
+        queue_config: QueueConfig,
     ) -> (Self, ChatServerHandler) {
         let sessions = Arc::new(RwLock::new(HashMap::new()));
-        let subscribed_channels = Arc::new(RwLock::new(HashMap::new()));
+        let pubsub = PubSubManager::new(redis_client, pubsub_connections);
+        let room_index = Arc::new(RwLock::new(RoomIndex::new()));
 
         let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
 
         (
             Self {
                 sessions,
-                subscribed_channels,
-                redis_client,
+                pubsub,
+                room_index,
                 redis_pool,
                 conversation_repo,
                 message_repo,
+                reaction_repo,
                 cmd_rx,
+                cmd_tx: cmd_tx.clone(),
                 notification_service_client,
+                dedupe_window,
+                live_config,
+                node_config,
             },
-            ChatServerHandler::new(cmd_tx),
+            ChatServerHandler::new(cmd_tx, queue_config),
         )
     }
 
     /// worker handles the received messages in the channel's buffer sent by `ChatServerHandler`
     pub async fn run(mut self) -> io::Result<()> {
+        // deliver pub/sub messages of every subscribed channel to local sessions
+        let (pubsub_tx, pubsub_rx) = mpsc::unbounded_channel();
+        self.pubsub.start(pubsub_tx);
+
+        let sessions = self.sessions.clone();
+        let room_index = self.room_index.clone();
+        let pubsub = self.pubsub.clone();
+        tokio::spawn(async move {
+            Self::start_pubsub_dispatch(sessions, room_index, pubsub, pubsub_rx).await;
+        });
+
+        // clean up whatever a previous crash of this node left behind before accepting sessions
+        if let Err(e) = self.register_node().await {
+            log::error!(
+                "Failed to register chat node {} - error: {e}",
+                self.node_config.node_id
+            );
+        }
+
+        // intervals touching redis and the database, stopped before the node shuts down
+        let mut background_tasks = Vec::new();
+
+        let clone_redis_pool = self.redis_pool.clone();
+        let node_id = self.node_config.node_id.clone();
+        let heartbeat_ttl = self.node_config.heartbeat_ttl;
+        let cmd_tx = self.cmd_tx.clone();
+        background_tasks.push(tokio::spawn(async move {
+            Self::start_heartbeat_interval(clone_redis_pool, node_id, heartbeat_ttl, cmd_tx).await;
+        }));
+
+        let clone_redis_pool = self.redis_pool.clone();
+        let clone_conversation_repo = self.conversation_repo.clone();
+        let node_id = self.node_config.node_id.clone();
+        let reaper_interval = self.node_config.reaper_interval;
+        background_tasks.push(tokio::spawn(async move {
+            Self::start_reaper_interval(
+                clone_redis_pool,
+                clone_conversation_repo,
+                node_id,
+                heartbeat_ttl,
+                reaper_interval,
+            )
+            .await;
+        }));
+
         let clone_conversation_repo = self.conversation_repo.clone();
         let clone_redis_pool = self.redis_pool.clone();
-        tokio::spawn(async move {
+        background_tasks.push(tokio::spawn(async move {
             Self::start_latest_message_cache_interval(clone_conversation_repo, clone_redis_pool)
                 .await;
-        });
+        }));
+
+        let clone_redis_pool = self.redis_pool.clone();
+        let viewer_count_interval = self.live_config.viewer_count_interval;
+        background_tasks.push(tokio::spawn(async move {
+            Self::start_viewer_count_interval(clone_redis_pool, viewer_count_interval).await;
+        }));
 
         while let Some(cmd) = self.cmd_rx.recv().await {
             match cmd {
@@ -112,6 +223,65 @@ impl ChatServer {
                     let _ = self.disconnect(user_id, conn_id).await;
                 }
 
+                Command::Shutdown { res_tx } => {
+                    for task in &background_tasks {
+                        task.abort();
+                    }
+
+                    if let Err(e) = self.shutdown().await {
+                        log::error!(
+                            "Failed to clean up chat node {} - error: {e}",
+                            self.node_config.node_id
+                        );
+                    }
+
+                    let _ = res_tx.send(());
+                    break;
+                }
+
+                Command::Metrics { res_tx } => {
+                    let sessions = self.sessions.read().await;
+                    let stats = sessions
+                        .iter()
+                        .map(|(conn_id, conn_tx)| conn_tx.stats(*conn_id))
+                        .collect();
+                    drop(sessions);
+
+                    let _ = res_tx.send(stats);
+                }
+
+                // runs in the loop so no connect or disconnect interleaves with the restore
+                Command::RestoreSessions => match self.restore_sessions().await {
+                    Ok(restored) => {
+                        log::warn!(
+                            "Chat node {} was reaped, restored {restored} sessions",
+                            self.node_config.node_id
+                        );
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "Failed to restore sessions of chat node {} - error: {e}",
+                            self.node_config.node_id
+                        );
+                    }
+                },
+
+                Command::Activity {
+                    user_id,
+                    conn_id,
+                    state,
+                    res_tx,
+                } => {
+                    if let Err(e) = self.set_activity(user_id, conn_id, state).await {
+                        log::error!("Failed to set {state} state of user {user_id} - session {conn_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::PresenceError(format!(
+                            "Failed to update presence - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
                 Command::Join {
                     user_id,
                     conn_id,
@@ -125,9 +295,9 @@ impl ChatServer {
                         log::error!(
                             "Failed to join user {user_id} to room {conversation_id} - error: {e}"
                         );
-                        let _ = res_tx.send(Err(ChatError::JoinError(format!(
-                            "Failed to join user to room"
-                        ))));
+                        let _ = res_tx.send(Err(ChatError::JoinError(
+                            "Failed to join user to room".to_string(),
+                        )));
                     } else {
                         let _ = res_tx.send(Ok(()));
                     }
@@ -136,13 +306,17 @@ impl ChatServer {
                 Command::Leave {
                     user_id,
                     conn_id,
+                    conversation_id,
                     res_tx,
                 } => {
-                    if let Err(e) = self.leave_converstaion(user_id, conn_id).await {
-                        log::error!("Failed to remove user {user_id} - session {conn_id} from current room - error: {e}");
-                        let _ = res_tx.send(Err(ChatError::LeaveError(format!(
-                            "Failed to remove user to current room"
-                        ))));
+                    if let Err(e) = self
+                        .leave_converstaion(user_id, conn_id, conversation_id)
+                        .await
+                    {
+                        log::error!("Failed to remove user {user_id} - session {conn_id} from room {conversation_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::LeaveError(
+                            "Failed to remove user from room".to_string(),
+                        )));
                     } else {
                         let _ = res_tx.send(Ok(()));
                     }
@@ -151,15 +325,187 @@ impl ChatServer {
                 Command::Message {
                     user_id,
                     conn_id,
+                    conversation_id,
                     msg,
                     r#type,
+                    client_message_id,
                     res_tx,
-                } => {
-                    if let Err(e) = self.send_message(user_id, conn_id, msg, r#type).await {
-                        log::error!("Failed to send message from user {user_id} to current room - error: {e}");
+                } => match self
+                    .send_message(
+                        user_id,
+                        conn_id,
+                        conversation_id,
+                        msg,
+                        r#type,
+                        client_message_id,
+                    )
+                    .await
+                {
+                    Ok(ack) => {
+                        let _ = res_tx.send(Ok(ack));
+                    }
+                    Err(e) => {
+                        log::error!("Failed to send message from user {user_id} to room {conversation_id} - error: {e}");
                         let _ = res_tx.send(Err(ChatError::MessageError(format!(
                             "Failed to send message from user to room - {e}"
                         ))));
+                    }
+                },
+
+                Command::Typing {
+                    user_id,
+                    conn_id,
+                    conversation_id,
+                    is_typing,
+                    res_tx,
+                } => {
+                    if let Err(e) = self
+                        .send_typing(user_id, conn_id, conversation_id, is_typing)
+                        .await
+                    {
+                        log::error!("Failed to send typing indicator from user {user_id} to room {conversation_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::TypingError(format!(
+                            "Failed to send typing indicator - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
+                Command::Read {
+                    user_id,
+                    conn_id,
+                    conversation_id,
+                    message_id,
+                    state,
+                    res_tx,
+                } => {
+                    if let Err(e) = self
+                        .send_receipt(user_id, conn_id, conversation_id, message_id, state)
+                        .await
+                    {
+                        log::error!(
+                            "Failed to update {state} receipt of user {user_id} - error: {e}"
+                        );
+                        let _ = res_tx.send(Err(ChatError::ReceiptError(format!(
+                            "Failed to update receipt - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
+                Command::React {
+                    user_id,
+                    conn_id,
+                    message_id,
+                    emoji,
+                    action,
+                    res_tx,
+                } => {
+                    if let Err(e) = self
+                        .send_reaction(user_id, conn_id, message_id, emoji, action)
+                        .await
+                    {
+                        log::error!("Failed to {action} reaction of user {user_id} on message {message_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::ReactionError(format!(
+                            "Failed to {action} reaction - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
+                Command::Sync {
+                    user_id,
+                    conversation_id,
+                    after_message_id,
+                    limit,
+                    res_tx,
+                } => {
+                    // reading history does not touch the chat server state, keep the loop free
+                    let conversation_repo = self.conversation_repo.clone();
+                    tokio::spawn(async move {
+                        let result = conversation_repo
+                            .sync_messages(user_id, conversation_id, after_message_id, limit)
+                            .await
+                            .map_err(|e| {
+                                log::error!("Failed to sync room {conversation_id} for user {user_id} - error: {e}");
+                                ChatError::SyncError(format!("Failed to sync messages - {e}"))
+                            })
+                            .and_then(|sync| {
+                                sync.ok_or_else(|| {
+                                    ChatError::SyncError(
+                                        "after_message_id is not a message of this conversation"
+                                            .to_string(),
+                                    )
+                                })
+                            });
+                        let _ = res_tx.send(result);
+                    });
+                }
+
+                Command::Broadcast {
+                    conversation_id,
+                    msg,
+                    res_tx,
+                } => {
+                    if let Err(e) = self.broadcast(conversation_id, &msg).await {
+                        log::error!("Failed to broadcast to room {conversation_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::BroadcastError(format!(
+                            "Failed to broadcast to room - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
+                Command::System {
+                    conversation_id,
+                    event,
+                    res_tx,
+                } => {
+                    match self.send_system_message(conversation_id, event).await {
+                        Ok(message_id) => {
+                            let _ = res_tx.send(Ok(message_id));
+                        }
+                        Err(e) => {
+                            log::error!("Failed to send system message to room {conversation_id} - error: {e}");
+                            let _ = res_tx.send(Err(ChatError::SystemMessageError(format!(
+                                "Failed to send system message - {e}"
+                            ))));
+                        }
+                    }
+                }
+
+                Command::Inbox {
+                    user_ids,
+                    event,
+                    res_tx,
+                } => {
+                    if let Err(e) = self.notify_inbox(&user_ids, &event).await {
+                        log::error!(
+                            "Failed to publish {} to user inboxes - error: {e}",
+                            event.r#type
+                        );
+                        let _ = res_tx.send(Err(ChatError::InboxError(format!(
+                            "Failed to publish inbox event - {e}"
+                        ))));
+                    } else {
+                        let _ = res_tx.send(Ok(()));
+                    }
+                }
+
+                Command::RemoveParticipant {
+                    user_id,
+                    conversation_id,
+                    res_tx,
+                } => {
+                    if let Err(e) = self.remove_participant(user_id, conversation_id).await {
+                        log::error!("Failed to remove user {user_id} from room {conversation_id} - error: {e}");
+                        let _ = res_tx.send(Err(ChatError::RemoveParticipantError(format!(
+                            "Failed to remove participant from room - {e}"
+                        ))));
                     } else {
                         let _ = res_tx.send(Ok(()));
                     }
@@ -174,31 +520,53 @@ impl ChatServer {
         &mut self,
         conn_id: ConnId,
         user_id: UserId,
-        conn_tx: mpsc::UnboundedSender<SendMsg>,
+        conn_tx: ConnSender,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut redis_conn = self.redis_pool.get().await?;
         // register local session
         let mut sessions = self.sessions.write().await;
         sessions.insert(conn_id, conn_tx);
+        self.room_index.write().await.connect(user_id, conn_id);
 
-        // set user status to online
+        // add user id to online set
         redis_conn
-            .hset::<&str, &str, &str, ()>(&format!("user:{user_id}"), "status", "online")
+            .sadd::<&str, &str, ()>("online_users", &user_id.to_string())
             .await?;
 
-        // add user id to online set
+        // initialize user's session, tagged with the node holding the connection
+        Self::set_session_rooms(
+            &mut redis_conn,
+            &self.node_config.node_id,
+            &user_id,
+            &conn_id,
+            &HashSet::new(),
+        )
+        .await?;
         redis_conn
-            .sadd::<&str, &str, ()>("online_users", &user_id.to_string())
+            .sadd::<&str, &str, ()>(
+                &format!("node:{}:sessions", self.node_config.node_id),
+                &format!("{user_id}:{conn_id}"),
+            )
             .await?;
 
-        // initialize user's session
+        // every new session starts active
         redis_conn
             .hset::<&str, &str, &str, ()>(
-                &format!("user:{user_id}:sessions"),
+                &format!("user:{user_id}:session_states"),
                 &conn_id.to_string(),
-                &serde_json::json!({"active_room": ""}).to_string(),
+                &ActivityState::Active.to_string(),
             )
             .await?;
+        Self::update_presence(
+            &self.redis_pool,
+            &self.conversation_repo,
+            &mut redis_conn,
+            user_id,
+        )
+        .await?;
+
+        // conversation list events are delivered regardless of the joined rooms
+        self.subscribe(format!("user:{user_id}:inbox")).await?;
 
         log::info!("connection id: {conn_id} - connected");
 
@@ -210,440 +578,1333 @@ impl ChatServer {
         user_id: UserId,
         conn_id: ConnId,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        // remove the user's session from current redis room
-        let _ = self
-            .leave_converstaion(user_id, conn_id)
-            .await
-            .map_err(|e| {
-                log::error!("{e}");
-            });
-
         let mut redis_conn = self.redis_pool.get().await?;
+
+        // remove the user's session from all of its redis rooms
+        let rooms = self
+            .get_session_rooms(&mut redis_conn, &user_id, &conn_id)
+            .await;
+        for room in rooms {
+            let conversation_id = match room.parse::<i32>() {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            let _ = self
+                .leave_converstaion(user_id, conn_id, conversation_id)
+                .await
+                .map_err(|e| {
+                    log::error!("{e}");
+                });
+        }
+
         // remove the current user's session from redis
         redis_conn
             .hdel::<&str, &str, ()>(&format!("user:{user_id}:sessions"), &conn_id.to_string())
             .await?;
-
-        // remove the current user from online set
         redis_conn
-            .srem::<&str, &str, ()>("online_users", &user_id.to_string())
+            .hdel::<&str, &str, ()>(
+                &format!("user:{user_id}:session_states"),
+                &conn_id.to_string(),
+            )
+            .await?;
+        redis_conn
+            .srem::<&str, &str, ()>(
+                &format!("node:{}:sessions", self.node_config.node_id),
+                &format!("{user_id}:{conn_id}"),
+            )
             .await?;
 
-        // set user's status to offline if all sessions are removed
+        // remove the current user from online set once all sessions are removed
         let session_count = redis_conn
             .hlen::<&str, i64>(&format!("user:{user_id}:sessions"))
             .await?;
 
         if session_count == 0 {
             redis_conn
-                .hset::<&str, &str, &str, ()>(&format!("user:{user_id}"), "status", "offline")
-                .await?
+                .srem::<&str, &str, ()>("online_users", &user_id.to_string())
+                .await?;
         }
 
+        Self::update_presence(
+            &self.redis_pool,
+            &self.conversation_repo,
+            &mut redis_conn,
+            user_id,
+        )
+        .await?;
+
         // remove current user's session in local
         let mut sessions = self.sessions.write().await;
         sessions.remove(&conn_id);
+        drop(sessions);
+
+        let mut room_index = self.room_index.write().await;
+        let emptied_rooms = room_index.disconnect(user_id, conn_id);
+        let has_local_session = room_index.has_user(user_id);
+        drop(room_index);
+
+        for conversation_id in emptied_rooms {
+            self.unsubscribe(format!("room:{conversation_id}")).await;
+        }
+
+        // stop listening to the user's inbox once none of their sessions lives on this node
+        if !has_local_session {
+            self.unsubscribe(format!("user:{user_id}:inbox")).await;
+        }
 
         Ok(())
     }
 
-    async fn join_conversation(
-        &mut self,
+    async fn set_activity(
+        &self,
         user_id: UserId,
         conn_id: ConnId,
-        conversation_id: ConversationId,
-        is_public_room: bool,
+        state: ActivityState,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut redis_conn = self.redis_pool.get().await?;
-        // ensure user in database conversation
-        let existed = self
-            .conversation_repo
-            .check_user_in_conversation(conversation_id, user_id)
-            .await?;
 
-        if existed.is_none() {
-            if is_public_room {
-                // add user to database conversation if they are not in it
-                self.conversation_repo
-                    .insert_conversation_user(conversation_id, user_id)
-                    .await?;
-            } else {
-                return Err(Box::new(ChatError::JoinError(
-                    "User is not allowed to join".to_string(),
-                )));
-            }
+        let exists: bool = redis_conn
+            .hexists(&format!("user:{user_id}:sessions"), &conn_id.to_string())
+            .await?;
+        if !exists {
+            return Err(ChatError::PresenceError("Session not found".to_string()).into());
         }
 
-        // join user to redis's room
         redis_conn
             .hset::<&str, &str, &str, ()>(
-                &format!("user:{user_id}:sessions"),
+                &format!("user:{user_id}:session_states"),
                 &conn_id.to_string(),
-                &serde_json::json!({"active_room": conversation_id.to_string()}).to_string(),
-            )
-            .await?;
-
-        redis_conn
-            .sadd::<&str, &str, ()>(
-                &format!("room:{conversation_id}:active_users"),
-                &user_id.to_string(),
-            )
-            .await?;
-
-        // update room last active time (use for remove subscription)
-        redis_conn
-            .hset::<&str, &str, &str, ()>(
-                &format!("room:{conversation_id}"),
-                "last_active",
-                &chrono::Utc::now().timestamp().to_string(),
+                &state.to_string(),
             )
             .await?;
 
-        self.subscribe(format!("room:{conversation_id}"))
-            .await
-            .unwrap();
-
-        Ok(())
+        Self::update_presence(
+            &self.redis_pool,
+            &self.conversation_repo,
+            &mut redis_conn,
+            user_id,
+        )
+        .await
     }
 
-    async fn leave_converstaion(
-        &self,
+    // the user is online while any session is active, away while all of them are idle
+    async fn update_presence(
+        redis_pool: &Arc<Pool>,
+        conversation_repo: &Arc<ConversationRepo>,
+        redis_conn: &mut deadpool_redis::Connection,
         user_id: UserId,
-        conn_id: ConnId,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut redis_conn = self.redis_pool.get().await?;
-        // get current conversation_id
-        let conversation_id = match self
-            .get_current_session_room(&mut redis_conn, &user_id, &conn_id)
-            .await
+        let session_states: Vec<String> = redis_conn
+            .hvals(&format!("user:{user_id}:session_states"))
+            .await?;
+
+        let status = if session_states.is_empty() {
+            PresenceStatus::Offline
+        } else if session_states
+            .iter()
+            .any(|state| *state == ActivityState::Active.to_string())
         {
-            Some(room_id) => room_id,
-            None => {
-                return Err(ChatError::LeaveError(
-                    "Room not found, user must join a room first".to_string(),
-                )
-                .into())
-            }
+            PresenceStatus::Online
+        } else {
+            PresenceStatus::Away
         };
 
-        // update session active_room to none
+        let last_seen_at = Utc::now();
+        let previous_status: Option<String> = redis_conn
+            .hget(&format!("user:{user_id}"), "status")
+            .await?;
+
         redis_conn
-            .hset::<&str, &str, &str, ()>(
-                &format!("user:{user_id}:sessions"),
-                &conn_id.to_string(),
-                &serde_json::json!({"active_room": ""}).to_string(),
+            .hset_multiple::<&str, &str, String, ()>(
+                &format!("user:{user_id}"),
+                &[
+                    ("status", status.to_string()),
+                    ("last_seen_at", last_seen_at.to_rfc3339()),
+                ],
             )
             .await?;
 
-        // loop through all current user's session, if none of them are in the current conversation, remove the user_id from the room
-        let user_sessions: HashMap<String, String> = redis_conn
-            .hgetall(&format!("user:{user_id}:sessions"))
-            .await?;
+        // only changes are pushed, activity inside the same state just moves last seen
+        if previous_status.as_deref() != Some(status.to_string().as_str()) {
+            Self::publish_presence(
+                redis_pool.clone(),
+                conversation_repo.clone(),
+                UserPresence {
+                    user_id,
+                    status,
+                    last_seen_at: Some(last_seen_at),
+                },
+            );
+        }
 
-        let mut is_remain = false;
-        for value in user_sessions.values() {
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(&value) {
-                if let Some(v) = json_value.get("active_room") {
-                    if v.as_str().unwrap().to_string() == conversation_id {
-                        is_remain = true;
-                        break;
-                    }
+        Ok(())
+    }
+
+    // pushes a presence change to the presence streams of the user and the inboxes of their conversation partners
+    fn publish_presence(
+        redis_pool: Arc<Pool>,
+        conversation_repo: Arc<ConversationRepo>,
+        presence: UserPresence,
+    ) {
+        tokio::spawn(async move {
+            let user_id = presence.user_id;
+            let payload = serde_json::json!(PresenceChanged::new(presence)).to_string();
+
+            let mut redis_conn = match redis_pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::error!("Get redis connection error: {e}");
+                    return;
+                }
+            };
+
+            let _ = redis_conn
+                .publish::<&str, &str, ()>(&format!("user:{user_id}:presence"), &payload)
+                .await
+                .map_err(|e| {
+                    log::error!("Publish presence of user {user_id} error: {e}");
+                });
+
+            let partners = match conversation_repo.find_conversation_partners(user_id).await {
+                Ok(result) => result,
+                Err(e) => {
+                    log::error!("Get conversation partners of user {user_id} error: {e}");
+                    return;
+                }
+            };
+
+            for partner_id in partners {
+                let _ = redis_conn
+                    .publish::<&str, &str, ()>(&format!("user:{partner_id}:inbox"), &payload)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Publish presence to user {partner_id} error: {e}");
+                    });
+            }
+        });
+    }
+
+    async fn join_conversation(
+        &mut self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        is_public_room: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        let conversation = match self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+        {
+            Some(conversation) => conversation,
+            None => {
+                return Err(Box::new(ChatError::JoinError(
+                    "Conversation not found".to_string(),
+                )))
+            }
+        };
+
+        // anyone may watch a live room, viewers do not become participants
+        let is_live = conversation.kind == ConversationKind::Live;
+
+        // ensure user in database conversation
+        let existed = self
+            .conversation_repo
+            .check_user_in_conversation(conversation_id, user_id)
+            .await?;
+
+        if existed.is_none() && !is_live {
+            if is_public_room {
+                // add user to database conversation if they are not in it
+                self.conversation_repo
+                    .insert_conversation_user(conversation_id, user_id)
+                    .await?;
+
+                let event = InboxEvent::new(
+                    InboxEventType::NewConversation,
+                    conversation_id,
+                    serde_json::json!(conversation),
+                );
+                self.notify_inbox(&[user_id], &event).await?;
+
+                let _ = self
+                    .send_system_message(conversation_id, SystemEvent::ParticipantAdded { user_id })
+                    .await
+                    .map_err(|e| {
+                        log::error!("Send participant added message error: {e}");
+                    });
+            } else {
+                return Err(Box::new(ChatError::JoinError(
+                    "User is not allowed to join".to_string(),
+                )));
+            }
+        }
+
+        // join user to redis's room, a session can be in several rooms at once
+        let mut rooms = self
+            .get_session_rooms(&mut redis_conn, &user_id, &conn_id)
+            .await;
+        rooms.insert(conversation_id.to_string());
+        Self::set_session_rooms(
+            &mut redis_conn,
+            &self.node_config.node_id,
+            &user_id,
+            &conn_id,
+            &rooms,
+        )
+        .await?;
+
+        redis_conn
+            .sadd::<&str, &str, ()>(
+                &format!("room:{conversation_id}:active_users"),
+                &user_id.to_string(),
+            )
+            .await?;
+        self.room_index
+            .write()
+            .await
+            .join(conversation_id, conn_id, user_id);
+
+        // update room last active time (use for remove subscription)
+        redis_conn
+            .hset::<&str, &str, &str, ()>(
+                &format!("room:{conversation_id}"),
+                "last_active",
+                &chrono::Utc::now().timestamp().to_string(),
+            )
+            .await?;
+
+        Self::cache_room_settings(
+            &mut redis_conn,
+            conversation_id,
+            conversation.kind,
+            conversation.slow_mode_secs,
+        )
+        .await?;
+
+        // live rooms get periodic viewer counts
+        if is_live {
+            redis_conn
+                .sadd::<&str, &str, ()>("live_rooms", &conversation_id.to_string())
+                .await?;
+        }
+
+        self.subscribe(format!("room:{conversation_id}")).await?;
+
+        Ok(())
+    }
+
+    async fn leave_converstaion(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        let mut rooms = self
+            .get_session_rooms(&mut redis_conn, &user_id, &conn_id)
+            .await;
+        if !rooms.remove(&conversation_id.to_string()) {
+            return Err(ChatError::LeaveError(
+                "Room not found, user must join the room first".to_string(),
+            )
+            .into());
+        }
+
+        // stop the user's typing indicator in the room they are leaving
+        let _ = Self::clear_typing(&mut redis_conn, conversation_id, &user_id)
+            .await
+            .map_err(|e| {
+                log::error!("Clear typing indicator error: {e}");
+            });
+
+        // remove the room from the session
+        Self::set_session_rooms(
+            &mut redis_conn,
+            &self.node_config.node_id,
+            &user_id,
+            &conn_id,
+            &rooms,
+        )
+        .await?;
+
+        // loop through all current user's session, if none of them are in the current conversation, remove the user_id from the room
+        let user_sessions: HashMap<String, String> = redis_conn
+            .hgetall(&format!("user:{user_id}:sessions"))
+            .await?;
+
+        let is_remain = user_sessions
+            .values()
+            .any(|value| Self::session_has_room(value, &conversation_id.to_string()));
+
+        if !is_remain {
+            redis_conn
+                .srem::<&str, &str, ()>(
+                    &format!("room:{conversation_id}:active_users"),
+                    &user_id.to_string(),
+                )
+                .await?;
+        }
+
+        // if there are no local connections in the room, unsubscribe
+        let is_room_empty = self
+            .room_index
+            .write()
+            .await
+            .leave(conversation_id, conn_id);
+        if is_room_empty {
+            self.unsubscribe(format!("room:{conversation_id}")).await;
+        }
+
+        Ok(())
+    }
+
+    // strips the room from every session of a user removed from the group, each node drops
+    // its local connections from the room once the removal reaches the user's inbox
+    async fn remove_participant(
+        &self,
+        user_id: UserId,
+        conversation_id: ConversationId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        let user_sessions: HashMap<String, String> = redis_conn
+            .hgetall(&format!("user:{user_id}:sessions"))
+            .await?;
+
+        for (conn_id, session_state) in user_sessions {
+            let mut rooms = Self::parse_session_rooms(&session_state);
+            if !rooms.remove(&conversation_id.to_string()) {
+                continue;
+            }
+
+            // the session stays owned by the node it lives on
+            let node_id = serde_json::from_str::<serde_json::Value>(&session_state)
+                .ok()
+                .and_then(|value| value["node"].as_str().map(|node| node.to_string()))
+                .unwrap_or_default();
+            let conn_id = Uuid::parse_str(&conn_id)?;
+
+            Self::set_session_rooms(&mut redis_conn, &node_id, &user_id, &conn_id, &rooms).await?;
+        }
+
+        redis_conn
+            .srem::<&str, &str, ()>(
+                &format!("room:{conversation_id}:active_users"),
+                &user_id.to_string(),
+            )
+            .await?;
+
+        let _ = Self::clear_typing(&mut redis_conn, conversation_id, &user_id)
+            .await
+            .map_err(|e| {
+                log::error!("Clear typing indicator error: {e}");
+            });
+
+        let event = InboxEvent::new(
+            InboxEventType::RemovedFromConversation,
+            conversation_id,
+            serde_json::json!({ "user_id": user_id }),
+        );
+        Self::publish_inbox(&mut redis_conn, user_id, &event).await?;
+
+        Ok(())
+    }
+
+    async fn send_message(
+        &mut self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        msg: SendMsg,
+        msg_type: String,
+        client_message_id: Option<String>,
+    ) -> Result<MessageAck, Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        // the target conversation must be one of the rooms the session joined
+        if !self
+            .is_session_in_room(&mut redis_conn, &user_id, &conn_id, conversation_id)
+            .await
+        {
+            return Err(
+                ChatError::MessageError("User has not joined this room".to_string()).into(),
+            );
+        }
+
+        // a retried send with the same client id is answered with the ack of the first one
+        let dedupe_key = match &client_message_id {
+            Some(id) => format!("room:{conversation_id}:client_msg:{user_id}:{id}"),
+            None => {
+                return self
+                    .publish_message(
+                        &mut redis_conn,
+                        user_id,
+                        conversation_id,
+                        &msg,
+                        &msg_type,
+                        None,
+                    )
+                    .await
+            }
+        };
+
+        let reserved: Option<String> = redis::cmd("SET")
+            .arg(&dedupe_key)
+            .arg("")
+            .arg("NX")
+            .arg("EX")
+            .arg(self.dedupe_window.as_secs())
+            .query_async(&mut redis_conn)
+            .await?;
+
+        if reserved.is_none() {
+            let sent: Option<String> = redis_conn.get(&dedupe_key).await?;
+            return match serde_json::from_str::<ClientMessage>(&sent.unwrap_or_default()) {
+                Ok(sent) if sent.content == msg && sent.r#type == msg_type => Ok(sent.ack),
+                Ok(_) => Err(ChatError::MessageError(
+                    "Client message id is already used by another message".to_string(),
+                )
+                .into()),
+                Err(_) => Err(ChatError::MessageError(
+                    "Message with the same id is still being processed".to_string(),
+                )
+                .into()),
+            };
+        }
+
+        match self
+            .publish_message(
+                &mut redis_conn,
+                user_id,
+                conversation_id,
+                &msg,
+                &msg_type,
+                client_message_id,
+            )
+            .await
+        {
+            Ok(ack) => {
+                let sent = ClientMessage {
+                    content: msg,
+                    r#type: msg_type,
+                    ack,
+                };
+                redis_conn
+                    .set_ex::<&str, &str, ()>(
+                        &dedupe_key,
+                        &serde_json::json!(sent).to_string(),
+                        self.dedupe_window.as_secs(),
+                    )
+                    .await?;
+                Ok(sent.ack)
+            }
+            Err(e) => {
+                // release the id so the client can retry
+                let _ = redis_conn.del::<&str, ()>(&dedupe_key).await.map_err(|e| {
+                    log::error!("Release client message id error: {e}");
+                });
+                Err(e)
+            }
+        }
+    }
+
+    async fn publish_message(
+        &self,
+        redis_conn: &mut deadpool_redis::Connection,
+        user_id: UserId,
+        conversation_id: ConversationId,
+        msg: &str,
+        msg_type: &str,
+        client_message_id: Option<String>,
+    ) -> Result<MessageAck, Box<dyn std::error::Error>> {
+        let (room_kind, slow_mode_secs) =
+            self.get_room_settings(redis_conn, conversation_id).await?;
+        self.check_send_limits(
+            redis_conn,
+            user_id,
+            conversation_id,
+            room_kind,
+            slow_mode_secs,
+            msg_type,
+        )
+        .await?;
+
+        // live rooms are ephemeral, only every n-th message is kept for moderation and replays
+        let is_live = room_kind == ConversationKind::Live;
+        let persist = !is_live
+            || self
+                .sample_live_message(redis_conn, conversation_id)
+                .await?;
+
+        // generate message json
+        let timestamp = Utc::now();
+        let mut msg_json = self
+            .generate_message_json(user_id, conversation_id, msg, msg_type, timestamp)
+            .await?;
+
+        let mut ack = MessageAck {
+            client_message_id: client_message_id.clone(),
+            message_id: None,
+            timestamp,
+        };
+
+        // messages are persisted before they are published, a failed insert is reported on the ack
+        // and nobody sees a message that is missing from history
+        // a retry of an already stored message returns the stored id and send time
+        // unsampled messages of a live room are only published, whatever their type
+        let persisted = match msg_type {
+            "message" if persist => match msg_json["message"].as_str() {
+                Some(content) => {
+                    let (message_id, sent_at, inserted) = self
+                        .message_repo
+                        .insert_message(
+                            conversation_id,
+                            user_id,
+                            Some(content.to_string()),
+                            MessageType::Message,
+                            timestamp,
+                            msg_json["reply_to"]["message_id"].as_i64(),
+                            client_message_id,
+                        )
+                        .await?;
+                    Some((message_id, sent_at, inserted, content.to_string()))
                 }
+                None => None,
+            },
+            "media" if persist => {
+                // media is uploaded beforehand, the message links the sender's attachments by url
+                let media = serde_json::from_value::<Vec<MediaContent>>(msg_json["media"].clone())?;
+                let urls: Vec<String> = media.iter().map(|m| m.url.clone()).collect();
+                let (message_id, sent_at, inserted) = self
+                    .message_repo
+                    .insert_media_message(
+                        conversation_id,
+                        user_id,
+                        timestamp,
+                        client_message_id,
+                        &urls,
+                    )
+                    .await?;
+                Some((message_id, sent_at, inserted, media_preview(&media)))
+            }
+            _ => None,
+        };
+
+        if let Some((message_id, sent_at, inserted, _)) = &persisted {
+            ack.message_id = Some(*message_id);
+            ack.timestamp = *sent_at;
+
+            // the stored message was delivered by the first attempt, the retry only gets its ack
+            if !inserted {
+                return Ok(ack);
             }
+
+            msg_json["message_id"] = serde_json::json!(message_id);
+            msg_json["timestamp"] = serde_json::json!(sent_at);
+        }
+
+        // publish message to subcriber
+        redis_conn
+            .publish::<&str, &str, ()>(&format!("room:{conversation_id}"), &msg_json.to_string())
+            .await?;
+
+        // live rooms have no participants to notify, pushes would fan out to every viewer
+        if let Some((message_id, sent_at, _, preview)) = persisted.filter(|_| !is_live) {
+            self.handle_offline_message(conversation_id, user_id, &preview, message_id);
+            self.handle_inbox_update(
+                conversation_id,
+                user_id,
+                msg_type,
+                preview,
+                message_id,
+                sent_at,
+            );
+        }
+
+        Ok(ack)
+    }
+
+    async fn send_typing(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        is_typing: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        if !self
+            .is_session_in_room(&mut redis_conn, &user_id, &conn_id, conversation_id)
+            .await
+        {
+            return Err(ChatError::TypingError("User has not joined this room".to_string()).into());
+        }
+
+        // indicators of thousands of viewers would drown a live room
+        let (room_kind, _) = self
+            .get_room_settings(&mut redis_conn, conversation_id)
+            .await?;
+        if room_kind == ConversationKind::Live {
+            return Err(ChatError::TypingError(
+                "Typing indicators are off in live rooms".to_string(),
+            )
+            .into());
+        }
+
+        if !is_typing {
+            return Self::clear_typing(&mut redis_conn, conversation_id, &user_id).await;
+        }
+
+        // typing state lives only in redis, each "start" refreshes the token that owns the expiry
+        let typing_key = format!("room:{conversation_id}:typing:{user_id}");
+        let token = Uuid::new_v4().to_string();
+
+        let was_typing: bool = redis_conn.exists(&typing_key).await?;
+
+        // the key outlives the expiry task so the task can still compare the token, it is only a safety net if this node dies
+        redis_conn
+            .set_ex::<&str, &str, ()>(&typing_key, &token, TYPING_TIMEOUT.as_secs() * 2)
+            .await?;
+
+        // only state changes are broadcast
+        if !was_typing {
+            Self::publish_typing(&mut redis_conn, conversation_id, user_id, true).await?;
+        }
+
+        // stop typing automatically if the client never sends "stop"
+        let redis_pool = self.redis_pool.clone();
+        tokio::spawn(async move {
+            sleep(TYPING_TIMEOUT).await;
+
+            let mut redis_conn = match redis_pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::error!("Get redis connection error: {e}");
+                    return;
+                }
+            };
+
+            // remove the key only if it has not been refreshed since
+            let script = redis::Script::new(
+                r"if redis.call('GET', KEYS[1]) == ARGV[1] then return redis.call('DEL', KEYS[1]) else return 0 end",
+            );
+            let removed: Result<i64, _> = script
+                .key(&typing_key)
+                .arg(&token)
+                .invoke_async(&mut redis_conn)
+                .await;
+
+            match removed {
+                Ok(1) => {
+                    let _ = Self::publish_typing(&mut redis_conn, conversation_id, user_id, false)
+                        .await
+                        .map_err(|e| {
+                            log::error!("Publish typing expiry error: {e}");
+                        });
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!("Expire typing indicator error: {e}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn clear_typing(
+        redis_conn: &mut deadpool_redis::Connection,
+        conversation_id: ConversationId,
+        user_id: &UserId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let removed: i64 = redis_conn
+            .del(&format!("room:{conversation_id}:typing:{user_id}"))
+            .await?;
+
+        if removed > 0 {
+            Self::publish_typing(redis_conn, conversation_id, *user_id, false).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn publish_typing(
+        redis_conn: &mut deadpool_redis::Connection,
+        conversation_id: ConversationId,
+        user_id: UserId,
+        is_typing: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let payload = serde_json::json!(TypingIndicator {
+            conversation_id,
+            sender_id: user_id,
+            r#type: "typing".to_string(),
+            is_typing,
+            timestamp: Utc::now(),
+        });
+
+        redis_conn
+            .publish::<&str, &str, ()>(&format!("room:{conversation_id}"), &payload.to_string())
+            .await?;
+
+        Ok(())
+    }
+
+    async fn send_receipt(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        message_id: i64,
+        state: ReceiptState,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        if !self
+            .is_session_in_room(&mut redis_conn, &user_id, &conn_id, conversation_id)
+            .await
+        {
+            return Err(
+                ChatError::ReceiptError("User has not joined this room".to_string()).into(),
+            );
         }
 
-        if !is_remain {
+        // cursors only move forward, nothing is broadcast when the state did not change
+        let cursor = match state {
+            ReceiptState::Read => {
+                self.conversation_repo
+                    .mark_as_read(conversation_id, user_id, Some(message_id))
+                    .await?
+            }
+            ReceiptState::Delivered => self
+                .conversation_repo
+                .mark_as_delivered(conversation_id, &[user_id], message_id)
+                .await?
+                .first()
+                .map(|(_, cursor)| *cursor),
+        };
+
+        if let Some(cursor) = cursor {
+            let receipt = ReadReceipt::new(conversation_id, user_id, cursor, state);
             redis_conn
-                .srem::<&str, &str, ()>(
-                    &format!("room:{conversation_id}:active_users"),
-                    &user_id.to_string(),
+                .publish::<&str, &str, ()>(
+                    &format!("room:{conversation_id}"),
+                    &serde_json::json!(receipt).to_string(),
                 )
                 .await?;
 
-            // if there are no users in the room, unsubscribe
-            if redis_conn
-                .scard::<&str, i32>(&format!("room:{conversation_id}:active_users"))
-                .await?
-                == 0
-            {
-                self.unsubscribe(format!("room:{conversation_id}")).await;
+            // reading moves the unread badge of every session of the reader
+            if state == ReceiptState::Read {
+                let unread_count = self
+                    .conversation_repo
+                    .get_conversation_unread_count(conversation_id, user_id)
+                    .await?;
+                let event = InboxEvent::new(
+                    InboxEventType::UnreadChanged,
+                    conversation_id,
+                    serde_json::json!(unread_count),
+                );
+                Self::publish_inbox(&mut redis_conn, user_id, &event).await?;
             }
         }
 
         Ok(())
     }
 
-    async fn send_message(
-        &mut self,
+    async fn send_reaction(
+        &self,
         user_id: UserId,
         conn_id: ConnId,
-        msg: SendMsg,
-        msg_type: String,
+        message_id: i64,
+        emoji: String,
+        action: ReactionAction,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let mut redis_conn = self.redis_pool.get().await?;
 
-        // get the current conversation the user's session is participating in
-        let active_room = match self
-            .get_current_session_room(&mut redis_conn, &user_id, &conn_id)
-            .await
-        {
-            Some(room_id) => room_id,
-            None => return Err(ChatError::MessageError(format!("User is not in any room")).into()),
+        let emoji = emoji.trim().to_string();
+        validate_emoji(&emoji).map_err(|e| ChatError::ReactionError(e.to_string()))?;
+
+        // only messages of a joined room can be reacted to
+        let conversation_id = match self.message_repo.find_message_by_id(message_id).await? {
+            Some(message)
+                if self
+                    .is_session_in_room(
+                        &mut redis_conn,
+                        &user_id,
+                        &conn_id,
+                        message.conversation_id,
+                    )
+                    .await =>
+            {
+                message.conversation_id
+            }
+            _ => {
+                return Err(ChatError::ReactionError(
+                    "Message not found in joined rooms".to_string(),
+                )
+                .into())
+            }
         };
 
-        // generate message json
-        let timestamp = Utc::now();
-        let msg_json = match Self::generate_message_json(
-            user_id.clone(),
-            &msg,
-            &msg_type,
-            timestamp.clone(),
-        ) {
-            Ok(value) => value,
-            Err(e) => {
-                return Err(e.into());
+        let changed = match action {
+            ReactionAction::Add => {
+                self.reaction_repo
+                    .insert_reaction(message_id, user_id, &emoji)
+                    .await?
+            }
+            ReactionAction::Remove => {
+                self.reaction_repo
+                    .delete_reaction(message_id, user_id, &emoji)
+                    .await?
             }
         };
 
-        // publish message to subcriber
-        match redis_conn
-            .publish::<&str, &str, ()>(&format!("room:{active_room}"), &msg_json.to_string())
-            .await
-        {
-            Ok(_) => {
-                if msg_type == "message" {
-                    // for normal messages
-                    if let Some(content) = msg_json["message"].as_str() {
-                        self.handle_offline_message(
-                            active_room.parse::<i32>()?,
-                            user_id,
-                            content,
-                            timestamp,
-                        );
-                    }
+        if changed {
+            let event = ReactionEvent::new(conversation_id, message_id, user_id, emoji, action);
+            redis_conn
+                .publish::<&str, &str, ()>(
+                    &format!("room:{conversation_id}"),
+                    &serde_json::json!(event).to_string(),
+                )
+                .await?;
+        }
 
-                    // !TODO: for live stream messages
-                }
-            }
-            Err(e) => {
-                return Err(e.into());
-            }
+        Ok(())
+    }
+
+    async fn broadcast(
+        &self,
+        conversation_id: ConversationId,
+        msg: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        redis_conn
+            .publish::<&str, &str, ()>(&format!("room:{conversation_id}"), msg)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn notify_inbox(
+        &self,
+        user_ids: &[UserId],
+        event: &InboxEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        for user_id in user_ids {
+            Self::publish_inbox(&mut redis_conn, *user_id, event).await?;
         }
 
         Ok(())
     }
 
-    async fn _send_system_message() {
-        todo!();
+    async fn publish_inbox(
+        redis_conn: &mut deadpool_redis::Connection,
+        user_id: UserId,
+        event: &InboxEvent,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        redis_conn
+            .publish::<&str, &str, ()>(
+                &format!("user:{user_id}:inbox"),
+                &serde_json::json!(event).to_string(),
+            )
+            .await?;
+
+        Ok(())
     }
 
-    async fn subscribe(&self, channel: String) -> Result<(), Box<dyn std::error::Error>> {
-        // Check if already subscribed
-        let channels = self.subscribed_channels.read().await;
-        if channels.contains_key(&channel) {
-            log::info!("Already subscribed to channel: {}", channel);
-            return Ok(());
+    async fn send_system_message(
+        &self,
+        conversation_id: ConversationId,
+        event: SystemEvent,
+    ) -> Result<i64, Box<dyn std::error::Error>> {
+        if self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+            .is_none()
+        {
+            return Err(ChatError::SystemMessageError("Conversation not found".to_string()).into());
         }
-        drop(channels);
 
-        // Add channel to subscribed list
-        let (unsub_tx, mut unsub_rx) = watch::channel(false);
-        let mut channels = self.subscribed_channels.write().await;
-        channels.insert(channel.clone(), unsub_tx);
-        drop(channels);
+        // system messages go through the same history as user messages, the event is the content
+        let content = serde_json::json!(event).to_string();
+        let (message_id, sent_at, _) = self
+            .message_repo
+            .insert_message(
+                conversation_id,
+                SYSTEM_SENDER_ID,
+                Some(content.clone()),
+                MessageType::System,
+                Utc::now(),
+                None,
+                None,
+            )
+            .await?;
 
-        let client = self.redis_client.clone();
-        let channel_clone = channel.clone();
-        let sessions = self.sessions.clone();
-        let redis_pool = self.redis_pool.clone();
+        let payload = SentSystemMessage {
+            conversation_id,
+            message_id,
+            sender_id: SYSTEM_SENDER_ID,
+            r#type: "system".to_string(),
+            event,
+            timestamp: sent_at,
+        };
 
-        // Spawn a task for the subscription
-        tokio::spawn(async move {
-            // Create a pub/sub connection
-            let mut pubsub = client.get_async_pubsub().await.unwrap();
-            if let Err(e) = pubsub.subscribe(&channel_clone).await {
-                log::error!("Failed to subscribe to channel {}: {}", channel_clone, e);
-                return;
-            }
+        let mut redis_conn = self.redis_pool.get().await?;
+        redis_conn
+            .publish::<&str, &str, ()>(
+                &format!("room:{conversation_id}"),
+                &serde_json::json!(payload).to_string(),
+            )
+            .await?;
 
-            log::info!("Subscribed to channel: {}", channel_clone);
+        // no push notification, only the latest message and conversation lists are refreshed
+        redis_conn
+            .hset::<&str, &str, i64, ()>(
+                "pending_updates",
+                &conversation_id.to_string(),
+                message_id,
+            )
+            .await?;
+        self.handle_inbox_update(
+            conversation_id,
+            SYSTEM_SENDER_ID,
+            "system",
+            content,
+            message_id,
+            sent_at,
+        );
+
+        Ok(message_id)
+    }
 
-            let mut msg_stream = pubsub.on_message();
+    async fn subscribe(&self, channel: String) -> Result<(), Box<dyn std::error::Error>> {
+        self.pubsub.subscribe(channel).await
+    }
 
-            loop {
-                let unsub_rx = pin!(unsub_rx.changed());
+    async fn unsubscribe(&self, channel: String) {
+        self.pubsub.unsubscribe(channel).await
+    }
 
-                let msg = pin!(msg_stream.next());
+    async fn start_pubsub_dispatch(
+        sessions: Arc<RwLock<HashMap<ConnId, ConnSender>>>,
+        room_index: Arc<RwLock<LocalRoomIndex>>,
+        pubsub: PubSubManager,
+        mut pubsub_rx: mpsc::UnboundedReceiver<PubSubMessage>,
+    ) {
+        while let Some((channel, payload)) = pubsub_rx.recv().await {
+            if let Err(e) = Self::handle_incoming_messages(
+                sessions.clone(),
+                room_index.clone(),
+                &pubsub,
+                &channel,
+                &payload,
+            )
+            .await
+            {
+                log::error!("Failed to handle incomming message: {e}");
+            }
+        }
+    }
 
-                match select(unsub_rx, msg).await {
-                    // handle unsubscription
-                    Either::Left((Ok(_), _)) => {
-                        log::info!("Unsubscribe from channel: {channel_clone}");
-                        break;
+    async fn handle_incoming_messages(
+        sessions: Arc<RwLock<HashMap<ConnId, ConnSender>>>,
+        room_index: Arc<RwLock<LocalRoomIndex>>,
+        pubsub: &PubSubManager,
+        channel: &str,
+        message: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let target_conns = if channel.starts_with("room:") {
+            let conversation_id = channel.split(":").collect::<Vec<&str>>()[1].parse::<i32>()?;
+
+            // typing indicators are not echoed back to the typing user
+            let skip_user = serde_json::from_str::<serde_json::Value>(message)
+                .ok()
+                .filter(|value| value["type"] == "typing")
+                .and_then(|value| value["sender_id"].as_str().map(|v| v.to_string()))
+                .and_then(|user_id| Uuid::parse_str(&user_id).ok());
+
+            // delivery to local sessions active in conversation
+            room_index
+                .read()
+                .await
+                .room_connections(conversation_id, skip_user)
+        } else if channel.starts_with("user:") && channel.ends_with(":inbox") {
+            let user_id = Uuid::parse_str(channel.split(":").collect::<Vec<&str>>()[1])?;
+
+            // inbox events go to every local session of the user
+            let user_conns = room_index.read().await.user_connections(user_id);
+
+            // a user removed from a group stops receiving its room on this node
+            let removed_from = serde_json::from_str::<serde_json::Value>(message)
+                .ok()
+                .filter(|value| value["type"] == "removed_from_conversation")
+                .and_then(|value| value["conversation_id"].as_i64());
+            if let (Some(conversation_id), false) = (removed_from, user_conns.is_empty()) {
+                let conversation_id = conversation_id as ConversationId;
+                let is_room_empty = {
+                    let mut room_index = room_index.write().await;
+                    for conn_id in &user_conns {
+                        room_index.leave(conversation_id, *conn_id);
                     }
+                    room_index
+                        .room_connections(conversation_id, None)
+                        .is_empty()
+                };
+                if is_room_empty {
+                    pubsub.unsubscribe(format!("room:{conversation_id}")).await;
+                }
+            }
 
-                    Either::Left((Err(_), _)) => {
-                        log::warn!("Unsub channel closed");
-                        log::info!("Unsubscribe from channel: {channel_clone}");
-                        break;
-                    }
+            user_conns
+        } else {
+            log::error!("Invalid channel");
+            return Ok(());
+        };
 
-                    // handle incoming messages
-                    Either::Right((Some(msg), _)) => {
-                        match msg.get_payload::<String>() {
-                            Ok(payload) => {
-                                // log::info!("Received message on channel {}: {}", channel_clone, payload);
-                                if let Err(e) = Self::handle_incoming_messages(
-                                    sessions.clone(),
-                                    redis_pool.clone(),
-                                    &channel_clone,
-                                    &payload,
-                                )
-                                .await
-                                {
-                                    log::error!("Failed to handle incomming message: {e}");
-                                }
-                            }
-                            Err(e) => {
-                                log::error!(
-                                    "Failed to decode message on channel {channel_clone}: {e}"
-                                );
-                            }
-                        }
+        let sessions = sessions.read().await;
+        for conn_id in &target_conns {
+            if let Some(sender) = sessions.get(conn_id) {
+                match sender.send(message.to_string()) {
+                    Ok(_) => {}
+                    // the session is closing, its disconnect is on the way
+                    Err(PushError::Closed) => {
+                        log::debug!("Session {conn_id} is closed, message skipped");
                     }
-                    Either::Right((None, _)) => {
-                        log::info!("PubSub stream closed");
-                        break;
+                    Err(PushError::TooSlow) => {
+                        log::warn!("Session {conn_id} is too slow, message skipped");
                     }
                 }
             }
-        });
+        }
 
         Ok(())
     }
 
-    async fn unsubscribe(&self, channel: String) {
-        // Check if already subscribed
-        let channels = self.subscribed_channels.read().await;
-        if !channels.contains_key(&channel) {
-            log::info!("Already unsubscribed from channel: {}", channel);
+    async fn cache_room_settings(
+        redis_conn: &mut deadpool_redis::Connection,
+        conversation_id: ConversationId,
+        kind: ConversationKind,
+        slow_mode_secs: i32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let kind = serde_json::json!(kind);
+        redis_conn
+            .hset_multiple::<&str, &str, String, ()>(
+                &format!("room:{conversation_id}"),
+                &[
+                    ("kind", kind.as_str().unwrap_or_default().to_string()),
+                    ("slow_mode_secs", slow_mode_secs.to_string()),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    // room settings are read on every message, they are cached in the room hash when a user joins
+    async fn get_room_settings(
+        &self,
+        redis_conn: &mut deadpool_redis::Connection,
+        conversation_id: ConversationId,
+    ) -> Result<(ConversationKind, i32), Box<dyn std::error::Error>> {
+        let (kind, slow_mode_secs): (Option<String>, Option<i32>) = redis_conn
+            .hget(
+                &format!("room:{conversation_id}"),
+                &["kind", "slow_mode_secs"],
+            )
+            .await?;
+
+        if let (Some(kind), Some(slow_mode_secs)) = (kind, slow_mode_secs) {
+            if let Ok(kind) = serde_json::from_value(serde_json::Value::String(kind)) {
+                return Ok((kind, slow_mode_secs));
+            }
         }
 
-        // get unsubscription sender
-        let unsub_tx = match channels.get(&channel) {
-            Some(tx) => tx,
+        let conversation = match self
+            .conversation_repo
+            .find_conversation_by_id(conversation_id)
+            .await?
+        {
+            Some(conversation) => conversation,
             None => {
-                log::error!("Unsubscription sender not found");
-                return;
+                return Err(ChatError::MessageError("Conversation not found".to_string()).into())
             }
         };
+        Self::cache_room_settings(
+            redis_conn,
+            conversation_id,
+            conversation.kind,
+            conversation.slow_mode_secs,
+        )
+        .await?;
 
-        // send unsubscibe signal
-        match unsub_tx.send(true) {
-            Ok(_) => {
-                // remove subscription in local
-                drop(channels);
-                let mut channels = self.subscribed_channels.write().await;
-                channels.remove(&channel);
-                log::info!("Channel {channel} removed");
-            }
-            Err(e) => {
-                log::error!("Send error: {e}");
-            }
-        }
+        Ok((conversation.kind, conversation.slow_mode_secs))
     }
 
-    async fn handle_incoming_messages(
-        sessions: Arc<RwLock<HashMap<ConnId, mpsc::UnboundedSender<SendMsg>>>>,
-        redis_pool: Arc<Pool>,
-        channel: &str,
-        message: &str,
+    async fn check_send_limits(
+        &self,
+        redis_conn: &mut deadpool_redis::Connection,
+        user_id: UserId,
+        conversation_id: ConversationId,
+        kind: ConversationKind,
+        slow_mode_secs: i32,
+        msg_type: &str,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if channel.starts_with("room:") {
-            let mut redis_conn = redis_pool.get().await?;
-            let conversation_id: &str = channel.split(":").collect::<Vec<&str>>()[1];
-
-            // delivery to sessions active in conversation
-            // get users in current conversation
-            let active_users: Vec<String> = redis_conn
-                .smembers(&format!("room:{conversation_id}:active_users"))
-                .await?;
-
-            let sessions = sessions.read().await;
+        if kind == ConversationKind::Live {
+            if msg_type != "message" {
+                return Err(ChatError::MessageError(
+                    "Only text messages are allowed in live rooms".to_string(),
+                )
+                .into());
+            }
 
-            // find session (local conn_id) of users in current conversation and send message to that session
-            for user_id in &active_users {
-                // get all sessions of the user
-                let user_sessions: HashMap<String, String> = redis_conn
-                    .hgetall(&format!("user:{}:sessions", user_id))
-                    .await?;
+            if self.live_config.rate_limit > 0 {
+                let rate_key = format!("room:{conversation_id}:rate:{user_id}");
+                let count: u64 = redis_conn.incr(&rate_key, 1).await?;
+                if count == 1 {
+                    redis_conn
+                        .expire::<&str, ()>(
+                            &rate_key,
+                            self.live_config.rate_limit_window.as_secs() as i64,
+                        )
+                        .await?;
+                }
+                if count > self.live_config.rate_limit {
+                    return Err(ChatError::MessageError(
+                        "Too many messages, please slow down".to_string(),
+                    )
+                    .into());
+                }
+            }
+        }
 
-                // get the session in the current conversation
-                let active_sessions: Vec<_> = user_sessions
-                    .into_iter()
-                    .filter_map(|(session_id, status)| {
-                        let value: serde_json::Value = serde_json::from_str(&status).ok()?;
-                        let active_room = value.get("active_room")?.as_str()?;
-                        if active_room == conversation_id {
-                            Some(session_id)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+        if slow_mode_secs > 0 {
+            let slow_mode_key = format!("room:{conversation_id}:slow_mode:{user_id}");
+            let allowed: Option<String> = redis::cmd("SET")
+                .arg(&slow_mode_key)
+                .arg("")
+                .arg("NX")
+                .arg("EX")
+                .arg(slow_mode_secs)
+                .query_async(redis_conn)
+                .await?;
 
-                for conn_id in &active_sessions {
-                    if let Ok(conn_id) = Uuid::parse_str(&conn_id) {
-                        if let Some(sender) = sessions.get(&conn_id) {
-                            sender.send(message.to_string()).unwrap();
-                        }
-                    }
-                }
+            if allowed.is_none() {
+                let wait: i64 = redis_conn.ttl(&slow_mode_key).await?;
+                return Err(ChatError::MessageError(format!(
+                    "Slow mode is on, wait {} seconds",
+                    wait.max(1)
+                ))
+                .into());
             }
-        } else {
-            log::error!("Invalid channel");
         }
+
         Ok(())
     }
 
-    async fn get_current_session_room(
+    async fn sample_live_message(
+        &self,
+        redis_conn: &mut deadpool_redis::Connection,
+        conversation_id: ConversationId,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.live_config.sample_rate == 0 {
+            return Ok(false);
+        }
+
+        let count: u64 = redis_conn
+            .incr(&format!("room:{conversation_id}:live_messages"), 1)
+            .await?;
+
+        Ok(count % self.live_config.sample_rate == 0)
+    }
+
+    async fn get_session_rooms(
         &self,
         redis_conn: &mut deadpool_redis::Connection,
         user_id: &UserId,
         conn_id: &ConnId,
-    ) -> Option<String> {
-        let session_state: String = match redis_conn
+    ) -> HashSet<String> {
+        let session_state: Option<String> = match redis_conn
             .hget(&format!("user:{user_id}:sessions"), &conn_id.to_string())
             .await
         {
             Ok(result) => result,
             Err(e) => {
-                log::error!("Get room error: {e}");
-                return None;
+                log::error!("Get rooms error: {e}");
+                return HashSet::new();
             }
         };
 
-        let value: serde_json::Value = match serde_json::from_str(&session_state) {
+        Self::parse_session_rooms(&session_state.unwrap_or_default())
+    }
+
+    // reads the rooms out of a raw session state of the `user:{id}:sessions` hash
+    fn parse_session_rooms(session_state: &str) -> HashSet<String> {
+        let value: serde_json::Value = match serde_json::from_str(session_state) {
             Ok(value) => value,
             Err(e) => {
-                log::error!("Get room error: {e}");
-                return None;
-            }
-        };
-
-        let active_room = match value["active_room"].as_str() {
-            Some(value) => value,
-            None => {
-                log::error!("Room not found");
-                return None;
+                log::error!("Get rooms error: {e}");
+                return HashSet::new();
             }
         };
 
-        if active_room.is_empty() {
-            return None;
+        match value["active_rooms"].as_array() {
+            Some(rooms) => rooms
+                .iter()
+                .filter_map(|room| room.as_str().map(|room| room.to_string()))
+                .collect(),
+            None => HashSet::new(),
         }
+    }
+
+    async fn set_session_rooms(
+        redis_conn: &mut deadpool_redis::Connection,
+        node_id: &str,
+        user_id: &UserId,
+        conn_id: &ConnId,
+        rooms: &HashSet<String>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        redis_conn
+            .hset::<&str, &str, &str, ()>(
+                &format!("user:{user_id}:sessions"),
+                &conn_id.to_string(),
+                &serde_json::json!({ "active_rooms": rooms, "node": node_id }).to_string(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn is_session_in_room(
+        &self,
+        redis_conn: &mut deadpool_redis::Connection,
+        user_id: &UserId,
+        conn_id: &ConnId,
+        conversation_id: ConversationId,
+    ) -> bool {
+        self.get_session_rooms(redis_conn, user_id, conn_id)
+            .await
+            .contains(&conversation_id.to_string())
+    }
 
-        Some(active_room.to_string())
+    // checks a raw session state of the `user:{id}:sessions` hash
+    fn session_has_room(session_state: &str, conversation_id: &str) -> bool {
+        serde_json::from_str::<serde_json::Value>(session_state)
+            .ok()
+            .and_then(|value| {
+                value["active_rooms"].as_array().map(|rooms| {
+                    rooms
+                        .iter()
+                        .any(|room| room.as_str() == Some(conversation_id))
+                })
+            })
+            .unwrap_or_default()
     }
 
     async fn get_not_active_users(
@@ -680,29 +1941,25 @@ impl ChatServer {
         conversation_id: i32,
         sender_id: Uuid,
         content: &str,
-        sent_at: DateTime<Utc>,
+        message_id: i64,
     ) {
         let redis_pool = self.redis_pool.clone();
-        let message_repo = self.message_repo.clone();
         let conversation_repo = self.conversation_repo.clone();
         let content_clone = content.to_owned();
         let mut notification_client = self.notification_service_client.clone();
 
         tokio::spawn(async move {
             // get inactive users
-            let inactive_users =
-                Self::get_not_active_users(redis_pool.clone(), conversation_repo, conversation_id)
-                    .await
-                    .unwrap_or_default();
+            let inactive_users = Self::get_not_active_users(
+                redis_pool.clone(),
+                conversation_repo.clone(),
+                conversation_id,
+            )
+            .await
+            .unwrap_or_default();
 
             log::info!("inactive: {:?}", inactive_users);
 
-            let is_read = if inactive_users.is_empty() {
-                true
-            } else {
-                false
-            };
-
             // send push notificaiton to inactive users
             let title = format!("New message from {}", sender_id);
             for user_id in inactive_users {
@@ -736,44 +1993,169 @@ impl ChatServer {
                 };
             }
 
-            // save message and status to database
-            let message_id = message_repo
-                .insert_message(
-                    conversation_id,
-                    sender_id,
-                    Some(content_clone),
-                    MessageType::Message,
-                    sent_at,
-                    is_read,
-                )
-                .await;
+            // cache latest message
+            let redis_conn = redis_pool.get().await;
+            match redis_conn {
+                Ok(mut conn) => {
+                    let _ = conn
+                        .hset::<&str, &str, i64, ()>(
+                            "pending_updates",
+                            &conversation_id.to_string(),
+                            message_id,
+                        )
+                        .await
+                        .map_err(|e| {
+                            log::error!("Cache latest message error: {e}");
+                        });
+                }
+                Err(e) => {
+                    log::error!("Get redis connection error: {e}");
+                }
+            }
+
+            // participants active in the room received the message through their sockets
+            let _ = Self::mark_delivered_to_active_users(
+                redis_pool.clone(),
+                conversation_repo,
+                conversation_id,
+                sender_id,
+                message_id,
+            )
+            .await
+            .map_err(|e| {
+                log::error!("Mark message as delivered error: {e}");
+            });
+        });
+    }
+
+    // refreshes the conversation list of every participant with the new latest message
+    fn handle_inbox_update(
+        &self,
+        conversation_id: i32,
+        sender_id: Uuid,
+        msg_type: &str,
+        content: String,
+        message_id: i64,
+        sent_at: DateTime<Utc>,
+    ) {
+        let redis_pool = self.redis_pool.clone();
+        let conversation_repo = self.conversation_repo.clone();
+        let msg_type = msg_type.to_owned();
+
+        tokio::spawn(async move {
+            let participants = match conversation_repo
+                .find_users_by_conversation_id(conversation_id)
+                .await
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    log::error!("Get participants of room {conversation_id} error: {e}");
+                    return;
+                }
+            };
+
+            let mut redis_conn = match redis_pool.get().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::error!("Get redis connection error: {e}");
+                    return;
+                }
+            };
 
-            if message_id.is_ok() {
-                // cache latest message
-                let redis_conn = redis_pool.get().await;
-                match redis_conn {
-                    Ok(mut conn) => {
-                        let _ = conn
-                            .hset::<&str, &str, i64, ()>(
-                                "pending_updates",
-                                &conversation_id.to_string(),
-                                message_id.unwrap(),
-                            )
-                            .await
-                            .map_err(|e| {
-                                log::error!("Cache latest message error: {e}");
-                            });
-                    }
-                    Err(e) => {
-                        log::error!("Get redis connection error: {e}");
-                    }
+            let updated = InboxEvent::new(
+                InboxEventType::ConversationUpdated,
+                conversation_id,
+                serde_json::json!({
+                    "message_id": message_id,
+                    "sender_id": sender_id,
+                    "type": msg_type,
+                    "content": content,
+                    "sent_at": sent_at,
+                }),
+            );
+
+            // one count query for the whole room instead of one per recipient
+            let recipients: Vec<Uuid> = participants
+                .iter()
+                .map(|participant| participant.user_id)
+                .filter(|user_id| *user_id != sender_id)
+                .collect();
+            let unread_counts = match conversation_repo
+                .get_unread_counts(conversation_id, &recipients)
+                .await
+            {
+                Ok(counts) => counts,
+                Err(e) => {
+                    log::error!("Get unread counts of room {conversation_id} error: {e}");
+                    HashMap::new()
+                }
+            };
+
+            for participant in participants {
+                let user_id = participant.user_id;
+                if let Err(e) = Self::publish_inbox(&mut redis_conn, user_id, &updated).await {
+                    log::error!("Publish conversation update to user {user_id} error: {e}");
+                    continue;
                 }
+
+                let Some(unread_count) = unread_counts.get(&user_id) else {
+                    continue;
+                };
+                let unread = InboxEvent::new(
+                    InboxEventType::UnreadChanged,
+                    conversation_id,
+                    serde_json::json!(unread_count),
+                );
+                let _ = Self::publish_inbox(&mut redis_conn, user_id, &unread)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Publish unread count to user {user_id} error: {e}");
+                    });
             }
         });
     }
 
-    fn generate_message_json(
+    async fn mark_delivered_to_active_users(
+        redis_pool: Arc<Pool>,
+        conversation_repo: Arc<ConversationRepo>,
+        conversation_id: ConversationId,
+        sender_id: UserId,
+        message_id: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = redis_pool.get().await?;
+
+        let active_user_ids: Vec<String> = redis_conn
+            .smembers(format!("room:{conversation_id}:active_users"))
+            .await?;
+
+        let recipients: Vec<Uuid> = active_user_ids
+            .iter()
+            .filter_map(|uid| Uuid::parse_str(uid).ok())
+            .filter(|uid| *uid != sender_id)
+            .collect();
+
+        let delivered = conversation_repo
+            .mark_as_delivered(conversation_id, &recipients, message_id)
+            .await?;
+
+        for (user_id, cursor) in delivered {
+            let receipt =
+                ReadReceipt::new(conversation_id, user_id, cursor, ReceiptState::Delivered);
+            redis_conn
+                .publish::<&str, &str, ()>(
+                    &format!("room:{conversation_id}"),
+                    &serde_json::json!(receipt).to_string(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn generate_message_json(
+        &self,
         user_id: UserId,
+        conversation_id: ConversationId,
         msg: &str,
         msg_type: &str,
         timestamp: DateTime<Utc>,
@@ -784,11 +2166,36 @@ impl ChatServer {
                     if value.message.is_empty() {
                         return Err(ChatError::MessageError("Empty message body".to_string()));
                     }
+
+                    // a reply may only quote a message of the same conversation
+                    let reply_to = match value.reply_to {
+                        Some(reply_to) => match self
+                            .message_repo
+                            .find_reply_preview(reply_to, conversation_id)
+                            .await
+                        {
+                            Ok(Some(preview)) => Some(preview),
+                            Ok(None) => {
+                                return Err(ChatError::MessageError(
+                                    "Replied message not found in this conversation".to_string(),
+                                ))
+                            }
+                            Err(e) => {
+                                return Err(ChatError::MessageError(format!(
+                                    "Cannot get replied message - {e}"
+                                )))
+                            }
+                        },
+                        None => None,
+                    };
+
                     return Ok(serde_json::json!(SentMessage {
+                        conversation_id,
                         sender_id: user_id,
                         r#type: "message".to_string(),
                         message: value.message,
                         timestamp: timestamp.clone(),
+                        reply_to,
                     }));
                 }
                 Err(ChatError::MessageError(
@@ -804,6 +2211,7 @@ impl ChatServer {
                         }
                     }
                     return Ok(serde_json::json!(SentMedia {
+                        conversation_id,
                         sender_id: user_id,
                         r#type: "media".to_string(),
                         timestamp: timestamp.clone(),
@@ -847,6 +2255,424 @@ impl ChatServer {
         }
     }
 
+    async fn start_viewer_count_interval(redis_pool: Arc<Pool>, period: Duration) {
+        let mut interval = interval(period);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = Self::publish_viewer_counts(&redis_pool, period).await {
+                log::error!("Error publishing viewer counts: {e}");
+            }
+        }
+    }
+
+    async fn publish_viewer_counts(
+        redis_pool: &Arc<Pool>,
+        period: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = redis_pool.get().await?;
+
+        let live_rooms: Vec<String> = redis_conn.smembers("live_rooms").await?;
+
+        for conversation_id in live_rooms {
+            // every node runs this interval, the first one to take the tick publishes the count
+            let acquired: Option<String> = redis::cmd("SET")
+                .arg(&format!("room:{conversation_id}:viewer_count_tick"))
+                .arg("")
+                .arg("NX")
+                .arg("PX")
+                .arg(period.as_millis() as u64)
+                .query_async(&mut redis_conn)
+                .await?;
+
+            if acquired.is_none() {
+                continue;
+            }
+
+            let viewers: i64 = redis_conn
+                .scard(&format!("room:{conversation_id}:active_users"))
+                .await?;
+
+            if viewers == 0 {
+                redis_conn
+                    .srem::<&str, &str, ()>("live_rooms", &conversation_id)
+                    .await?;
+                continue;
+            }
+
+            let conversation_id = match conversation_id.parse::<i32>() {
+                Ok(id) => id,
+                Err(e) => {
+                    log::error!("Failed to parse conversation id '{conversation_id}': {e}");
+                    continue;
+                }
+            };
+
+            let payload = ViewerCount::new(conversation_id, viewers);
+            redis_conn
+                .publish::<&str, &str, ()>(
+                    &format!("room:{conversation_id}"),
+                    &serde_json::json!(payload).to_string(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    // closes every local session with a reconnect hint and removes the node's state from redis
+    async fn shutdown(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let node_id = &self.node_config.node_id;
+
+        let sessions = self.sessions.read().await;
+        for conn_tx in sessions.values() {
+            conn_tx.close_for_restart();
+        }
+        log::info!("Closing {} sessions of chat node {node_id}", sessions.len());
+        drop(sessions);
+
+        let mut redis_conn = self.redis_pool.get().await?;
+
+        let purged = Self::purge_node(
+            &self.redis_pool,
+            &self.conversation_repo,
+            &mut redis_conn,
+            node_id,
+        )
+        .await?;
+        redis_conn
+            .del::<&str, ()>(&format!("node:{node_id}:heartbeat"))
+            .await?;
+        redis_conn
+            .srem::<&str, &str, ()>("chat_nodes", node_id)
+            .await?;
+
+        // latest messages not yet written by the cache interval
+        let pending_updates: i64 = redis_conn.hlen("pending_updates").await?;
+        if pending_updates > 0 {
+            Self::process_updates(&self.conversation_repo, &mut redis_conn).await?;
+        }
+
+        log::info!("Chat node {node_id} stopped, purged {purged} sessions and flushed {pending_updates} latest messages");
+
+        Ok(())
+    }
+
+    // purges the sessions this node left behind, then announces the node with a fresh heartbeat
+    async fn register_node(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+        let node_id = &self.node_config.node_id;
+
+        let purged = Self::purge_node(
+            &self.redis_pool,
+            &self.conversation_repo,
+            &mut redis_conn,
+            node_id,
+        )
+        .await?;
+        if purged > 0 {
+            log::warn!("Purged {purged} stale sessions of chat node {node_id}");
+        }
+
+        Self::send_heartbeat(&mut redis_conn, node_id, self.node_config.heartbeat_ttl).await?;
+
+        log::info!("Chat node {node_id} registered");
+
+        Ok(())
+    }
+
+    // writes the sessions of the open connections back to redis, returns how many were restored
+    async fn restore_sessions(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let mut redis_conn = self.redis_pool.get().await?;
+        let node_id = &self.node_config.node_id;
+
+        let sessions = self.room_index.read().await.sessions();
+        let mut users = HashSet::new();
+
+        for (user_id, conn_id, rooms) in &sessions {
+            let rooms: HashSet<String> = rooms.iter().map(|room| room.to_string()).collect();
+
+            Self::set_session_rooms(&mut redis_conn, node_id, user_id, conn_id, &rooms).await?;
+            redis_conn
+                .sadd::<&str, &str, ()>(
+                    &format!("node:{node_id}:sessions"),
+                    &format!("{user_id}:{conn_id}"),
+                )
+                .await?;
+
+            // the activity of a session is only kept in redis, it comes back active until the
+            // client reports otherwise
+            redis_conn
+                .hset::<&str, &str, &str, ()>(
+                    &format!("user:{user_id}:session_states"),
+                    &conn_id.to_string(),
+                    &ActivityState::Active.to_string(),
+                )
+                .await?;
+
+            for conversation_id in &rooms {
+                redis_conn
+                    .sadd::<&str, &str, ()>(
+                        &format!("room:{conversation_id}:active_users"),
+                        &user_id.to_string(),
+                    )
+                    .await?;
+            }
+
+            users.insert(*user_id);
+        }
+
+        for user_id in users {
+            redis_conn
+                .sadd::<&str, &str, ()>("online_users", &user_id.to_string())
+                .await?;
+            Self::update_presence(
+                &self.redis_pool,
+                &self.conversation_repo,
+                &mut redis_conn,
+                user_id,
+            )
+            .await?;
+        }
+
+        Ok(sessions.len())
+    }
+
+    async fn send_heartbeat(
+        redis_conn: &mut deadpool_redis::Connection,
+        node_id: &str,
+        heartbeat_ttl: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        redis_conn
+            .set_ex::<&str, &str, ()>(
+                &format!("node:{node_id}:heartbeat"),
+                &Utc::now().to_rfc3339(),
+                heartbeat_ttl.as_secs().max(1),
+            )
+            .await?;
+        redis_conn
+            .sadd::<&str, &str, ()>("chat_nodes", node_id)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn start_heartbeat_interval(
+        redis_pool: Arc<Pool>,
+        node_id: String,
+        heartbeat_ttl: Duration,
+        cmd_tx: mpsc::UnboundedSender<Command>,
+    ) {
+        // refresh well before the key expires so a single slow tick does not kill the node
+        let mut interval = interval(heartbeat_ttl / 3);
+
+        loop {
+            interval.tick().await;
+
+            match redis_pool.get().await {
+                Ok(mut redis_conn) => {
+                    // a redis outage or a long pause lets the heartbeat expire while the node lives
+                    // on, the reaper drops a node from the set once it purged its sessions
+                    let reaped = match redis_conn
+                        .sismember::<&str, &str, bool>("chat_nodes", &node_id)
+                        .await
+                    {
+                        Ok(registered) => !registered,
+                        Err(e) => {
+                            log::error!("Error checking registration of chat node {node_id}: {e}");
+                            false
+                        }
+                    };
+
+                    if let Err(e) =
+                        Self::send_heartbeat(&mut redis_conn, &node_id, heartbeat_ttl).await
+                    {
+                        log::error!("Error sending heartbeat of chat node {node_id}: {e}");
+                        continue;
+                    }
+
+                    if reaped {
+                        let _ = cmd_tx.send(Command::RestoreSessions);
+                    }
+                }
+                Err(e) => {
+                    log::error!("Redis connection error while sending heartbeat: {e}");
+                }
+            }
+        }
+    }
+
+    async fn start_reaper_interval(
+        redis_pool: Arc<Pool>,
+        conversation_repo: Arc<ConversationRepo>,
+        node_id: String,
+        heartbeat_ttl: Duration,
+        period: Duration,
+    ) {
+        let mut interval = interval(period);
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) =
+                Self::reap_dead_nodes(&redis_pool, &conversation_repo, &node_id, heartbeat_ttl)
+                    .await
+            {
+                log::error!("Error reaping dead chat nodes: {e}");
+            }
+        }
+    }
+
+    async fn reap_dead_nodes(
+        redis_pool: &Arc<Pool>,
+        conversation_repo: &Arc<ConversationRepo>,
+        node_id: &str,
+        heartbeat_ttl: Duration,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut redis_conn = redis_pool.get().await?;
+
+        let nodes: Vec<String> = redis_conn.smembers("chat_nodes").await?;
+
+        for dead_node_id in nodes {
+            if dead_node_id == node_id {
+                continue;
+            }
+
+            let alive: bool = redis_conn
+                .exists(&format!("node:{dead_node_id}:heartbeat"))
+                .await?;
+            if alive {
+                continue;
+            }
+
+            // every node runs the reaper, the first one to take the lock purges the dead node
+            let acquired: Option<String> = redis::cmd("SET")
+                .arg(&format!("node:{dead_node_id}:reaping"))
+                .arg(node_id)
+                .arg("NX")
+                .arg("EX")
+                .arg(heartbeat_ttl.as_secs().max(1))
+                .query_async(&mut redis_conn)
+                .await?;
+            if acquired.is_none() {
+                continue;
+            }
+
+            let purged = Self::purge_node(
+                redis_pool,
+                conversation_repo,
+                &mut redis_conn,
+                &dead_node_id,
+            )
+            .await?;
+            redis_conn
+                .srem::<&str, &str, ()>("chat_nodes", &dead_node_id)
+                .await?;
+
+            log::warn!("Chat node {dead_node_id} is dead, purged {purged} of its sessions");
+        }
+
+        Ok(())
+    }
+
+    // removes every session a node holds from redis and returns how many were purged
+    async fn purge_node(
+        redis_pool: &Arc<Pool>,
+        conversation_repo: &Arc<ConversationRepo>,
+        redis_conn: &mut deadpool_redis::Connection,
+        node_id: &str,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let node_sessions: Vec<String> = redis_conn
+            .smembers(&format!("node:{node_id}:sessions"))
+            .await?;
+
+        let mut purged = 0;
+        for node_session in &node_sessions {
+            let ids = node_session.split_once(':').and_then(|(user_id, conn_id)| {
+                Some((
+                    Uuid::parse_str(user_id).ok()?,
+                    Uuid::parse_str(conn_id).ok()?,
+                ))
+            });
+
+            let (user_id, conn_id) = match ids {
+                Some(ids) => ids,
+                None => {
+                    log::error!("Failed to parse session '{node_session}' of chat node {node_id}");
+                    continue;
+                }
+            };
+
+            if let Err(e) =
+                Self::purge_session(redis_pool, conversation_repo, redis_conn, user_id, conn_id)
+                    .await
+            {
+                log::error!("Failed to purge session {conn_id} of user {user_id} - error: {e}");
+                continue;
+            }
+            purged += 1;
+        }
+
+        redis_conn
+            .del::<&str, ()>(&format!("node:{node_id}:sessions"))
+            .await?;
+
+        Ok(purged)
+    }
+
+    // same redis cleanup as `disconnect`, for a session whose node is gone
+    async fn purge_session(
+        redis_pool: &Arc<Pool>,
+        conversation_repo: &Arc<ConversationRepo>,
+        redis_conn: &mut deadpool_redis::Connection,
+        user_id: UserId,
+        conn_id: ConnId,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let session_state: Option<String> = redis_conn
+            .hget(&format!("user:{user_id}:sessions"), &conn_id.to_string())
+            .await?;
+        let rooms = Self::parse_session_rooms(&session_state.unwrap_or_default());
+
+        redis_conn
+            .hdel::<&str, &str, ()>(&format!("user:{user_id}:sessions"), &conn_id.to_string())
+            .await?;
+        redis_conn
+            .hdel::<&str, &str, ()>(
+                &format!("user:{user_id}:session_states"),
+                &conn_id.to_string(),
+            )
+            .await?;
+
+        // the user stays in a room only while another of their sessions is in it
+        let user_sessions: HashMap<String, String> = redis_conn
+            .hgetall(&format!("user:{user_id}:sessions"))
+            .await?;
+
+        for conversation_id in rooms {
+            let is_remain = user_sessions
+                .values()
+                .any(|value| Self::session_has_room(value, &conversation_id));
+
+            if !is_remain {
+                redis_conn
+                    .srem::<&str, &str, ()>(
+                        &format!("room:{conversation_id}:active_users"),
+                        &user_id.to_string(),
+                    )
+                    .await?;
+            }
+        }
+
+        if user_sessions.is_empty() {
+            redis_conn
+                .srem::<&str, &str, ()>("online_users", &user_id.to_string())
+                .await?;
+        }
+
+        Self::update_presence(redis_pool, conversation_repo, redis_conn, user_id).await
+    }
+
     async fn process_updates(
         conversation_repo: &Arc<ConversationRepo>,
         redis_conn: &mut deadpool_redis::Connection,
@@ -1,47 +1,117 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use tokio::sync::{mpsc, oneshot};
 
-use crate::errors::chat_error::ChatError;
+use crate::{
+    errors::chat_error::ChatError,
+    models::{
+        conversation::ConversationSync,
+        message::SystemEvent,
+        presence::ActivityState,
+        reaction::ReactionAction,
+        ws::{InboxEvent, MessageAck, ReceiptState},
+    },
+};
 
-use super::{Command, ConnId, ConversationId, SendMsg, UserId};
+use super::{
+    conn_queue::{self, ConnReceiver, QueueConfig, QueueStats},
+    Command, ConnId, ConversationId, SendMsg, UserId,
+};
 
 #[derive(Debug, Clone)]
 pub struct ChatServerHandler {
     cmd_tx: mpsc::UnboundedSender<Command>,
+    queue_config: QueueConfig,
+    shutting_down: Arc<AtomicBool>,
 }
 
 impl ChatServerHandler {
-    pub fn new(cmd_tx: mpsc::UnboundedSender<Command>) -> Self {
-        Self { cmd_tx }
+    pub fn new(cmd_tx: mpsc::UnboundedSender<Command>, queue_config: QueueConfig) -> Self {
+        Self {
+            cmd_tx,
+            queue_config,
+            shutting_down: Arc::new(AtomicBool::new(false)),
+        }
     }
 
-    pub async fn connect(
-        &self,
-        user_id: UserId,
-        conn_tx: mpsc::UnboundedSender<SendMsg>,
-    ) -> Option<ConnId> {
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Acquire)
+    }
+
+    // drains the chat server, returns once sessions are told to reconnect and redis is cleaned up
+    pub async fn shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Release);
+
+        let (res_tx, res_rx) = oneshot::channel();
+
+        if self.cmd_tx.send(Command::Shutdown { res_tx }).is_err() {
+            return;
+        }
+
+        let _ = res_rx.await;
+    }
+
+    // registers a connection and returns the receiving end of its outgoing queue
+    pub async fn connect(&self, user_id: UserId) -> Option<(ConnId, ConnReceiver)> {
+        let (conn_tx, conn_rx) = conn_queue::channel(self.queue_config);
+
         // one shot channel to receive the result
         let (res_tx, res_rx) = oneshot::channel();
 
         // send connect command to 'ChatServer'
+        // a node that is shutting down refuses the connection
         self.cmd_tx
             .send(Command::Connect {
                 user_id,
                 conn_tx,
                 res_tx,
             })
-            .unwrap();
+            .ok()?;
+
+        res_rx.await.ok()?.map(|conn_id| (conn_id, conn_rx))
+    }
+
+    pub async fn queue_stats(&self) -> Vec<QueueStats> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        // no connections are left once the chat server has shut down
+        if self.cmd_tx.send(Command::Metrics { res_tx }).is_err() {
+            return Vec::new();
+        }
 
-        res_rx.await.unwrap()
+        res_rx.await.unwrap_or_default()
     }
 
     pub fn disconnect(&self, user_id: UserId, conn_id: ConnId) {
-        self.cmd_tx
-            .send(Command::Disconnect { user_id, conn_id })
-            .unwrap();
+        // after a shutdown the chat server is gone and has already cleaned up the session
+        let _ = self.cmd_tx.send(Command::Disconnect { user_id, conn_id });
 
         log::info!("connection id: {conn_id} - disconnected");
     }
 
+    pub async fn set_activity(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        state: ActivityState,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Activity {
+                user_id,
+                conn_id,
+                state,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
     pub async fn join_conversation(
         &self,
         user_id: UserId,
@@ -50,55 +120,237 @@ impl ChatServerHandler {
     ) -> Result<(), ChatError> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.cmd_tx
-            .send(Command::Join {
+        self.request(
+            Command::Join {
                 user_id,
                 conn_id,
                 conversation_id,
                 res_tx,
-            })
-            .unwrap();
-
-        res_rx.await.unwrap()
+            },
+            res_rx,
+        )
+        .await
     }
 
     pub async fn leave_converstaion(
         &self,
         user_id: UserId,
         conn_id: ConnId,
+        conversation_id: ConversationId,
     ) -> Result<(), ChatError> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.cmd_tx
-            .send(Command::Leave {
+        self.request(
+            Command::Leave {
                 user_id,
                 conn_id,
+                conversation_id,
                 res_tx,
-            })
-            .unwrap();
-
-        res_rx.await.unwrap()
+            },
+            res_rx,
+        )
+        .await
     }
 
     pub async fn send_message(
         &self,
         user_id: UserId,
         conn_id: ConnId,
+        conversation_id: ConversationId,
         msg: SendMsg,
         r#type: String,
-    ) -> Result<(), ChatError> {
+        client_message_id: Option<String>,
+    ) -> Result<MessageAck, ChatError> {
         let (res_tx, res_rx) = oneshot::channel();
 
-        self.cmd_tx
-            .send(Command::Message {
+        self.request(
+            Command::Message {
                 user_id,
                 conn_id,
+                conversation_id,
                 msg,
                 r#type,
+                client_message_id,
                 res_tx,
-            })
-            .unwrap();
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn send_typing(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        is_typing: bool,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Typing {
+                user_id,
+                conn_id,
+                conversation_id,
+                is_typing,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn send_receipt(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        message_id: i64,
+        state: ReceiptState,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Read {
+                user_id,
+                conn_id,
+                conversation_id,
+                message_id,
+                state,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn send_reaction(
+        &self,
+        user_id: UserId,
+        conn_id: ConnId,
+        message_id: i64,
+        emoji: String,
+        action: ReactionAction,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::React {
+                user_id,
+                conn_id,
+                message_id,
+                emoji,
+                action,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn sync_messages(
+        &self,
+        user_id: UserId,
+        conversation_id: ConversationId,
+        after_message_id: i64,
+        limit: Option<i32>,
+    ) -> Result<ConversationSync, ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Sync {
+                user_id,
+                conversation_id,
+                after_message_id,
+                limit,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn broadcast(
+        &self,
+        conversation_id: ConversationId,
+        msg: SendMsg,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Broadcast {
+                conversation_id,
+                msg,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn send_system_message(
+        &self,
+        conversation_id: ConversationId,
+        event: SystemEvent,
+    ) -> Result<i64, ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::System {
+                conversation_id,
+                event,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn notify_inbox(
+        &self,
+        user_ids: Vec<UserId>,
+        event: InboxEvent,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::Inbox {
+                user_ids,
+                event,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    pub async fn remove_participant(
+        &self,
+        user_id: UserId,
+        conversation_id: ConversationId,
+    ) -> Result<(), ChatError> {
+        let (res_tx, res_rx) = oneshot::channel();
+
+        self.request(
+            Command::RemoveParticipant {
+                user_id,
+                conversation_id,
+                res_tx,
+            },
+            res_rx,
+        )
+        .await
+    }
+
+    // sends a command to the chat server and waits for its reply, requests still in flight
+    // while the node shuts down get an error instead of taking the caller down
+    async fn request<T>(
+        &self,
+        cmd: Command,
+        res_rx: oneshot::Receiver<Result<T, ChatError>>,
+    ) -> Result<T, ChatError> {
+        self.cmd_tx.send(cmd).map_err(|_| ChatError::ShuttingDown)?;
 
-        res_rx.await.unwrap()
+        res_rx.await.map_err(|_| ChatError::ShuttingDown)?
     }
 }
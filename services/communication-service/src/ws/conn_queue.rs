@@ -0,0 +1,412 @@
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use super::{ConnId, SendMsg};
+
+// What to do when a connection's outgoing queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    // discard the oldest queued message to make room
+    DropOldest,
+    // close the connection, the client has to reconnect and sync
+    Close,
+    // replace queued ephemeral events (typing, receipts, counts, presence) with newer ones,
+    // close the connection when no such event is left to replace
+    Coalesce,
+}
+
+impl FromStr for SlowConsumerPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop_oldest" => Ok(Self::DropOldest),
+            "close" => Ok(Self::Close),
+            "coalesce" => Ok(Self::Coalesce),
+            _ => Err(format!("Invalid slow consumer policy: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueueConfig {
+    pub capacity: usize,
+    pub policy: SlowConsumerPolicy,
+}
+
+// Why a connection's queue stopped delivering
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueClosed {
+    // the client could not keep up
+    TooSlow,
+    // the node is shutting down, the client should reconnect elsewhere
+    Restarting,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PushError {
+    // the session is gone
+    Closed,
+    // the queue overflowed and the connection is being closed
+    TooSlow,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueStats {
+    pub conn_id: ConnId,
+    pub depth: usize,
+    pub capacity: usize,
+    pub dropped: u64,
+    pub coalesced: u64,
+    // the connection is being closed because the client could not keep up
+    pub too_slow: bool,
+}
+
+// Counters of every queue of this process, closed connections included
+#[derive(Debug, Serialize)]
+pub struct QueueTotals {
+    pub dropped: u64,
+    pub coalesced: u64,
+    pub slow_consumers: u64,
+}
+
+static DROPPED_TOTAL: AtomicU64 = AtomicU64::new(0);
+static COALESCED_TOTAL: AtomicU64 = AtomicU64::new(0);
+static SLOW_CONSUMERS_TOTAL: AtomicU64 = AtomicU64::new(0);
+
+pub fn totals() -> QueueTotals {
+    QueueTotals {
+        dropped: DROPPED_TOTAL.load(Ordering::Relaxed),
+        coalesced: COALESCED_TOTAL.load(Ordering::Relaxed),
+        slow_consumers: SLOW_CONSUMERS_TOTAL.load(Ordering::Relaxed),
+    }
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<SendMsg>>,
+    notify: Notify,
+    config: QueueConfig,
+    // set by the receiver when the session ends
+    closed: AtomicBool,
+    // set by the sender when the client could not keep up
+    too_slow: AtomicBool,
+    // set by the chat server when the node shuts down
+    restarting: AtomicBool,
+    dropped: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+// Bounded outgoing queue of a WebSocket connection
+pub fn channel(config: QueueConfig) -> (ConnSender, ConnReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::with_capacity(config.capacity)),
+        notify: Notify::new(),
+        config,
+        closed: AtomicBool::new(false),
+        too_slow: AtomicBool::new(false),
+        restarting: AtomicBool::new(false),
+        dropped: AtomicU64::new(0),
+        coalesced: AtomicU64::new(0),
+    });
+
+    (
+        ConnSender {
+            shared: shared.clone(),
+        },
+        ConnReceiver { shared },
+    )
+}
+
+#[derive(Clone)]
+pub struct ConnSender {
+    shared: Arc<Shared>,
+}
+
+impl ConnSender {
+    pub fn send(&self, msg: SendMsg) -> Result<(), PushError> {
+        let shared = &self.shared;
+
+        if shared.closed.load(Ordering::Acquire) || shared.restarting.load(Ordering::Acquire) {
+            return Err(PushError::Closed);
+        }
+        if shared.too_slow.load(Ordering::Acquire) {
+            return Err(PushError::TooSlow);
+        }
+
+        let mut queue = shared.queue.lock().unwrap();
+
+        if queue.len() >= shared.config.capacity {
+            match shared.config.policy {
+                SlowConsumerPolicy::DropOldest => {
+                    queue.pop_front();
+                    shared.dropped.fetch_add(1, Ordering::Relaxed);
+                    DROPPED_TOTAL.fetch_add(1, Ordering::Relaxed);
+                }
+                SlowConsumerPolicy::Close => {
+                    return Err(self.mark_too_slow());
+                }
+                SlowConsumerPolicy::Coalesce => {
+                    if !Self::coalesce(&mut queue, &msg) {
+                        drop(queue);
+                        return Err(self.mark_too_slow());
+                    }
+                    shared.coalesced.fetch_add(1, Ordering::Relaxed);
+                    COALESCED_TOTAL.fetch_add(1, Ordering::Relaxed);
+                    drop(queue);
+                    shared.notify.notify_one();
+                    return Ok(());
+                }
+            }
+        }
+
+        queue.push_back(msg);
+        drop(queue);
+        shared.notify.notify_one();
+
+        Ok(())
+    }
+
+    pub fn stats(&self, conn_id: ConnId) -> QueueStats {
+        QueueStats {
+            conn_id,
+            depth: self.shared.queue.lock().unwrap().len(),
+            capacity: self.shared.config.capacity,
+            dropped: self.shared.dropped.load(Ordering::Relaxed),
+            coalesced: self.shared.coalesced.load(Ordering::Relaxed),
+            too_slow: self.shared.too_slow.load(Ordering::Acquire),
+        }
+    }
+
+    // asks the connection to close, messages still queued are discarded
+    pub fn close_for_restart(&self) {
+        self.shared.restarting.store(true, Ordering::Release);
+        self.shared.notify.notify_one();
+    }
+
+    fn mark_too_slow(&self) -> PushError {
+        if !self.shared.too_slow.swap(true, Ordering::AcqRel) {
+            SLOW_CONSUMERS_TOTAL.fetch_add(1, Ordering::Relaxed);
+        }
+        self.shared.notify.notify_one();
+        PushError::TooSlow
+    }
+
+    // replaces a queued event superseded by `msg`, or failing that drops the oldest ephemeral one
+    fn coalesce(queue: &mut VecDeque<SendMsg>, msg: &str) -> bool {
+        let key = coalesce_key(msg);
+
+        if let Some(key) = &key {
+            if let Some(queued) = queue
+                .iter_mut()
+                .find(|queued| coalesce_key(queued).as_ref() == Some(key))
+            {
+                *queued = msg.to_string();
+                return true;
+            }
+        }
+
+        match queue
+            .iter()
+            .position(|queued| coalesce_key(queued).is_some())
+        {
+            Some(index) => {
+                queue.remove(index);
+                queue.push_back(msg.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct ConnReceiver {
+    shared: Arc<Shared>,
+}
+
+impl ConnReceiver {
+    // next queued message, or why the connection has to be closed
+    pub async fn recv(&mut self) -> Result<SendMsg, QueueClosed> {
+        loop {
+            let notified = self.shared.notify.notified();
+
+            if self.shared.restarting.load(Ordering::Acquire) {
+                return Err(QueueClosed::Restarting);
+            }
+            if self.shared.too_slow.load(Ordering::Acquire) {
+                return Err(QueueClosed::TooSlow);
+            }
+            if let Some(msg) = self.shared.queue.lock().unwrap().pop_front() {
+                return Ok(msg);
+            }
+
+            notified.await;
+        }
+    }
+}
+
+impl Drop for ConnReceiver {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.queue.lock().unwrap().clear();
+    }
+}
+
+// events that only carry the latest state, a newer one makes the queued one useless
+fn coalesce_key(msg: &str) -> Option<String> {
+    let value = serde_json::from_str::<serde_json::Value>(msg).ok()?;
+
+    match value["type"].as_str()? {
+        "typing" => Some(format!(
+            "typing:{}:{}",
+            value["conversation_id"], value["sender_id"]
+        )),
+        "read" => Some(format!(
+            "read:{}:{}:{}",
+            value["conversation_id"], value["user_id"], value["state"]
+        )),
+        "viewer_count" => Some(format!("viewer_count:{}", value["conversation_id"])),
+        "presence_changed" => Some(format!("presence_changed:{}", value["user_id"])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn queue(capacity: usize, policy: SlowConsumerPolicy) -> (ConnSender, ConnReceiver) {
+        channel(QueueConfig { capacity, policy })
+    }
+
+    fn typing(sender: &str, is_typing: bool) -> String {
+        serde_json::json!({
+            "type": "typing",
+            "conversation_id": 1,
+            "sender_id": sender,
+            "is_typing": is_typing,
+        })
+        .to_string()
+    }
+
+    fn message(text: &str) -> String {
+        serde_json::json!({ "type": "message", "conversation_id": 1, "message": text }).to_string()
+    }
+
+    async fn drain(conn_rx: &mut ConnReceiver, count: usize) -> Vec<SendMsg> {
+        let mut received = Vec::new();
+        for _ in 0..count {
+            received.push(conn_rx.recv().await.unwrap());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn drop_oldest_discards_the_front_of_a_full_queue() {
+        let (conn_tx, mut conn_rx) = queue(2, SlowConsumerPolicy::DropOldest);
+
+        for text in ["a", "b", "c"] {
+            conn_tx.send(message(text)).unwrap();
+        }
+
+        assert_eq!(
+            drain(&mut conn_rx, 2).await,
+            vec![message("b"), message("c")]
+        );
+        assert_eq!(conn_tx.stats(Uuid::nil()).dropped, 1);
+    }
+
+    #[tokio::test]
+    async fn close_policy_closes_a_full_queue() {
+        let (conn_tx, mut conn_rx) = queue(1, SlowConsumerPolicy::Close);
+
+        conn_tx.send(message("a")).unwrap();
+        assert_eq!(conn_tx.send(message("b")), Err(PushError::TooSlow));
+        assert_eq!(conn_tx.send(message("c")), Err(PushError::TooSlow));
+
+        assert_eq!(conn_rx.recv().await, Err(QueueClosed::TooSlow));
+        assert!(conn_tx.stats(Uuid::nil()).too_slow);
+    }
+
+    #[tokio::test]
+    async fn coalesce_replaces_the_queued_event_of_the_same_key_in_place() {
+        let (conn_tx, mut conn_rx) = queue(2, SlowConsumerPolicy::Coalesce);
+
+        conn_tx.send(typing("alice", true)).unwrap();
+        conn_tx.send(message("a")).unwrap();
+        conn_tx.send(typing("alice", false)).unwrap();
+
+        assert_eq!(
+            drain(&mut conn_rx, 2).await,
+            vec![typing("alice", false), message("a")]
+        );
+        assert_eq!(conn_tx.stats(Uuid::nil()).coalesced, 1);
+    }
+
+    #[tokio::test]
+    async fn coalesce_makes_room_by_dropping_the_oldest_ephemeral_event() {
+        let (conn_tx, mut conn_rx) = queue(2, SlowConsumerPolicy::Coalesce);
+
+        conn_tx.send(message("a")).unwrap();
+        conn_tx.send(typing("alice", true)).unwrap();
+        conn_tx.send(message("b")).unwrap();
+
+        assert_eq!(
+            drain(&mut conn_rx, 2).await,
+            vec![message("a"), message("b")]
+        );
+    }
+
+    #[tokio::test]
+    async fn coalesce_closes_when_no_ephemeral_event_is_queued() {
+        let (conn_tx, mut conn_rx) = queue(1, SlowConsumerPolicy::Coalesce);
+
+        conn_tx.send(message("a")).unwrap();
+        assert_eq!(conn_tx.send(message("b")), Err(PushError::TooSlow));
+        assert_eq!(conn_rx.recv().await, Err(QueueClosed::TooSlow));
+    }
+
+    #[test]
+    fn coalesce_keys_separate_senders_and_rooms() {
+        assert_eq!(
+            coalesce_key(&typing("alice", true)),
+            coalesce_key(&typing("alice", false))
+        );
+        assert_ne!(
+            coalesce_key(&typing("alice", true)),
+            coalesce_key(&typing("bob", true))
+        );
+        assert_eq!(coalesce_key(&message("a")), None);
+        assert_eq!(coalesce_key("not json"), None);
+    }
+
+    #[tokio::test]
+    async fn restart_closes_the_queue() {
+        let (conn_tx, mut conn_rx) = queue(4, SlowConsumerPolicy::Close);
+
+        conn_tx.send(message("a")).unwrap();
+        conn_tx.close_for_restart();
+
+        assert_eq!(conn_rx.recv().await, Err(QueueClosed::Restarting));
+        assert_eq!(conn_tx.send(message("b")), Err(PushError::Closed));
+    }
+
+    #[test]
+    fn sends_fail_once_the_receiver_is_gone() {
+        let (conn_tx, conn_rx) = queue(4, SlowConsumerPolicy::Close);
+        drop(conn_rx);
+
+        assert_eq!(conn_tx.send(message("a")), Err(PushError::Closed));
+    }
+}
@@ -1,10 +1,25 @@
 use tokio::sync::{mpsc, oneshot};
 use uuid::Uuid;
 
-use crate::errors::chat_error::ChatError;
+use crate::{
+    errors::chat_error::ChatError,
+    models::{
+        conversation::ConversationSync,
+        message::SystemEvent,
+        presence::ActivityState,
+        reaction::ReactionAction,
+        ws::{InboxEvent, MessageAck, ReceiptState},
+    },
+};
+
+use self::conn_queue::{ConnSender, QueueStats};
 
 pub mod chat_server;
 pub mod chat_server_handler;
+pub mod conn_queue;
+pub mod protocol;
+pub mod pubsub;
+pub mod room_index;
 pub mod ws_handler;
 
 pub type ConnId = Uuid;
@@ -15,15 +30,36 @@ pub type SendMsg = String;
 pub enum Command {
     Connect {
         user_id: UserId,
-        conn_tx: mpsc::UnboundedSender<String>,
+        conn_tx: ConnSender,
         res_tx: oneshot::Sender<Option<ConnId>>,
     },
 
+    // closes every session and cleans up the node, the chat server stops afterwards
+    Shutdown {
+        res_tx: oneshot::Sender<()>,
+    },
+
+    // outgoing queue state of every local connection
+    Metrics {
+        res_tx: oneshot::Sender<Vec<QueueStats>>,
+    },
+
+    // writes the local sessions back to redis after another node reaped this one
+    RestoreSessions,
+
     Disconnect {
         user_id: UserId,
         conn_id: ConnId,
     },
 
+    // client reported activity of a session, drives online and away
+    Activity {
+        user_id: UserId,
+        conn_id: ConnId,
+        state: ActivityState,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
     Join {
         user_id: UserId,
         conn_id: ConnId,
@@ -34,14 +70,79 @@ pub enum Command {
     Message {
         user_id: UserId,
         conn_id: ConnId,
+        conversation_id: ConversationId,
         msg: SendMsg,
         r#type: String,
-        res_tx: oneshot::Sender<Result<(), ChatError>>,
+        client_message_id: Option<String>,
+        res_tx: oneshot::Sender<Result<MessageAck, ChatError>>,
     },
 
     Leave {
         user_id: UserId,
         conn_id: ConnId,
+        conversation_id: ConversationId,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    Typing {
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        is_typing: bool,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    Read {
+        user_id: UserId,
+        conn_id: ConnId,
+        conversation_id: ConversationId,
+        message_id: i64,
+        state: ReceiptState,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    React {
+        user_id: UserId,
+        conn_id: ConnId,
+        message_id: i64,
+        emoji: String,
+        action: ReactionAction,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    Sync {
+        user_id: UserId,
+        conversation_id: ConversationId,
+        after_message_id: i64,
+        limit: Option<i32>,
+        res_tx: oneshot::Sender<Result<ConversationSync, ChatError>>,
+    },
+
+    // publish a server generated event to every session in the conversation
+    Broadcast {
+        conversation_id: ConversationId,
+        msg: SendMsg,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    // store and publish a server generated message in the conversation
+    System {
+        conversation_id: ConversationId,
+        event: SystemEvent,
+        res_tx: oneshot::Sender<Result<i64, ChatError>>,
+    },
+
+    // publish a conversation list event to the inbox of every given user
+    Inbox {
+        user_ids: Vec<UserId>,
+        event: InboxEvent,
+        res_tx: oneshot::Sender<Result<(), ChatError>>,
+    },
+
+    // take every session of a user removed from the group out of its room, on all nodes
+    RemoveParticipant {
+        user_id: UserId,
+        conversation_id: ConversationId,
         res_tx: oneshot::Sender<Result<(), ChatError>>,
     },
 }
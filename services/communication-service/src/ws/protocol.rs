@@ -0,0 +1,257 @@
+use std::{fmt, str::FromStr};
+
+use derive_more::Display;
+use serde::{de::DeserializeOwned, Serialize};
+
+const PROTOCOL_PREFIX: &str = "farmera";
+
+// protocol versions this server speaks, events may change shape between versions
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const MAX_PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+pub enum WireFormat {
+    #[display("json")]
+    Json,
+
+    #[display("msgpack")]
+    MsgPack,
+}
+
+// encoded frame ready to be written to the socket
+pub enum Frame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+// WebSocket subprotocol negotiated through `Sec-WebSocket-Protocol`, e.g. `farmera.v1.msgpack`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WsProtocol {
+    pub version: u32,
+    pub format: WireFormat,
+}
+
+impl Default for WsProtocol {
+    // clients that do not offer a subprotocol keep talking JSON
+    fn default() -> Self {
+        Self {
+            version: MIN_PROTOCOL_VERSION,
+            format: WireFormat::Json,
+        }
+    }
+}
+
+impl fmt::Display for WsProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PROTOCOL_PREFIX}.v{}.{}", self.version, self.format)
+    }
+}
+
+impl FromStr for WsProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid WebSocket protocol: {s}");
+
+        let mut parts = s.trim().split('.');
+        if parts.next() != Some(PROTOCOL_PREFIX) {
+            return Err(invalid());
+        }
+
+        let version = parts
+            .next()
+            .and_then(|version| version.strip_prefix('v'))
+            .and_then(|version| version.parse::<u32>().ok())
+            .ok_or_else(invalid)?;
+
+        let format = match parts.next() {
+            Some("json") => WireFormat::Json,
+            Some("msgpack") => WireFormat::MsgPack,
+            _ => return Err(invalid()),
+        };
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self { version, format })
+    }
+}
+
+impl WsProtocol {
+    // picks the first offered subprotocol this server supports, in the client's order of preference
+    pub fn negotiate(offered: &str) -> Option<Self> {
+        offered
+            .split(',')
+            .filter_map(|protocol| protocol.parse::<WsProtocol>().ok())
+            .find(|protocol| {
+                (MIN_PROTOCOL_VERSION..=MAX_PROTOCOL_VERSION).contains(&protocol.version)
+            })
+    }
+
+    pub fn supported() -> Vec<String> {
+        (MIN_PROTOCOL_VERSION..=MAX_PROTOCOL_VERSION)
+            .flat_map(|version| {
+                [WireFormat::Json, WireFormat::MsgPack]
+                    .map(|format| WsProtocol { version, format }.to_string())
+            })
+            .collect()
+    }
+
+    // text frames are always JSON, so a binary connection can still be debugged by hand
+    pub fn decode_text<T: DeserializeOwned>(&self, text: &str) -> Result<T, String> {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    }
+
+    pub fn decode_binary<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        match self.format {
+            WireFormat::MsgPack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+            WireFormat::Json => Err(format!(
+                "Binary frames require the {} protocol",
+                WsProtocol {
+                    version: self.version,
+                    format: WireFormat::MsgPack,
+                }
+            )),
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Frame, String> {
+        match self.format {
+            WireFormat::Json => serde_json::to_string(value)
+                .map(Frame::Text)
+                .map_err(|e| e.to_string()),
+            // field names are kept so both encodings carry the same document
+            WireFormat::MsgPack => rmp_serde::to_vec_named(value)
+                .map(Frame::Binary)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    // broadcast payloads travel through redis as JSON and are re-encoded per connection
+    pub fn transcode(&self, json: &str) -> Result<Frame, String> {
+        match self.format {
+            WireFormat::Json => Ok(Frame::Text(json.to_string())),
+            WireFormat::MsgPack => {
+                let value: serde_json::Value =
+                    serde_json::from_str(json).map_err(|e| e.to_string())?;
+                self.encode(&value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        r#type: String,
+        conversation_id: i32,
+        content: Option<String>,
+    }
+
+    fn event() -> Event {
+        Event {
+            r#type: "message".to_string(),
+            conversation_id: 7,
+            content: Some("Fresh tomatoes today".to_string()),
+        }
+    }
+
+    fn protocol(format: WireFormat) -> WsProtocol {
+        WsProtocol {
+            version: MIN_PROTOCOL_VERSION,
+            format,
+        }
+    }
+
+    #[test]
+    fn protocol_names_round_trip() {
+        for name in WsProtocol::supported() {
+            assert_eq!(name.parse::<WsProtocol>().unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn invalid_protocol_names_are_rejected() {
+        for name in [
+            "",
+            "farmera",
+            "farmera.v1",
+            "farmera.1.json",
+            "farmera.v1.xml",
+            "farmera.v1.json.extra",
+            "other.v1.json",
+        ] {
+            assert!(name.parse::<WsProtocol>().is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn negotiate_picks_the_first_supported_offer() {
+        let offered = format!(
+            "farmera.v{}.json, farmera.v1.msgpack, farmera.v1.json",
+            MAX_PROTOCOL_VERSION + 1
+        );
+        assert_eq!(
+            WsProtocol::negotiate(&offered),
+            Some(protocol(WireFormat::MsgPack))
+        );
+    }
+
+    #[test]
+    fn negotiate_selects_nothing_when_no_offer_is_supported() {
+        assert_eq!(WsProtocol::negotiate("chat, superchat"), None);
+        assert_eq!(WsProtocol::negotiate(""), None);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let json = protocol(WireFormat::Json);
+
+        let text = match json.encode(&event()).unwrap() {
+            Frame::Text(text) => text,
+            Frame::Binary(_) => panic!("JSON is sent as text frames"),
+        };
+        assert_eq!(json.decode_text::<Event>(&text).unwrap(), event());
+        assert!(json.decode_binary::<Event>(text.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn msgpack_round_trip() {
+        let msgpack = protocol(WireFormat::MsgPack);
+
+        let bytes = match msgpack.encode(&event()).unwrap() {
+            Frame::Binary(bytes) => bytes,
+            Frame::Text(_) => panic!("MessagePack is sent as binary frames"),
+        };
+        assert_eq!(msgpack.decode_binary::<Event>(&bytes).unwrap(), event());
+
+        // text frames stay JSON on a MessagePack connection
+        let text = serde_json::to_string(&event()).unwrap();
+        assert_eq!(msgpack.decode_text::<Event>(&text).unwrap(), event());
+    }
+
+    #[test]
+    fn transcode_re_encodes_broadcast_json() {
+        let json = serde_json::to_string(&event()).unwrap();
+
+        match protocol(WireFormat::Json).transcode(&json).unwrap() {
+            Frame::Text(text) => assert_eq!(text, json),
+            Frame::Binary(_) => panic!("JSON is sent as text frames"),
+        }
+
+        let msgpack = protocol(WireFormat::MsgPack);
+        match msgpack.transcode(&json).unwrap() {
+            Frame::Binary(bytes) => {
+                assert_eq!(msgpack.decode_binary::<Event>(&bytes).unwrap(), event())
+            }
+            Frame::Text(_) => panic!("MessagePack is sent as binary frames"),
+        }
+        assert!(msgpack.transcode("not json").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use redis::aio::PubSubSink;
+use tokio::{
+    sync::{mpsc, Mutex, RwLock},
+    time::sleep,
+};
+
+// (channel, payload) of a received pub/sub message
+pub type PubSubMessage = (String, String);
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// One redis pub/sub connection multiplexing many channels
+struct PubSubShard {
+    // channels that should be subscribed, replayed after every reconnect
+    channels: RwLock<HashSet<String>>,
+    // subscribe/unsubscribe half of the live connection, none while reconnecting
+    sink: Mutex<Option<PubSubSink>>,
+}
+
+// Subscriptions of a chat node, spread over a fixed number of shared pub/sub connections.
+// Channels are added and removed on the running connections, and each connection
+// reconnects and resubscribes on its own when redis drops it.
+#[derive(Clone)]
+pub struct PubSubManager {
+    redis_client: Arc<redis::Client>,
+    shards: Arc<Vec<PubSubShard>>,
+}
+
+impl PubSubManager {
+    pub fn new(redis_client: Arc<redis::Client>, connections: usize) -> Self {
+        let shards = (0..connections.max(1))
+            .map(|_| PubSubShard {
+                channels: RwLock::new(HashSet::new()),
+                sink: Mutex::new(None),
+            })
+            .collect();
+
+        Self {
+            redis_client,
+            shards: Arc::new(shards),
+        }
+    }
+
+    // spawns one task per connection, received messages are forwarded to `msg_tx`
+    pub fn start(&self, msg_tx: mpsc::UnboundedSender<PubSubMessage>) {
+        for shard_id in 0..self.shards.len() {
+            let manager = self.clone();
+            let msg_tx = msg_tx.clone();
+
+            tokio::spawn(async move {
+                manager.run_shard(shard_id, msg_tx).await;
+            });
+        }
+    }
+
+    pub async fn subscribe(&self, channel: String) -> Result<(), Box<dyn std::error::Error>> {
+        let shard = self.shard(&channel);
+
+        if !shard.channels.write().await.insert(channel.clone()) {
+            log::info!("Already subscribed to channel: {}", channel);
+            return Ok(());
+        }
+
+        // without a live connection the channel is picked up by the next resubscribe
+        if let Some(sink) = shard.sink.lock().await.as_mut() {
+            if let Err(e) = sink.subscribe(&channel).await {
+                // forget the channel so the next caller tries again
+                shard.channels.write().await.remove(&channel);
+                return Err(e.into());
+            }
+        }
+
+        log::info!("Subscribed to channel: {}", channel);
+
+        Ok(())
+    }
+
+    pub async fn unsubscribe(&self, channel: String) {
+        let shard = self.shard(&channel);
+
+        if !shard.channels.write().await.remove(&channel) {
+            log::info!("Already unsubscribed from channel: {}", channel);
+            return;
+        }
+
+        if let Some(sink) = shard.sink.lock().await.as_mut() {
+            if let Err(e) = sink.unsubscribe(&channel).await {
+                log::error!("Failed to unsubscribe from channel {channel}: {e}");
+                return;
+            }
+        }
+
+        log::info!("Unsubscribe from channel: {channel}");
+    }
+
+    fn shard(&self, channel: &str) -> &PubSubShard {
+        let mut hasher = DefaultHasher::new();
+        channel.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+
+    async fn run_shard(&self, shard_id: usize, msg_tx: mpsc::UnboundedSender<PubSubMessage>) {
+        let shard = &self.shards[shard_id];
+        let mut reconnect_delay = MIN_RECONNECT_DELAY;
+
+        loop {
+            let pubsub = match self.redis_client.get_async_pubsub().await {
+                Ok(pubsub) => pubsub,
+                Err(e) => {
+                    log::error!(
+                        "PubSub connection {shard_id} failed, retry in {reconnect_delay:?}: {e}"
+                    );
+                    sleep(reconnect_delay).await;
+                    reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                    continue;
+                }
+            };
+
+            let (mut sink, mut msg_stream) = pubsub.split();
+
+            // publish the sink before replaying, a channel added in between is subscribed by its caller
+            *shard.sink.lock().await = Some(sink.clone());
+
+            let channels: Vec<String> = shard.channels.read().await.iter().cloned().collect();
+            let mut resubscribed = true;
+            for channel in &channels {
+                if let Err(e) = sink.subscribe(channel).await {
+                    log::error!("Failed to resubscribe to channel {channel}: {e}");
+                    resubscribed = false;
+                    break;
+                }
+            }
+
+            if resubscribed {
+                log::info!(
+                    "PubSub connection {shard_id} ready with {} channels",
+                    channels.len()
+                );
+                reconnect_delay = MIN_RECONNECT_DELAY;
+
+                while let Some(msg) = msg_stream.next().await {
+                    match msg.get_payload::<String>() {
+                        Ok(payload) => {
+                            if msg_tx
+                                .send((msg.get_channel_name().to_string(), payload))
+                                .is_err()
+                            {
+                                log::warn!("PubSub receiver closed");
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            log::error!(
+                                "Failed to decode message on channel {}: {e}",
+                                msg.get_channel_name()
+                            );
+                        }
+                    }
+                }
+            }
+
+            // stream closed, the connection is gone
+            *shard.sink.lock().await = None;
+            log::warn!("PubSub connection {shard_id} closed, reconnect in {reconnect_delay:?}");
+            sleep(reconnect_delay).await;
+            reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+}
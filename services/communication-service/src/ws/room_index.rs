@@ -0,0 +1,237 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+// In-process index of the rooms joined by the connections of this node.
+// Redis stays the source of truth across nodes, this only answers "who here is in the room"
+// so fan-out of a pub/sub message does not need a round-trip per participant.
+pub struct RoomIndex<R, C, U> {
+    // room -> local connections in it, with the user owning each connection
+    rooms: HashMap<R, HashMap<C, U>>,
+    // user -> local connections of the user
+    users: HashMap<U, HashSet<C>>,
+}
+
+impl<R, C, U> Default for RoomIndex<R, C, U> {
+    fn default() -> Self {
+        Self {
+            rooms: HashMap::new(),
+            users: HashMap::new(),
+        }
+    }
+}
+
+impl<R, C, U> RoomIndex<R, C, U>
+where
+    R: Hash + Eq + Copy,
+    C: Hash + Eq + Copy,
+    U: Hash + Eq + Copy,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect(&mut self, user_id: U, conn_id: C) {
+        self.users.entry(user_id).or_default().insert(conn_id);
+    }
+
+    // drops the connection from every room, returns the rooms left without local connections
+    pub fn disconnect(&mut self, user_id: U, conn_id: C) -> Vec<R> {
+        if let Some(conns) = self.users.get_mut(&user_id) {
+            conns.remove(&conn_id);
+            if conns.is_empty() {
+                self.users.remove(&user_id);
+            }
+        }
+
+        let mut emptied = Vec::new();
+        self.rooms.retain(|room_id, conns| {
+            if conns.remove(&conn_id).is_some() && conns.is_empty() {
+                emptied.push(*room_id);
+                return false;
+            }
+            true
+        });
+
+        emptied
+    }
+
+    pub fn join(&mut self, room_id: R, conn_id: C, user_id: U) {
+        self.rooms
+            .entry(room_id)
+            .or_default()
+            .insert(conn_id, user_id);
+    }
+
+    // returns true when no local connection is left in the room
+    pub fn leave(&mut self, room_id: R, conn_id: C) -> bool {
+        match self.rooms.get_mut(&room_id) {
+            Some(conns) => {
+                conns.remove(&conn_id);
+                if conns.is_empty() {
+                    self.rooms.remove(&room_id);
+                    true
+                } else {
+                    false
+                }
+            }
+            None => true,
+        }
+    }
+
+    // local connections in the room, except the ones owned by `skip_user`
+    pub fn room_connections(&self, room_id: R, skip_user: Option<U>) -> Vec<C> {
+        match self.rooms.get(&room_id) {
+            Some(conns) => conns
+                .iter()
+                .filter(|(_, user_id)| Some(**user_id) != skip_user)
+                .map(|(conn_id, _)| *conn_id)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn user_connections(&self, user_id: U) -> Vec<C> {
+        match self.users.get(&user_id) {
+            Some(conns) => conns.iter().copied().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn has_user(&self, user_id: U) -> bool {
+        self.users.contains_key(&user_id)
+    }
+
+    // every local connection with its user and the rooms it joined
+    pub fn sessions(&self) -> Vec<(U, C, Vec<R>)> {
+        let mut conn_rooms: HashMap<C, Vec<R>> = HashMap::new();
+        for (room_id, conns) in &self.rooms {
+            for conn_id in conns.keys() {
+                conn_rooms.entry(*conn_id).or_default().push(*room_id);
+            }
+        }
+
+        let mut sessions = Vec::new();
+        for (user_id, conns) in &self.users {
+            for conn_id in conns {
+                let rooms = conn_rooms.remove(conn_id).unwrap_or_default();
+                sessions.push((*user_id, *conn_id, rooms));
+            }
+        }
+        sessions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut conns: Vec<u32>) -> Vec<u32> {
+        conns.sort();
+        conns
+    }
+
+    #[test]
+    fn join_adds_connections_to_the_room() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.connect('a', 1);
+        index.connect('a', 2);
+        index.connect('b', 3);
+
+        index.join(10, 1, 'a');
+        index.join(10, 3, 'b');
+        index.join(20, 2, 'a');
+
+        assert_eq!(sorted(index.room_connections(10, None)), vec![1, 3]);
+        assert_eq!(index.room_connections(20, None), vec![2]);
+        assert!(index.room_connections(30, None).is_empty());
+    }
+
+    #[test]
+    fn room_connections_skips_the_given_user() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.join(10, 1, 'a');
+        index.join(10, 2, 'a');
+        index.join(10, 3, 'b');
+
+        assert_eq!(index.room_connections(10, Some('a')), vec![3]);
+        assert_eq!(sorted(index.room_connections(10, Some('c'))), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn leave_reports_when_the_room_is_empty() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.join(10, 1, 'a');
+        index.join(10, 2, 'b');
+
+        assert!(!index.leave(10, 1));
+        assert_eq!(index.room_connections(10, None), vec![2]);
+
+        assert!(index.leave(10, 2));
+        assert!(index.room_connections(10, None).is_empty());
+
+        // leaving a room that is not indexed leaves nothing to listen to
+        assert!(index.leave(20, 1));
+    }
+
+    #[test]
+    fn leave_of_an_absent_connection_keeps_the_room() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.join(10, 1, 'a');
+
+        assert!(!index.leave(10, 2));
+        assert_eq!(index.room_connections(10, None), vec![1]);
+    }
+
+    #[test]
+    fn disconnect_leaves_every_room_and_returns_the_emptied_ones() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.connect('a', 1);
+        index.connect('b', 2);
+        index.join(10, 1, 'a');
+        index.join(20, 1, 'a');
+        index.join(20, 2, 'b');
+
+        assert_eq!(index.disconnect('a', 1), vec![10]);
+        assert!(index.room_connections(10, None).is_empty());
+        assert_eq!(index.room_connections(20, None), vec![2]);
+        assert!(!index.has_user('a'));
+        assert!(index.has_user('b'));
+    }
+
+    #[test]
+    fn user_connections_follow_connect_and_disconnect() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.connect('a', 1);
+        index.connect('a', 2);
+
+        assert_eq!(sorted(index.user_connections('a')), vec![1, 2]);
+
+        index.disconnect('a', 1);
+        assert_eq!(index.user_connections('a'), vec![2]);
+        assert!(index.user_connections('b').is_empty());
+    }
+
+    #[test]
+    fn sessions_lists_every_connection_with_its_rooms() {
+        let mut index = RoomIndex::<i32, u32, char>::new();
+        index.connect('a', 1);
+        index.connect('a', 2);
+        index.connect('b', 3);
+
+        index.join(10, 1, 'a');
+        index.join(20, 1, 'a');
+        index.join(10, 3, 'b');
+
+        let mut sessions = index.sessions();
+        sessions.sort();
+        for (_, _, rooms) in &mut sessions {
+            rooms.sort();
+        }
+        assert_eq!(
+            sessions,
+            vec![('a', 1, vec![10, 20]), ('a', 2, vec![]), ('b', 3, vec![10])]
+        );
+    }
+}
@@ -1,24 +1,35 @@
 use std::{pin::pin, time::Duration};
 
 use actix_ws::{AggregatedMessage, CloseCode, CloseReason};
+use chrono::Utc;
 use futures_util::{
     future::{select, Either},
     StreamExt,
 };
-use tokio::{
-    sync::mpsc,
-    time::{interval, Instant},
-};
+use tokio::time::{interval, Instant};
 
 use crate::{
-    models::ws::{Event, WSRequest, WSResponse},
-    ws::{chat_server_handler::ChatServerHandler, ConnId, UserId},
+    models::{
+        presence::ActivityState,
+        reaction::ReactionAction,
+        ws::{Event, ReceiptState, WSRequest, WSResponse},
+    },
+    ws::{
+        chat_server_handler::ChatServerHandler,
+        conn_queue::QueueClosed,
+        protocol::{Frame, WsProtocol},
+        ConnId, UserId,
+    },
 };
 
 const HEARTBEAT: Duration = Duration::from_secs(5);
 
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+const MAX_CLIENT_MESSAGE_ID_LENGTH: usize = 64;
+
+const DEFAULT_SYNC_LIMIT: i32 = 100;
+
 pub struct WSHandler;
 
 impl WSHandler {
@@ -27,31 +38,33 @@ impl WSHandler {
         mut session: actix_ws::Session,
         msg_stream: actix_ws::MessageStream,
         user_id: UserId,
+        protocol: WsProtocol,
+        token_expires_at: i64,
     ) {
         log::debug!("Connected");
 
         let mut last_heartbeat = Instant::now();
         let mut interval = interval(HEARTBEAT);
 
-        // sender & receiver channel for connection
-        let (conn_tx, mut conn_rx) = mpsc::unbounded_channel();
-
-        // attempt to connect
-        let conn_id = match chat_server_handler.connect(user_id, conn_tx).await {
-            Some(id) => {
+        // attempt to connect, messages for the connection arrive on its bounded queue
+        let (conn_id, mut conn_rx) = match chat_server_handler.connect(user_id).await {
+            Some((id, conn_rx)) => {
                 // send a successful connection response
-                let _ = session
-                    .text(
-                        serde_json::json!(WSResponse {
-                            id: "".to_string(),
-                            event: Event::Connect,
-                            data: serde_json::json!({"connection_id": id}),
-                            status: "connected".to_string(),
-                        })
-                        .to_string(),
-                    )
-                    .await;
-                id
+                Self::send_response(
+                    &mut session,
+                    &protocol,
+                    &WSResponse {
+                        id: "".to_string(),
+                        event: Event::Connect,
+                        data: serde_json::json!({
+                            "connection_id": id,
+                            "protocol": protocol.to_string(),
+                        }),
+                        status: "connected".to_string(),
+                    },
+                )
+                .await;
+                (id, conn_rx)
             }
             None => {
                 let _ = session
@@ -84,18 +97,27 @@ impl WSHandler {
                 Either::Left((Either::Left((Some(Ok(msg)), _)), _)) => match msg {
                     AggregatedMessage::Text(text) => {
                         // log::info!("Text msg received: {text}");
-                        Self::process_text_message(
+                        Self::process_request(
                             &chat_server_handler,
                             &mut session,
-                            &text,
+                            &protocol,
+                            protocol.decode_text(&text),
                             user_id,
                             conn_id,
                         )
                         .await;
                     }
 
-                    AggregatedMessage::Binary(_bin) => {
-                        log::warn!("Unexpected binanry message");
+                    AggregatedMessage::Binary(bin) => {
+                        Self::process_request(
+                            &chat_server_handler,
+                            &mut session,
+                            &protocol,
+                            protocol.decode_binary(&bin),
+                            user_id,
+                            conn_id,
+                        )
+                        .await;
                     }
 
                     AggregatedMessage::Ping(bytes) => {
@@ -125,15 +147,27 @@ impl WSHandler {
                 Either::Left((Either::Left((None, _)), _)) => break None,
 
                 // chat messages received from other room participants
-                Either::Left((Either::Right((Some(msg), _)), _)) => {
+                Either::Left((Either::Right((Ok(msg), _)), _)) => {
                     log::info!("Msg recevied from other particitpants");
-                    Self::process_receive_message(&mut session, &msg).await;
+                    Self::process_receive_message(&mut session, &protocol, &msg).await;
                 }
 
-                // all connection's message senders were dropped
-                Either::Left((Either::Right((None, _)), _)) => unreachable!(
-                    "all connection message senders were dropped; chat server may have panicked"
-                ),
+                // the client could not keep up with its queue
+                Either::Left((Either::Right((Err(QueueClosed::TooSlow), _)), _)) => {
+                    log::warn!("Client {user_id} is too slow, disconnecting");
+                    break Some(CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("Client too slow".into()),
+                    });
+                }
+
+                // the node is shutting down, clients reconnect to another one
+                Either::Left((Either::Right((Err(QueueClosed::Restarting), _)), _)) => {
+                    break Some(CloseReason {
+                        code: CloseCode::Restart,
+                        description: Some("Server restarting, reconnect".into()),
+                    });
+                }
 
                 // heartbeat interval tick
                 Either::Right((_, _)) => {
@@ -143,6 +177,14 @@ impl WSHandler {
                         log::info!("Client {user_id} has not sent heartbeat over {CLIENT_TIMEOUT:?}, disconnecting");
                         break None;
                     }
+                    // the socket outlives the access token it was opened with, the client reconnects with a fresh one
+                    if Utc::now().timestamp() >= token_expires_at {
+                        log::info!("Access token of client {user_id} expired, disconnecting");
+                        break Some(CloseReason {
+                            code: CloseCode::Policy,
+                            description: Some("Access token expired".into()),
+                        });
+                    }
                     let _ = session.ping(b"").await;
                 }
             }
@@ -153,10 +195,11 @@ impl WSHandler {
         let _ = session.close(close_reason).await;
     }
 
-    async fn process_text_message(
+    async fn process_request(
         chat_server_handler: &ChatServerHandler,
         session: &mut actix_ws::Session,
-        text: &str,
+        protocol: &WsProtocol,
+        request: Result<WSRequest, String>,
         user_id: UserId,
         conn_id: ConnId,
     ) {
@@ -167,9 +210,7 @@ impl WSHandler {
             data: serde_json::json!(""),
         };
 
-        // log::info!("{}", text);
-
-        match serde_json::from_str::<WSRequest>(text) {
+        match request {
             Ok(request) => {
                 response.id = request.id;
 
@@ -203,9 +244,23 @@ impl WSHandler {
                         if let Ok(data_value) =
                             serde_json::from_value::<serde_json::Value>(request.data)
                         {
-                            if let (Some(r#type), Some(content)) =
-                                (data_value["type"].as_str(), data_value.get("content"))
+                            // picked by the client for a message and kept across its retries, the
+                            // request id restarts with every connection and cannot dedupe them
+                            let client_message_id = data_value["client_message_id"]
+                                .as_str()
+                                .filter(|id| !id.is_empty())
+                                .map(|id| id.to_string());
+
+                            if client_message_id
+                                .as_ref()
+                                .is_some_and(|id| id.len() > MAX_CLIENT_MESSAGE_ID_LENGTH)
                             {
+                                response.data = serde_json::json!({"message": format!("Client message id must be at most {MAX_CLIENT_MESSAGE_ID_LENGTH} characters")});
+                            } else if let (Some(conversation_id), Some(r#type), Some(content)) = (
+                                data_value["conversation_id"].as_i64(),
+                                data_value["type"].as_str(),
+                                data_value.get("content"),
+                            ) {
                                 let content = match serde_json::to_string(content) {
                                     Ok(result) => result,
                                     Err(e) => {
@@ -218,13 +273,16 @@ impl WSHandler {
                                         .send_message(
                                             user_id,
                                             conn_id,
+                                            conversation_id as i32,
                                             content.to_owned(),
                                             r#type.to_owned(),
+                                            client_message_id,
                                         )
                                         .await
                                     {
-                                        Ok(_) => {
+                                        Ok(ack) => {
                                             response.status = "sent".to_string();
+                                            response.data = serde_json::json!(ack);
                                         }
                                         Err(e) => {
                                             response.data =
@@ -235,21 +293,188 @@ impl WSHandler {
                                     response.data = serde_json::json!({"message": "Wrong type or empty content".to_string()})
                                 }
                             } else {
-                                response.data = serde_json::json!({"message": "Data must include type and content"});
+                                response.data = serde_json::json!({"message": "Data must include conversation_id, type and content"});
                             }
                         }
                     }
 
                     Event::Leave => {
                         response.event = Event::Leave;
-                        match chat_server_handler
-                            .leave_converstaion(user_id, conn_id)
-                            .await
-                        {
-                            Ok(_) => {
-                                response.status = "left".to_string();
+
+                        if let Some(conversation_id) = request.data["conversation_id"].as_i64() {
+                            match chat_server_handler
+                                .leave_converstaion(user_id, conn_id, conversation_id as i32)
+                                .await
+                            {
+                                Ok(_) => {
+                                    response.status = "left".to_string();
+                                }
+                                Err(e) => {
+                                    response.data = serde_json::json!({"message": e.to_string()})
+                                }
+                            }
+                        } else {
+                            response.data =
+                                serde_json::json!({"message": "Data must include conversation_id"});
+                        }
+                    }
+
+                    Event::Typing => {
+                        response.event = Event::Typing;
+
+                        if let (Some(conversation_id), Some(is_typing)) = (
+                            request.data["conversation_id"].as_i64(),
+                            request.data["is_typing"].as_bool(),
+                        ) {
+                            match chat_server_handler
+                                .send_typing(user_id, conn_id, conversation_id as i32, is_typing)
+                                .await
+                            {
+                                Ok(_) => {
+                                    response.status = "sent".to_string();
+                                }
+                                Err(e) => {
+                                    response.data = serde_json::json!({"message": e.to_string()})
+                                }
+                            }
+                        } else {
+                            response.data = serde_json::json!({"message": "Data must include conversation_id and is_typing"});
+                        }
+                    }
+
+                    Event::Read => {
+                        response.event = Event::Read;
+
+                        // "state" defaults to read, clients may also acknowledge delivery only
+                        let state = match request.data.get("state") {
+                            Some(state) => {
+                                serde_json::from_value::<ReceiptState>(state.clone()).ok()
+                            }
+                            None => Some(ReceiptState::Read),
+                        };
+
+                        if let (Some(conversation_id), Some(message_id), Some(state)) = (
+                            request.data["conversation_id"].as_i64(),
+                            request.data["message_id"].as_i64(),
+                            state,
+                        ) {
+                            match chat_server_handler
+                                .send_receipt(
+                                    user_id,
+                                    conn_id,
+                                    conversation_id as i32,
+                                    message_id,
+                                    state,
+                                )
+                                .await
+                            {
+                                Ok(_) => {
+                                    response.status = state.to_string();
+                                }
+                                Err(e) => {
+                                    response.data = serde_json::json!({"message": e.to_string()})
+                                }
+                            }
+                        } else {
+                            response.data = serde_json::json!({"message": "Data must include conversation_id, message_id and a valid state"});
+                        }
+                    }
+
+                    Event::Reaction => {
+                        response.event = Event::Reaction;
+
+                        // "action" defaults to add
+                        let action = match request.data.get("action") {
+                            Some(action) => {
+                                serde_json::from_value::<ReactionAction>(action.clone()).ok()
+                            }
+                            None => Some(ReactionAction::Add),
+                        };
+
+                        if let (Some(message_id), Some(emoji), Some(action)) = (
+                            request.data["message_id"].as_i64(),
+                            request.data["emoji"].as_str(),
+                            action,
+                        ) {
+                            match chat_server_handler
+                                .send_reaction(
+                                    user_id,
+                                    conn_id,
+                                    message_id,
+                                    emoji.to_owned(),
+                                    action,
+                                )
+                                .await
+                            {
+                                Ok(_) => {
+                                    response.status = "sent".to_string();
+                                }
+                                Err(e) => {
+                                    response.data = serde_json::json!({"message": e.to_string()})
+                                }
+                            }
+                        } else {
+                            response.data = serde_json::json!({"message": "Data must include message_id, emoji and a valid action"});
+                        }
+                    }
+
+                    Event::Sync => {
+                        response.event = Event::Sync;
+
+                        if let (Some(conversation_id), Some(after_message_id)) = (
+                            request.data["conversation_id"].as_i64(),
+                            request.data["after_message_id"].as_i64(),
+                        ) {
+                            let limit = request.data["limit"]
+                                .as_i64()
+                                .map(|limit| limit as i32)
+                                .or(Some(DEFAULT_SYNC_LIMIT));
+
+                            match chat_server_handler
+                                .sync_messages(
+                                    user_id,
+                                    conversation_id as i32,
+                                    after_message_id,
+                                    limit,
+                                )
+                                .await
+                            {
+                                Ok(result) => {
+                                    response.status = "synced".to_string();
+                                    response.data = serde_json::json!(result);
+                                }
+                                Err(e) => {
+                                    response.data = serde_json::json!({"message": e.to_string()})
+                                }
+                            }
+                        } else {
+                            response.data = serde_json::json!({"message": "Data must include conversation_id and after_message_id"});
+                        }
+                    }
+
+                    Event::Presence => {
+                        response.event = Event::Presence;
+
+                        match request.data.get("state").and_then(|state| {
+                            serde_json::from_value::<ActivityState>(state.clone()).ok()
+                        }) {
+                            Some(state) => {
+                                match chat_server_handler
+                                    .set_activity(user_id, conn_id, state)
+                                    .await
+                                {
+                                    Ok(_) => {
+                                        response.status = state.to_string();
+                                    }
+                                    Err(e) => {
+                                        response.data =
+                                            serde_json::json!({"message": e.to_string()})
+                                    }
+                                }
+                            }
+                            None => {
+                                response.data = serde_json::json!({"message": "Data must include a valid state"});
                             }
-                            Err(e) => response.data = serde_json::json!({"message": e.to_string()}),
                         }
                     }
 
@@ -259,14 +484,40 @@ impl WSHandler {
                 }
             }
             Err(e) => {
-                log::error!("Text error: {e}");
-                response.data = serde_json::json!({"message": "Invalid JSON format"});
+                log::error!("Request decode error: {e}");
+                response.data =
+                    serde_json::json!({"message": format!("Invalid request format - {e}")});
             }
         }
-        let _ = session.text(serde_json::json!(response).to_string()).await;
+        Self::send_response(session, protocol, &response).await;
+    }
+
+    async fn process_receive_message(
+        session: &mut actix_ws::Session,
+        protocol: &WsProtocol,
+        msg: &str,
+    ) {
+        match protocol.transcode(msg) {
+            Ok(frame) => Self::send_frame(session, frame).await,
+            Err(e) => log::error!("Encode broadcast message error: {e}"),
+        }
+    }
+
+    async fn send_response(
+        session: &mut actix_ws::Session,
+        protocol: &WsProtocol,
+        response: &WSResponse,
+    ) {
+        match protocol.encode(response) {
+            Ok(frame) => Self::send_frame(session, frame).await,
+            Err(e) => log::error!("Encode response error: {e}"),
+        }
     }
 
-    async fn process_receive_message(session: &mut actix_ws::Session, msg: &str) {
-        session.text(msg).await.unwrap();
+    async fn send_frame(session: &mut actix_ws::Session, frame: Frame) {
+        let _ = match frame {
+            Frame::Text(text) => session.text(text).await,
+            Frame::Binary(bytes) => session.binary(bytes).await,
+        };
     }
 }